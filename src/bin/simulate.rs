@@ -0,0 +1,74 @@
+//! Simulates a bunch of games without a window and prints their outcomes.
+//!
+//! Usage: `cargo run --release --bin simulate -- [games] [max-seconds]
+//! [max-waves] [first-seed]`
+
+use std::env;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use unfair_advantage::game::GameOutcome;
+use unfair_advantage::headless::{self, HeadlessConfig};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+
+    let games: u64 = arg(&mut args, "games", 10)?;
+    let max_seconds: f32 = arg(&mut args, "max-seconds", 600.0)?;
+    let max_waves: usize = arg(&mut args, "max-waves", 10)?;
+    let first_seed: u64 = arg(&mut args, "first-seed", 0)?;
+
+    let outcomes = (first_seed..first_seed + games)
+        .map(|seed| {
+            let outcome = headless::simulate(HeadlessConfig {
+                seed,
+                max_seconds,
+                max_waves,
+            });
+
+            println!("{}", outcome);
+
+            outcome
+        })
+        .collect::<Vec<_>>();
+
+    let count = |result| {
+        outcomes
+            .iter()
+            .filter(|outcome| outcome.result == Some(result))
+            .count()
+    };
+
+    let avg_waves = outcomes
+        .iter()
+        .map(|outcome| outcome.waves_survived as f32)
+        .sum::<f32>()
+        / outcomes.len().max(1) as f32;
+
+    println!(
+        "games={} victories={} defeats={} avg_waves_survived={:.2}",
+        outcomes.len(),
+        count(GameOutcome::Victory),
+        count(GameOutcome::Defeat),
+        avg_waves,
+    );
+
+    Ok(())
+}
+
+fn arg<T>(
+    args: &mut impl Iterator<Item = String>,
+    name: &str,
+    default: T,
+) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Some(arg) = args.next() {
+        arg.parse()
+            .with_context(|| format!("Couldn't parse `{}`: {}", name, arg))
+    } else {
+        Ok(default)
+    }
+}
@@ -0,0 +1,494 @@
+//! Copying & pasting configurations of lymph nodes.
+//!
+//! A blueprint remembers resources and links of a lymph node or of a whole
+//! chain of them, relative to the node it's been copied from; pasting it onto
+//! another node maps the rest of the blueprint onto the nearest player-owned
+//! nodes and re-targets the links accordingly.
+//!
+//! Named blueprints are kept locally, in `blueprints.ron`, so that they can
+//! be reused across games.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::level::LevelPoint;
+use crate::systems::bio::{LymphNode, LymphNodeResource};
+
+const BLUEPRINTS_PATH: &str = "blueprints.ron";
+
+/// How far (in tiles) a lymph node can be from where the blueprint expects it
+/// to be, and still get the blueprint's node pasted onto it
+const MAX_PASTE_DISTANCE: i32 = 4;
+
+pub struct BlueprintsPlugin;
+
+impl Plugin for BlueprintsPlugin {
+    fn build(&self, app: &mut App) {
+        let blueprints = Blueprints::load().unwrap_or_else(|err| {
+            error!("{:?}", err);
+            Blueprints::default()
+        });
+
+        app.insert_resource(blueprints);
+    }
+}
+
+#[derive(Default)]
+pub struct Blueprints {
+    /// Most recently copied blueprint
+    pub clipboard: Option<Blueprint>,
+
+    saved: Vec<Blueprint>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub name: String,
+
+    /// Copied nodes; the first one is the node the blueprint's been copied
+    /// from, i.e. the one that gets matched with the node it's pasted onto
+    nodes: Vec<BlueprintNode>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BlueprintNode {
+    /// Position relative to the blueprint's first node
+    offset: [i32; 2],
+
+    #[serde(default)]
+    targets: BlueprintTargets,
+
+    resource: Option<LymphNodeResource>,
+
+    /// Target saved before lymph nodes could target more than one node; it
+    /// gets converted into `Self::targets` when the blueprint is loaded
+    #[serde(default, skip_serializing)]
+    target: Option<LegacyBlueprintTarget>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum BlueprintTargets {
+    /// Pasting leaves the node's targets as they are - used for nodes with
+    /// links that lead outside the blueprint
+    Keep,
+
+    /// Indices of the targeted nodes (within `Blueprint::nodes`); empty for
+    /// nodes that spawn their products outside
+    Nodes(Vec<usize>),
+}
+
+impl Default for BlueprintTargets {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+enum LegacyBlueprintTarget {
+    Keep,
+    Outside,
+    Node(usize),
+}
+
+impl From<LegacyBlueprintTarget> for BlueprintTargets {
+    fn from(target: LegacyBlueprintTarget) -> Self {
+        match target {
+            LegacyBlueprintTarget::Keep => Self::Keep,
+            LegacyBlueprintTarget::Outside => Self::Nodes(vec![]),
+            LegacyBlueprintTarget::Node(idx) => Self::Nodes(vec![idx]),
+        }
+    }
+}
+
+/// Configuration a blueprint assigns to a single lymph node
+#[derive(Clone, Debug)]
+pub struct PastedLymphNode {
+    pub entity: Entity,
+    pub resource: Option<LymphNodeResource>,
+
+    /// `None` stands for keeping the node's current targets
+    pub targets: Option<Vec<Entity>>,
+}
+
+impl Blueprints {
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            clipboard: None,
+            saved: read_blueprints(Path::new(BLUEPRINTS_PATH))?,
+        })
+    }
+
+    pub fn saved(&self) -> &[Blueprint] {
+        &self.saved
+    }
+
+    /// Saves the clipboard under given name, replacing the blueprint that's
+    /// been saved under it before (if any).
+    pub fn save(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            bail!("Blueprint needs a name");
+        }
+
+        let mut blueprint = self
+            .clipboard
+            .clone()
+            .context("Nothing has been copied yet")?;
+
+        blueprint.name = name.into();
+
+        if let Some(saved) =
+            self.saved.iter_mut().find(|saved| saved.name == name)
+        {
+            *saved = blueprint;
+        } else {
+            self.saved.push(blueprint);
+        }
+
+        write_blueprints(Path::new(BLUEPRINTS_PATH), &self.saved)
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Result<()> {
+        if idx < self.saved.len() {
+            self.saved.remove(idx);
+        }
+
+        write_blueprints(Path::new(BLUEPRINTS_PATH), &self.saved)
+    }
+}
+
+impl Blueprint {
+    /// Copies configuration of given node or, if `chain` is set, of the whole
+    /// chain it's part of (i.e. of all the nodes linked with it, directly or
+    /// not).
+    ///
+    /// `nodes` should contain just the player-owned nodes.
+    pub fn copy<'a>(
+        root: Entity,
+        nodes: impl IntoIterator<Item = (Entity, &'a LymphNode)>,
+        chain: bool,
+    ) -> Option<Self> {
+        let nodes: BTreeMap<_, _> = nodes.into_iter().collect();
+        let root_node = nodes.get(&root)?;
+
+        let mut entities = vec![root];
+
+        if chain {
+            let mut visited = BTreeSet::from([root]);
+            let mut idx = 0;
+
+            while let Some(&entity) = entities.get(idx) {
+                let node = nodes[&entity];
+
+                let linked = node
+                    .parents
+                    .iter()
+                    .chain(&node.targets)
+                    .copied()
+                    .filter(|linked| nodes.contains_key(linked));
+
+                for linked in linked {
+                    if visited.insert(linked) {
+                        entities.push(linked);
+                    }
+                }
+
+                idx += 1;
+            }
+        }
+
+        let nodes = entities
+            .iter()
+            .map(|entity| {
+                let node = nodes[entity];
+
+                let targets = node
+                    .targets
+                    .iter()
+                    .map(|&target| {
+                        entities.iter().position(|&entity| entity == target)
+                    })
+                    .collect::<Option<_>>()
+                    .map_or(BlueprintTargets::Keep, BlueprintTargets::Nodes);
+
+                BlueprintNode {
+                    offset: [
+                        node.pos.x - root_node.pos.x,
+                        node.pos.y - root_node.pos.y,
+                    ],
+                    targets,
+                    resource: node.resource,
+                    target: None,
+                }
+            })
+            .collect();
+
+        Some(Self {
+            name: Default::default(),
+            nodes,
+        })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Maps the blueprint onto lymph nodes, starting from `root`; returns the
+    /// configuration each of the affected nodes should get.
+    ///
+    /// `nodes` should contain just the player-owned nodes.
+    pub fn paste<'a>(
+        &self,
+        root: Entity,
+        nodes: impl IntoIterator<Item = (Entity, &'a LymphNode)>,
+    ) -> Result<Vec<PastedLymphNode>> {
+        let nodes: Vec<_> = nodes.into_iter().collect();
+
+        let root_pos = nodes
+            .iter()
+            .find(|(entity, _)| *entity == root)
+            .map(|(_, node)| node.pos)
+            .context("Blueprints can be pasted only onto your lymph nodes")?;
+
+        let mut used = BTreeSet::new();
+        let mut entities = Vec::new();
+
+        for (idx, bp_node) in self.nodes.iter().enumerate() {
+            let pos = LevelPoint::new(
+                root_pos.x + bp_node.offset[0],
+                root_pos.y + bp_node.offset[1],
+            );
+
+            let entity = if idx == 0 {
+                root
+            } else {
+                nodes
+                    .iter()
+                    .filter(|(entity, _)| {
+                        *entity != root && !used.contains(entity)
+                    })
+                    .map(|(entity, node)| (*entity, distance(node.pos, pos)))
+                    .filter(|(_, distance)| *distance <= MAX_PASTE_DISTANCE)
+                    .min_by_key(|(_, distance)| *distance)
+                    .map(|(entity, _)| entity)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "There's no free lymph node of yours near [{}, {}]",
+                            pos.x,
+                            pos.y
+                        )
+                    })?
+            };
+
+            used.insert(entity);
+            entities.push(entity);
+        }
+
+        Ok(self
+            .nodes
+            .iter()
+            .zip(&entities)
+            .map(|(bp_node, &entity)| PastedLymphNode {
+                entity,
+                resource: bp_node.resource,
+                targets: match &bp_node.targets {
+                    BlueprintTargets::Keep => None,
+                    BlueprintTargets::Nodes(idxs) => idxs
+                        .iter()
+                        .map(|&idx| entities.get(idx).copied())
+                        .collect(),
+                },
+            })
+            .collect())
+    }
+}
+
+fn read_blueprints(path: &Path) -> Result<Vec<Blueprint>> {
+    if !path.exists() {
+        return Ok(Default::default());
+    }
+
+    let blueprints = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+    let mut blueprints: Vec<Blueprint> = ron::from_str(&blueprints)
+        .with_context(|| format!("Couldn't load `{}`", path.display()))?;
+
+    for node in blueprints.iter_mut().flat_map(|bp| &mut bp.nodes) {
+        if let Some(target) = node.target.take() {
+            node.targets = target.into();
+        }
+    }
+
+    Ok(blueprints)
+}
+
+fn write_blueprints(path: &Path, blueprints: &[Blueprint]) -> Result<()> {
+    let blueprints = ron::ser::to_string_pretty(blueprints, Default::default())
+        .context("Couldn't serialize blueprints")?;
+
+    fs::write(path, blueprints)
+        .with_context(|| format!("Couldn't write `{}`", path.display()))
+}
+
+/// Returns the Chebyshev distance between given points, i.e. the number of
+/// tiles one has to walk (diagonally, too) to get from one to another.
+fn distance(a: LevelPoint, b: LevelPoint) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::systems::bio::{Body, LymphNodeFlow, LymphNodeState};
+
+    fn node(x: i32, y: i32, targets: Vec<Entity>) -> LymphNode {
+        LymphNode {
+            pos: LevelPoint::new(x, y),
+            resource: None,
+            targets,
+            product: None,
+            parents: vec![],
+            warning: None,
+            diagnostic: None,
+            state: LymphNodeState {
+                is_paused: false,
+                is_awaiting_resources: false,
+            },
+            production: Default::default(),
+            production_tt: 0.0,
+            stock: Default::default(),
+            flow: LymphNodeFlow::Idle,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = env::temp_dir()
+            .join(format!("blueprints-round-trip-{}.ron", process::id()));
+
+        let (a, b, c) = (
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        );
+
+        // `a` sends its products into `b`, which spawns them outside; `c`
+        // isn't linked with any of them, so it doesn't get copied
+        let mut node_a = node(0, 0, vec![b]);
+        let mut node_b = node(2, 1, vec![]);
+        let node_c = node(-5, 0, vec![]);
+
+        node_a.resource = Some(LymphNodeResource::Body(Body::Circle));
+        node_b.parents.push(a);
+
+        let mut blueprint = Blueprint::copy(
+            a,
+            [(a, &node_a), (b, &node_b), (c, &node_c)],
+            true,
+        )
+        .unwrap();
+
+        blueprint.name = "chain".into();
+
+        assert_eq!(2, blueprint.node_count());
+
+        write_blueprints(&path, &[blueprint]).unwrap();
+
+        let blueprints = read_blueprints(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(1, blueprints.len());
+        assert_eq!("chain", blueprints[0].name);
+
+        // Pasted somewhere else, the blueprint should pick the free node that's
+        // closest to where `b` is expected to be
+        let (d, e, f) = (
+            Entity::from_raw(3),
+            Entity::from_raw(4),
+            Entity::from_raw(5),
+        );
+
+        let node_d = node(20, 20, vec![]);
+        let node_e = node(23, 21, vec![]);
+        let node_f = node(19, 24, vec![]);
+
+        let pasted = blueprints[0]
+            .paste(d, [(d, &node_d), (e, &node_e), (f, &node_f)])
+            .unwrap();
+
+        assert_eq!(2, pasted.len());
+
+        assert_eq!(d, pasted[0].entity);
+        assert_eq!(
+            Some(LymphNodeResource::Body(Body::Circle)),
+            pasted[0].resource
+        );
+        assert_eq!(Some(vec![e]), pasted[0].targets);
+
+        assert_eq!(e, pasted[1].entity);
+        assert_eq!(None, pasted[1].resource);
+        assert_eq!(Some(vec![]), pasted[1].targets);
+    }
+
+    #[test]
+    fn paste_without_free_nodes() {
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+
+        let node_a = node(0, 0, vec![b]);
+        let node_b = node(2, 1, vec![]);
+
+        let blueprint =
+            Blueprint::copy(a, [(a, &node_a), (b, &node_b)], true).unwrap();
+
+        let err = blueprint.paste(a, [(a, &node_a)]).unwrap_err();
+
+        assert_eq!(
+            "There's no free lymph node of yours near [2, 1]",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn legacy_targets() {
+        let path = env::temp_dir()
+            .join(format!("blueprints-legacy-{}.ron", process::id()));
+
+        fs::write(
+            &path,
+            "[(
+                name: \"chain\",
+                nodes: [
+                    (offset: (0, 0), target: Node(1), resource: None),
+                    (offset: (2, 1), target: Outside, resource: None),
+                    (offset: (4, 2), target: Keep, resource: None),
+                ],
+            )]",
+        )
+        .unwrap();
+
+        let blueprints = read_blueprints(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        let targets: Vec<_> = blueprints[0]
+            .nodes
+            .iter()
+            .map(|node| match &node.targets {
+                BlueprintTargets::Keep => None,
+                BlueprintTargets::Nodes(idxs) => Some(idxs.clone()),
+            })
+            .collect();
+
+        assert_eq!(vec![Some(vec![1]), Some(vec![]), None], targets);
+    }
+}
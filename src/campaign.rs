@@ -0,0 +1,243 @@
+//! Chains hand-authored levels (see `levels/campaign.toml`) into a campaign
+//! and keeps track of which of them the player has already completed.
+//!
+//! Completed levels are remembered locally, in `progress.toml`; a level gets
+//! unlocked once the one before it is completed.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::{env, fs};
+
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{GameOutcome, GameState};
+
+const CAMPAIGN_PATH: &str = "levels/campaign.toml";
+const PROGRESS_PATH: &str = "progress.toml";
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        let mut campaign = Campaign::load().unwrap_or_else(|err| {
+            error!("{:?}", err);
+            Campaign::default()
+        });
+
+        // When a campaign's level gets launched through `LEVEL`, completing
+        // it should count as well
+        if let Ok(path) = env::var("LEVEL") {
+            campaign.current =
+                campaign.levels.iter().position(|level| level.path == path);
+        }
+
+        app.insert_resource(campaign).add_system(record_victory);
+    }
+}
+
+#[derive(Default)]
+pub struct Campaign {
+    pub levels: Vec<CampaignLevel>,
+
+    /// Index of the level that's being played at the moment; `None` when
+    /// playing a level from outside the campaign (e.g. a generated one)
+    pub current: Option<usize>,
+
+    completed: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct CampaignLevel {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct CampaignDef {
+    level: Vec<CampaignLevel>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct ProgressDef {
+    /// Paths of the completed levels
+    completed: BTreeSet<String>,
+}
+
+impl Campaign {
+    pub fn load() -> Result<Self> {
+        let campaign = fs::read_to_string(CAMPAIGN_PATH)
+            .with_context(|| format!("Couldn't read `{}`", CAMPAIGN_PATH))?;
+
+        let campaign: CampaignDef = toml::from_str(&campaign)
+            .with_context(|| format!("Couldn't load `{}`", CAMPAIGN_PATH))?;
+
+        // Broken progress shouldn't prevent the player from playing
+        let progress =
+            read_progress(Path::new(PROGRESS_PATH)).unwrap_or_else(|err| {
+                error!("{:?}", err);
+                ProgressDef::default()
+            });
+
+        Ok(Self {
+            levels: campaign.level,
+            current: None,
+            completed: progress.completed,
+        })
+    }
+
+    pub fn is_completed(&self, idx: usize) -> bool {
+        self.levels
+            .get(idx)
+            .map_or(false, |level| self.completed.contains(&level.path))
+    }
+
+    pub fn is_unlocked(&self, idx: usize) -> bool {
+        idx == 0 || self.is_completed(idx - 1)
+    }
+
+    /// Returns index of the level that comes after the current one, if it's
+    /// been unlocked
+    pub fn next(&self) -> Option<usize> {
+        let idx = self.current? + 1;
+
+        (idx < self.levels.len() && self.is_unlocked(idx)).then(|| idx)
+    }
+
+    fn complete(&mut self, idx: usize) -> Result<()> {
+        let level = &self.levels[idx];
+
+        if self.completed.insert(level.path.clone()) {
+            info!("Level completed: {}", level.name);
+
+            write_progress(
+                Path::new(PROGRESS_PATH),
+                &ProgressDef {
+                    completed: self.completed.clone(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_progress(path: &Path) -> Result<ProgressDef> {
+    if !path.exists() {
+        return Ok(Default::default());
+    }
+
+    let progress = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+    toml::from_str(&progress)
+        .with_context(|| format!("Couldn't load `{}`", path.display()))
+}
+
+fn write_progress(path: &Path, progress: &ProgressDef) -> Result<()> {
+    let progress = toml::to_string(progress)
+        .context("Couldn't serialize campaign's progress")?;
+
+    fs::write(path, progress)
+        .with_context(|| format!("Couldn't write `{}`", path.display()))
+}
+
+fn record_victory(state: Res<GameState>, mut campaign: ResMut<Campaign>) {
+    if !state.is_changed() || state.outcome != Some(GameOutcome::Victory) {
+        return;
+    }
+
+    if let Some(idx) = campaign.current {
+        if let Err(err) = campaign.complete(idx) {
+            error!("{:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    fn campaign() -> Campaign {
+        let levels = ["1", "2", "3"]
+            .into_iter()
+            .map(|name| CampaignLevel {
+                name: name.into(),
+                path: format!("levels/{}.toml", name),
+            })
+            .collect();
+
+        Campaign {
+            levels,
+            current: None,
+            completed: Default::default(),
+        }
+    }
+
+    #[test]
+    fn shipped_campaign() {
+        let campaign: CampaignDef =
+            toml::from_str(include_str!("../levels/campaign.toml")).unwrap();
+
+        for level in campaign.level {
+            assert!(Path::new(&level.path).exists(), "{}", level.path);
+        }
+    }
+
+    #[test]
+    fn progression() {
+        let mut campaign = campaign();
+
+        campaign.current = Some(0);
+
+        assert!(campaign.is_unlocked(0));
+        assert!(!campaign.is_unlocked(1));
+        assert_eq!(None, campaign.next());
+
+        campaign.completed.insert("levels/1.toml".into());
+
+        assert!(campaign.is_completed(0));
+        assert!(campaign.is_unlocked(1));
+        assert!(!campaign.is_unlocked(2));
+        assert_eq!(Some(1), campaign.next());
+
+        campaign.current = Some(2);
+
+        assert_eq!(None, campaign.next());
+    }
+
+    #[test]
+    fn progress_round_trip() {
+        let path = env::temp_dir()
+            .join(format!("progress-round-trip-{}.toml", process::id()));
+
+        assert!(read_progress(&path).unwrap().completed.is_empty());
+
+        let mut campaign = campaign();
+
+        campaign.completed.insert("levels/1.toml".into());
+        campaign.completed.insert("levels/2.toml".into());
+
+        write_progress(
+            &path,
+            &ProgressDef {
+                completed: campaign.completed.clone(),
+            },
+        )
+        .unwrap();
+
+        campaign.completed = read_progress(&path).unwrap().completed;
+        fs::remove_file(&path).unwrap();
+
+        assert!(campaign.is_completed(0));
+        assert!(campaign.is_completed(1));
+        assert!(!campaign.is_completed(2));
+        assert!(campaign.is_unlocked(2));
+    }
+}
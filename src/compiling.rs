@@ -1,16 +1,28 @@
 mod compiler;
+mod diagnostic;
+mod recipes;
 mod recompile_event;
 
 use bevy::prelude::*;
 
-use self::compiler::*;
+pub use self::compiler::*;
+pub use self::diagnostic::*;
+pub use self::recipes::Recipes;
 pub use self::recompile_event::RecompileEvent;
 
 pub struct CompilingPlugin;
 
 impl Plugin for CompilingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<RecompileEvent>()
+        let recipes = Recipes::load().unwrap_or_else(|err| {
+            error!("{:?}", err);
+            warn!("Falling back to the built-in recipes");
+
+            Recipes::builtin()
+        });
+
+        app.insert_resource(recipes)
+            .add_event::<RecompileEvent>()
             .add_system(recompile_event::compile)
             .add_system(recompile_event::link);
     }
@@ -21,7 +33,9 @@ pub enum CompilationWarning {
     NodeIsPaused,
     NodeIsAwaitingResources,
     NodeHasNoProduct,
+    NodeHasConflictingInputs,
     NodeHasNoChild,
+    NodeIsInCycle,
     Infected,
 }
 
@@ -35,7 +49,9 @@ impl CompilationWarning {
                 "[!] Node does not produce anything because its parent is paused."
             }
             Self::NodeHasNoProduct => "[!] Node does not produce anything because it is misconfigured (i.e. it uses an illegal combination of resources).",
+            Self::NodeHasConflictingInputs => "[!] Node does not produce anything because its inputs cannot be merged (i.e. more than one leukocyte gets sent into it).",
             Self::NodeHasNoChild => "[!] Node does not produce anything because it must be linked with another node first.",
+            Self::NodeIsInCycle => "[!] Node does not produce anything because its links form a loop (i.e. its product would end up being sent back into it).",
             Self::Infected => "[!] Node is infected and cannot be controlled.",
         }
     }
@@ -46,9 +62,10 @@ impl CompilationWarning {
             Self::NodeIsAwaitingResources => {
                 "lymph-node.state.awaiting-resources.png"
             }
-            Self::NodeHasNoProduct | Self::NodeHasNoChild => {
-                "lymph-node.state.error.png"
-            }
+            Self::NodeHasNoProduct
+            | Self::NodeHasConflictingInputs
+            | Self::NodeHasNoChild
+            | Self::NodeIsInCycle => "lymph-node.state.error.png",
             Self::Infected => "lymph-node.state.infected.png",
         }
     }
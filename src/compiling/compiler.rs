@@ -1,29 +1,200 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
-use bevy::prelude::{warn, Entity};
+use bevy::prelude::Entity;
 use itertools::Itertools;
 
+use super::{
+    describe_product, describe_resource, CompilationDiagnostic, DiagnosticStep,
+    Recipes,
+};
+use crate::level::LevelPoint;
 use crate::systems::bio::{
-    Leukocyte, LeukocyteKind, LeukocyteProps, LymphNode, LymphNodeProduct,
-    LymphNodeResource, LymphNodeState, LymphNodeTarget, Pathogen, Protein,
+    Antigen, AntigenBinder, LeukocyteKind, LymphNode, LymphNodeProduct,
+    LymphNodeResource, LymphNodeState, Pathogen,
 };
 
+/// Compiles lymph nodes incrementally: results of the previous compilation are
+/// kept around, so that only nodes that have changed since then (and everything
+/// downstream of them) get compiled again.
 #[derive(Default)]
 pub struct Compiler {
+    /// Lymph nodes, as they were during the previous compilation
     nodes: BTreeMap<Entity, CachedLymphNode>,
-    parents: BTreeMap<Entity, Entity>,
+    compiled: BTreeMap<Entity, CompiledLymphNode>,
+
+    /// Antigens for which killers get compiled into memory cells (see
+    /// `ImmuneMemory`)
+    memory_cells: BTreeSet<Antigen>,
 }
 
+/// Part of a lymph node that affects the outcome of its compilation
+#[derive(Clone, Debug, PartialEq)]
 struct CachedLymphNode {
+    pos: (i32, i32),
     resource: Option<LymphNodeResource>,
-    state: LymphNodeState,
+    children: Vec<Entity>,
+    is_paused: bool,
     pathogen: Option<Pathogen>,
 }
 
-const MAX_DEPTH: u8 = 128;
+#[derive(Clone, Debug)]
+pub struct CompiledLymphNode {
+    pub product: Option<LymphNodeProduct>,
+
+    /// Lymph nodes linked into this one, in the order their products get
+    /// merged
+    pub parents: Vec<Entity>,
+
+    pub state: LymphNodeState,
+
+    /// Whether the node's inputs cannot be merged (e.g. because there are
+    /// two leukocytes among them)
+    pub has_conflicting_inputs: bool,
+
+    /// Whether the node's links lead back to it
+    pub is_in_cycle: bool,
+
+    /// Explanation of why the node doesn't produce anything
+    pub diagnostic: Option<CompilationDiagnostic>,
+}
+
+/// Returned when node's inputs cannot be merged together; inputs are referred
+/// to by their indices
+enum MergeError {
+    /// There's nothing to merge
+    NoInputs,
+
+    /// Input is a pathogen
+    Pathogen(usize),
+
+    /// There's more than one leukocyte among the inputs
+    ConflictingInputs(Vec<usize>),
+
+    /// There's no recipe for any group of the resources
+    NoRecipe(Vec<usize>),
+
+    /// There's no modifier for the resource and leukocyte of given kind
+    NoModifier(usize, LeukocyteKind),
+}
 
 impl Compiler {
-    pub fn add(&mut self, entity: Entity, node: &LymphNode) {
+    /// Compiles given lymph nodes and returns the ones that might've changed
+    /// since the previous call - i.e. the modified ones and everything that's
+    /// downstream of them.
+    pub fn compile<'a>(
+        &mut self,
+        recipes: &Recipes,
+        nodes: impl IntoIterator<Item = (Entity, &'a LymphNode)>,
+    ) -> BTreeMap<Entity, CompiledLymphNode> {
+        let nodes: BTreeMap<_, _> = nodes
+            .into_iter()
+            .map(|(entity, node)| (entity, CachedLymphNode::new(node)))
+            .collect();
+
+        let affected = self.affected(&nodes);
+
+        // Products get merged in the order of their nodes' positions, so that
+        // the outcome doesn't depend on the order in which the nodes have been
+        // linked (or spawned)
+        let parents = nodes
+            .iter()
+            .flat_map(|(&entity, node)| {
+                node.children.iter().map(move |&child| (child, entity))
+            })
+            .into_group_map()
+            .into_iter()
+            .map(|(child, parents)| {
+                let parents = parents
+                    .into_iter()
+                    .sorted_by_key(|parent| nodes[parent].pos)
+                    .collect();
+
+                (child, parents)
+            })
+            .collect();
+
+        let mut compilation = Compilation {
+            recipes,
+            memory_cells: &self.memory_cells,
+            nodes: &nodes,
+            parents: &parents,
+            affected: &affected,
+            in_cycle: &find_cycles(&nodes, &affected),
+            previous: &self.compiled,
+            results: Default::default(),
+        };
+
+        for &entity in &affected {
+            compilation.resolve(entity);
+        }
+
+        let results = compilation.results;
+
+        self.compiled.retain(|entity, _| nodes.contains_key(entity));
+        self.compiled.extend(results.clone());
+        self.nodes = nodes;
+
+        results
+    }
+
+    /// Updates antigens for which killers get compiled into memory cells;
+    /// when they change, the next compilation starts from scratch.
+    pub fn remember(&mut self, memory_cells: BTreeSet<Antigen>) {
+        if self.memory_cells != memory_cells {
+            self.memory_cells = memory_cells;
+            self.nodes.clear();
+        }
+    }
+
+    /// Returns the outcome of compiling given node during the most recent
+    /// compilation.
+    pub fn get(&self, entity: Entity) -> Option<&CompiledLymphNode> {
+        self.compiled.get(&entity)
+    }
+
+    /// Returns nodes that have to be compiled again: the ones that have been
+    /// added or modified since the previous compilation, the ones they have
+    /// been (or are now) linked into, and everything downstream of those.
+    fn affected(
+        &self,
+        nodes: &BTreeMap<Entity, CachedLymphNode>,
+    ) -> BTreeSet<Entity> {
+        let mut pending = Vec::new();
+
+        for (&entity, node) in nodes {
+            let prev = self.nodes.get(&entity);
+
+            if prev == Some(node) {
+                continue;
+            }
+
+            pending.push(entity);
+            pending.extend(&node.children);
+            pending.extend(prev.into_iter().flat_map(|prev| &prev.children));
+        }
+
+        for (entity, prev) in &self.nodes {
+            if !nodes.contains_key(entity) {
+                pending.extend(&prev.children);
+            }
+        }
+
+        let mut affected = BTreeSet::new();
+
+        while let Some(entity) = pending.pop() {
+            if let Some(node) = nodes.get(&entity) {
+                if affected.insert(entity) {
+                    pending.extend(&node.children);
+                }
+            }
+        }
+
+        affected
+    }
+}
+
+impl CachedLymphNode {
+    fn new(node: &LymphNode) -> Self {
         let pathogen =
             if let Some(LymphNodeProduct::Pathogen(pathogen)) = node.product {
                 Some(pathogen)
@@ -31,162 +202,660 @@ impl Compiler {
                 None
             };
 
-        self.nodes.insert(
+        Self {
+            pos: (node.pos.x, node.pos.y),
+            resource: node.resource,
+            children: node.targets.clone(),
+            is_paused: node.state.is_paused,
+            pathogen,
+        }
+    }
+}
+
+/// Returns those of the affected nodes whose links lead back to them.
+///
+/// Everything downstream of an affected node is affected as well, so a loop
+/// passing through any of the affected nodes consists of affected nodes only -
+/// it's enough to follow the links from each of them (without leaving the
+/// affected nodes) and see whether we get back to where we've started.
+fn find_cycles(
+    nodes: &BTreeMap<Entity, CachedLymphNode>,
+    affected: &BTreeSet<Entity>,
+) -> BTreeSet<Entity> {
+    affected
+        .iter()
+        .copied()
+        .filter(|&start| {
+            let mut visited = BTreeSet::new();
+            let mut pending = nodes[&start].children.clone();
+
+            while let Some(entity) = pending.pop() {
+                if entity == start {
+                    return true;
+                }
+
+                if affected.contains(&entity) && visited.insert(entity) {
+                    pending.extend(&nodes[&entity].children);
+                }
+            }
+
+            false
+        })
+        .collect()
+}
+
+/// State of a single compilation, i.e. of resolving the affected nodes
+struct Compilation<'a> {
+    recipes: &'a Recipes,
+    memory_cells: &'a BTreeSet<Antigen>,
+    nodes: &'a BTreeMap<Entity, CachedLymphNode>,
+    parents: &'a BTreeMap<Entity, Vec<Entity>>,
+    affected: &'a BTreeSet<Entity>,
+    in_cycle: &'a BTreeSet<Entity>,
+    previous: &'a BTreeMap<Entity, CompiledLymphNode>,
+    results: BTreeMap<Entity, CompiledLymphNode>,
+}
+
+impl Compilation<'_> {
+    fn resolve(&mut self, entity: Entity) {
+        if self.results.contains_key(&entity) {
+            return;
+        }
+
+        let nodes = self.nodes;
+        let node = &nodes[&entity];
+        let parents = self.parents.get(&entity).cloned().unwrap_or_default();
+        let is_in_cycle = self.in_cycle.contains(&entity);
+
+        let mut state = LymphNodeState {
+            is_paused: node.is_paused,
+            is_awaiting_resources: false,
+        };
+
+        let product = if is_in_cycle {
+            // Node in a cycle would depend on itself, so there's no point in
+            // looking at its parents
+            Err(None)
+        } else {
+            let mut inputs = Vec::new();
+
+            for &parent in &parents {
+                let (product, parent_state) = self.get(parent);
+
+                state.is_awaiting_resources |= parent_state.is_paused
+                    || parent_state.is_awaiting_resources;
+
+                inputs.extend(product.map(|product| (parent, product)));
+            }
+
+            if let Some(pathogen) = node.pathogen {
+                Ok(LymphNodeProduct::Pathogen(pathogen))
+            } else {
+                // Node's own resource goes last, after all of the upstream
+                // products
+                inputs.extend(
+                    node.resource
+                        .map(|res| (entity, LymphNodeProduct::Resource(res))),
+                );
+
+                self.merge(&inputs).map_err(|err| Some((inputs, err)))
+            }
+        };
+
+        // Same order as the warnings (see `recompile_event::compile()`), so
+        // that the diagnostic explains the warning that's displayed
+        let diagnostic = if is_in_cycle {
+            Some(self.diagnose_cycle(entity))
+        } else if node.pathogen.is_some() || state.is_paused {
+            None
+        } else if state.is_awaiting_resources {
+            self.diagnose_paused_ancestor(entity)
+        } else if let Err(Some((inputs, err))) = &product {
+            Some(self.diagnose_merge(entity, inputs, err))
+        } else {
+            None
+        };
+
+        let has_conflicting_inputs = matches!(
+            &product,
+            Err(Some((_, MergeError::ConflictingInputs(_))))
+        );
+
+        self.results.insert(
             entity,
-            CachedLymphNode {
-                resource: node.resource,
-                state: LymphNodeState {
-                    is_paused: node.state.is_paused,
-                    is_awaiting_resources: false,
-                },
-                pathogen,
+            CompiledLymphNode {
+                product: product.ok(),
+                parents,
+                state,
+                has_conflicting_inputs,
+                is_in_cycle,
+                diagnostic,
             },
         );
-
-        if let LymphNodeTarget::LymphNode(child) = node.target {
-            self.parents.insert(child, entity);
-        }
     }
 
-    pub fn compile(
-        self,
-    ) -> BTreeMap<
-        Entity,
-        (Option<LymphNodeProduct>, Option<Entity>, LymphNodeState),
-    > {
-        self.nodes
-            .keys()
-            .cloned()
-            .collect_vec()
-            .into_iter()
-            .map(|entity| {
-                let product = self.resolve_product(0, entity);
-                let parent = self.parents.get(&entity).cloned();
-                let state = self.resolve_state(entity);
+    /// Returns product and state of given node, either resolving it (if it's
+    /// affected) or taking it from the previous compilation.
+    fn get(
+        &mut self,
+        entity: Entity,
+    ) -> (Option<LymphNodeProduct>, LymphNodeState) {
+        let node = if self.affected.contains(&entity) {
+            self.resolve(entity);
+            self.results.get(&entity)
+        } else {
+            self.previous.get(&entity)
+        };
 
-                (entity, (product, parent, state))
-            })
-            .collect()
+        if let Some(node) = node {
+            (node.product.clone(), node.state)
+        } else {
+            let state = LymphNodeState {
+                is_paused: false,
+                is_awaiting_resources: false,
+            };
+
+            (None, state)
+        }
     }
 
-    // TODO(pwy) add more combinations
-    // TODO(pwy) memoization would be nice
-    fn resolve_product(
+    fn merge(
         &self,
-        depth: u8,
-        entity: Entity,
-    ) -> Option<LymphNodeProduct> {
-        use {LymphNodeProduct as P, LymphNodeResource as R};
+        inputs: &[(Entity, LymphNodeProduct)],
+    ) -> Result<LymphNodeProduct, MergeError> {
+        let mut leukocytes = Vec::new();
+        let mut resources = Vec::new();
 
-        if depth > MAX_DEPTH {
-            return None;
-        }
+        for (idx, (_, input)) in inputs.iter().enumerate() {
+            match input {
+                LymphNodeProduct::Resource(res) => resources.push((idx, *res)),
 
-        let node = &self.nodes[&entity];
+                LymphNodeProduct::Leukocyte(cell) => {
+                    leukocytes.push((idx, cell.clone()))
+                }
 
-        if let Some(pathogen) = node.pathogen {
-            return Some(P::Pathogen(pathogen));
+                LymphNodeProduct::Pathogen(_) => {
+                    return Err(MergeError::Pathogen(idx));
+                }
+            }
         }
 
-        let lhs = self
-            .parents
-            .get(&entity)
-            .and_then(|&parent| self.resolve_product(depth + 1, parent));
+        let cell = match leukocytes.len() {
+            0 => {
+                match resources.len() {
+                    0 => return Err(MergeError::NoInputs),
+                    1 => return Ok(LymphNodeProduct::Resource(resources[0].1)),
+                    _ => (),
+                }
+
+                // Leukocyte is crafted out of the largest group of resources
+                // there's a recipe for (preferring the earliest resources),
+                // while the rest of them modifies it
+                let max_inputs = resources.len().min(Recipes::MAX_INPUTS);
 
-        let rhs = node.resource;
+                let crafted = (2..=max_inputs).rev().find_map(|size| {
+                    (0..resources.len()).combinations(size).find_map(|idxs| {
+                        let inputs = idxs
+                            .iter()
+                            .map(|&idx| resources[idx].1)
+                            .collect_vec();
 
-        let (lhs, rhs) = match (lhs, rhs) {
-            (None, None) => {
-                return None;
-            }
-            (None, Some(rhs)) => {
-                return Some(LymphNodeProduct::Resource(rhs));
+                        let cell = self.recipes.craft(&inputs)?;
+
+                        Some((idxs, cell))
+                    })
+                });
+
+                let (idxs, cell) = if let Some(crafted) = crafted {
+                    crafted
+                } else {
+                    return Err(MergeError::NoRecipe(
+                        resources.iter().map(|(idx, _)| *idx).collect(),
+                    ));
+                };
+
+                for idx in idxs.into_iter().rev() {
+                    resources.remove(idx);
+                }
+
+                let AntigenBinder(antigen) = cell.binder;
+
+                if cell.kind == LeukocyteKind::Killer
+                    && self.memory_cells.contains(&antigen)
+                {
+                    cell.into_memory_cell()
+                } else {
+                    cell
+                }
             }
-            (Some(lhs), None) => {
-                return Some(lhs);
+
+            1 => leukocytes.remove(0).1,
+
+            _ => {
+                return Err(MergeError::ConflictingInputs(
+                    leukocytes.iter().map(|(idx, _)| *idx).collect(),
+                ));
             }
-            (Some(lhs), Some(rhs)) => (lhs, rhs),
         };
 
-        match (lhs, rhs) {
-            (P::Resource(res1), res2) => {
-                if let (R::Antigen(binder), R::Body(body))
-                | (R::Body(body), R::Antigen(binder)) = (res1, res2)
+        resources
+            .into_iter()
+            .try_fold(cell, |cell, (idx, res)| {
+                let kind = cell.kind;
+
+                self.recipes
+                    .modify(cell, res)
+                    .ok_or(MergeError::NoModifier(idx, kind))
+            })
+            .map(LymphNodeProduct::Leukocyte)
+    }
+
+    fn diagnose_cycle(&self, entity: Entity) -> CompilationDiagnostic {
+        // Maps each visited node into the node it's been reached from, so that
+        // the shortest loop can be walked back once we get back to `entity`
+        let mut sources = BTreeMap::new();
+        let mut pending = VecDeque::from([entity]);
+
+        'search: while let Some(node) = pending.pop_front() {
+            for &child in &self.nodes[&node].children {
+                if sources.contains_key(&child)
+                    || !self.nodes.contains_key(&child)
                 {
-                    Some(P::Leukocyte(Leukocyte {
-                        body,
-                        binder,
-                        kind: LeukocyteKind::Killer,
-                        props: LeukocyteProps { hp: 1.0 },
-                        proteins: Default::default(),
-                    }))
-                } else {
-                    None
+                    continue;
                 }
-            }
 
-            (P::Leukocyte(mut cell), res) => match res {
-                R::Body(_) => {
-                    cell.props.hp += 0.5;
-                    Some(P::Leukocyte(cell))
+                sources.insert(child, node);
+
+                if child == entity {
+                    break 'search;
                 }
 
-                R::Antigen(_) => {
-                    cell.props.hp += 0.2;
-                    Some(P::Leukocyte(cell))
+                pending.push_back(child);
+            }
+        }
+
+        let mut steps = vec![self.step(entity, None, false)];
+        let mut current = entity;
+
+        while let Some(&source) = sources.get(&current) {
+            if source == entity {
+                break;
+            }
+
+            steps.push(self.step(source, None, false));
+            current = source;
+        }
+
+        steps.reverse();
+
+        CompilationDiagnostic {
+            steps,
+            reason: "Links form a loop, so this node's product would end up \
+                     being sent back into it."
+                .into(),
+            suggestion: "Unlink any of the nodes in the loop.".into(),
+        }
+    }
+
+    /// Explains which of the node's ancestors is paused, looking for the
+    /// closest one.
+    fn diagnose_paused_ancestor(
+        &self,
+        entity: Entity,
+    ) -> Option<CompilationDiagnostic> {
+        // Maps each visited ancestor into the node it's linked into, so that
+        // the path can be walked back once a paused ancestor is found
+        let mut children = BTreeMap::new();
+        let mut pending = VecDeque::from([entity]);
+
+        while let Some(node) = pending.pop_front() {
+            for &parent in self.parents.get(&node).into_iter().flatten() {
+                if parent == entity || children.contains_key(&parent) {
+                    continue;
                 }
 
-                R::Protein(prot) => {
-                    cell.proteins.push(prot);
+                children.insert(parent, node);
 
-                    match prot {
-                        Protein::Dumbbell => {
-                            cell.props.hp += 1.0;
-                            Some(P::Leukocyte(cell))
-                        }
+                if !self.nodes[&parent].is_paused {
+                    pending.push_back(parent);
+                    continue;
+                }
 
-                        Protein::Star => {
-                            cell.props.hp *= 2.0;
-                            Some(P::Leukocyte(cell))
-                        }
-                    }
+                let mut steps = vec![self.step(parent, None, true)];
+                let mut current = parent;
+
+                while let Some(&child) = children.get(&current) {
+                    steps.push(self.step(child, None, false));
+                    current = child;
                 }
-            },
 
-            (P::Pathogen(_), _) => {
-                warn!("Cannot compile with pathogens!");
-                None
+                let label = self.label(parent);
+
+                return Some(CompilationDiagnostic {
+                    steps,
+                    reason: format!(
+                        "{} is paused, so its products don't reach this node.",
+                        label
+                    ),
+                    suggestion: format!("Unpause {}.", label),
+                });
             }
         }
+
+        None
     }
 
-    fn resolve_state(&self, entity: Entity) -> LymphNodeState {
-        let mut state = self.nodes[&entity].state;
+    fn diagnose_merge(
+        &self,
+        entity: Entity,
+        inputs: &[(Entity, LymphNodeProduct)],
+        err: &MergeError,
+    ) -> CompilationDiagnostic {
+        let failing = match err {
+            MergeError::NoInputs => vec![],
+            MergeError::Pathogen(idx) | MergeError::NoModifier(idx, _) => {
+                vec![*idx]
+            }
+            MergeError::ConflictingInputs(idxs)
+            | MergeError::NoRecipe(idxs) => idxs.clone(),
+        };
 
-        {
-            let mut depth = 0;
-            let mut node = entity;
+        let steps = inputs
+            .iter()
+            .enumerate()
+            .map(|(idx, (node, input))| {
+                self.step(*node, Some(input.clone()), failing.contains(&idx))
+            })
+            .collect();
 
-            loop {
-                if depth > MAX_DEPTH {
-                    break;
-                }
+        // Node's own resource is the last input, so it's better described
+        // as "this node" than by its position
+        let label = |idx: usize| {
+            let node = inputs[idx].0;
 
-                if let Some(parent) = self.parents.get(&node) {
-                    node = *parent;
-                } else {
-                    break;
-                }
+            if node == entity {
+                "this node".to_string()
+            } else {
+                self.label(node)
+            }
+        };
 
-                if self.nodes[&node].state.is_paused {
-                    state.is_awaiting_resources = true;
-                    break;
-                }
+        let (reason, suggestion) = match err {
+            MergeError::NoInputs => (
+                "Node has no resource and no other node sends anything into \
+                 it."
+                .into(),
+                "Pick a resource or link another node into this one.".into(),
+            ),
+
+            MergeError::Pathogen(idx) => (
+                format!("{} sends a pathogen into this node.", label(*idx)),
+                format!("Unlink {}.", label(*idx)),
+            ),
 
-                depth += 1;
+            MergeError::ConflictingInputs(idxs) => (
+                format!(
+                    "Leukocytes from {} cannot be merged together.",
+                    idxs.iter().map(|&idx| label(idx)).join(" and ")
+                ),
+                "Leave only one of them linked into this node.".into(),
+            ),
+
+            MergeError::NoRecipe(idxs) => {
+                let resources = idxs
+                    .iter()
+                    .filter_map(|&idx| {
+                        if let LymphNodeProduct::Resource(res) = inputs[idx].1 {
+                            Some(res)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect_vec();
+
+                let has =
+                    |f: fn(&LymphNodeResource) -> bool| resources.iter().any(f);
+
+                let suggestion =
+                    if !has(|res| matches!(res, LymphNodeResource::Body(_))) {
+                        "Add a body - either as this node's resource or by \
+                     linking a node that provides one."
+                    } else if !has(|res| {
+                        matches!(res, LymphNodeResource::Antigen(_))
+                    }) {
+                        "Add an antigen - either as this node's resource or by \
+                     linking a node that provides one."
+                    } else {
+                        "Try a different combination of resources (see \
+                     `recipes.toml`)."
+                    };
+
+                (
+                    format!(
+                        "There's no recipe that turns {} into a leukocyte.",
+                        resources
+                            .iter()
+                            .map(|&res| describe_resource(res))
+                            .join(" + ")
+                    ),
+                    suggestion.into(),
+                )
             }
+
+            MergeError::NoModifier(idx, kind) => (
+                format!(
+                    "{} from {} cannot be applied to {:?} leukocyte.",
+                    describe_product(Some(&inputs[*idx].1)),
+                    label(*idx),
+                    kind
+                ),
+                format!("Change the resource of {} or unlink it.", label(*idx)),
+            ),
+        };
+
+        CompilationDiagnostic {
+            steps,
+            reason,
+            suggestion,
+        }
+    }
+
+    fn step(
+        &self,
+        entity: Entity,
+        input: Option<LymphNodeProduct>,
+        is_failing: bool,
+    ) -> DiagnosticStep {
+        let (x, y) = self.nodes[&entity].pos;
+
+        DiagnosticStep {
+            node: entity,
+            pos: LevelPoint::new(x, y),
+            input,
+            is_failing,
         }
+    }
+
+    fn label(&self, entity: Entity) -> String {
+        let (x, y) = self.nodes[&entity].pos;
+
+        format!("[{}, {}]", x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::bio::Body;
+
+    fn entity(id: u32) -> Entity {
+        Entity::from_raw(id)
+    }
+
+    /// Builds nodes out of `(id, children ids)` pairs
+    fn nodes(links: &[(u32, &[u32])]) -> BTreeMap<Entity, CachedLymphNode> {
+        links
+            .iter()
+            .map(|&(id, children)| {
+                let node = CachedLymphNode {
+                    pos: (id as i32, 0),
+                    resource: None,
+                    children: children.iter().copied().map(entity).collect(),
+                    is_paused: false,
+                    pathogen: None,
+                };
+
+                (entity(id), node)
+            })
+            .collect()
+    }
+
+    fn entities(ids: &[u32]) -> BTreeSet<Entity> {
+        ids.iter().copied().map(entity).collect()
+    }
+
+    fn all(nodes: &BTreeMap<Entity, CachedLymphNode>) -> BTreeSet<Entity> {
+        nodes.keys().copied().collect()
+    }
+
+    #[test]
+    fn self_loop() {
+        let nodes = nodes(&[(1, &[1]), (2, &[])]);
+
+        assert_eq!(entities(&[1]), find_cycles(&nodes, &all(&nodes)));
+    }
+
+    #[test]
+    fn multi_node_cycle() {
+        let nodes = nodes(&[(1, &[2]), (2, &[3]), (3, &[1])]);
+
+        assert_eq!(entities(&[1, 2, 3]), find_cycles(&nodes, &all(&nodes)));
+    }
+
+    #[test]
+    fn chain_leading_into_cycle() {
+        // 1 -> 2 -> 3 -> 4 -> 3
+        let nodes = nodes(&[(1, &[2]), (2, &[3]), (3, &[4]), (4, &[3])]);
+
+        assert_eq!(entities(&[3, 4]), find_cycles(&nodes, &all(&nodes)));
+    }
+
+    #[test]
+    fn chain_leading_out_of_cycle() {
+        // 3 is linked into the cycle formed by 1 and 2, while 4 is linked
+        // into 3 - neither of them is a part of the cycle, though
+        let nodes =
+            nodes(&[(1, &[2]), (2, &[1]), (3, &[1]), (4, &[3]), (5, &[])]);
+
+        assert_eq!(entities(&[1, 2]), find_cycles(&nodes, &all(&nodes)));
+    }
+
+    #[test]
+    fn cycle_through_one_of_many_children() {
+        // 1 -> 2, 1 -> 3 -> 1
+        let nodes = nodes(&[(1, &[2, 3]), (2, &[]), (3, &[1])]);
+
+        assert_eq!(entities(&[1, 3]), find_cycles(&nodes, &all(&nodes)));
+    }
+
+    #[test]
+    fn no_cycles() {
+        let nodes = nodes(&[(1, &[2]), (2, &[3]), (3, &[]), (4, &[])]);
+
+        assert!(find_cycles(&nodes, &all(&nodes)).is_empty());
+    }
+
+    #[test]
+    fn first_compilation_affects_everything() {
+        let nodes = nodes(&[(1, &[2]), (2, &[]), (3, &[])]);
+
+        assert_eq!(all(&nodes), Compiler::default().affected(&nodes));
+    }
+
+    #[test]
+    fn unchanged_nodes_are_not_affected() {
+        // 1 -> 2 -> 3, 4 -> 3, 5
+        let nodes =
+            nodes(&[(1, &[2]), (2, &[3]), (3, &[]), (4, &[3]), (5, &[])]);
+
+        let compiler = Compiler {
+            nodes: nodes.clone(),
+            ..Default::default()
+        };
+
+        assert!(compiler.affected(&nodes).is_empty());
+    }
+
+    #[test]
+    fn modified_node_affects_just_downstream() {
+        // 1 -> 2 -> 3, 4 -> 3, 5
+        let mut nodes =
+            nodes(&[(1, &[2]), (2, &[3]), (3, &[]), (4, &[3]), (5, &[])]);
+
+        let compiler = Compiler {
+            nodes: nodes.clone(),
+            ..Default::default()
+        };
+
+        nodes.get_mut(&entity(2)).unwrap().resource =
+            Some(LymphNodeResource::Body(Body::Circle));
+
+        assert_eq!(entities(&[2, 3]), compiler.affected(&nodes));
+    }
+
+    #[test]
+    fn modified_node_affects_all_of_its_children() {
+        // 1 -> 2, 1 -> 3, 4
+        let mut nodes = nodes(&[(1, &[2, 3]), (2, &[]), (3, &[]), (4, &[])]);
+
+        let compiler = Compiler {
+            nodes: nodes.clone(),
+            ..Default::default()
+        };
+
+        nodes.get_mut(&entity(1)).unwrap().resource =
+            Some(LymphNodeResource::Body(Body::Circle));
+
+        assert_eq!(entities(&[1, 2, 3]), compiler.affected(&nodes));
+    }
+
+    #[test]
+    fn relinked_node_affects_previous_and_current_child() {
+        // 1 -> 2 -> 3, 4
+        let mut nodes = nodes(&[(1, &[2]), (2, &[3]), (3, &[]), (4, &[])]);
+
+        let compiler = Compiler {
+            nodes: nodes.clone(),
+            ..Default::default()
+        };
+
+        // 1 -> 2, 3 -> 4
+        nodes.get_mut(&entity(2)).unwrap().children.clear();
+        nodes.get_mut(&entity(3)).unwrap().children = vec![entity(4)];
+
+        assert_eq!(entities(&[2, 3, 4]), compiler.affected(&nodes));
+    }
+
+    #[test]
+    fn removed_node_affects_its_child() {
+        // 1 -> 2 -> 3, 4
+        let mut nodes = nodes(&[(1, &[2]), (2, &[3]), (3, &[]), (4, &[])]);
+
+        let compiler = Compiler {
+            nodes: nodes.clone(),
+            ..Default::default()
+        };
+
+        nodes.remove(&entity(1));
+
+        assert_eq!(entities(&[2, 3]), compiler.affected(&nodes));
+    }
+
+    #[test]
+    fn cycles_are_found_among_affected_nodes() {
+        // 1 <-> 2, 3 <-> 4
+        let nodes = nodes(&[(1, &[2]), (2, &[1]), (3, &[4]), (4, &[3])]);
 
-        state
+        assert_eq!(entities(&[3, 4]), find_cycles(&nodes, &entities(&[3, 4])));
     }
 }
@@ -0,0 +1,67 @@
+//! Explanations of why a lymph node doesn't produce anything, shown in the
+//! lymph node editor.
+
+use bevy::prelude::Entity;
+
+use crate::level::LevelPoint;
+use crate::systems::bio::{AntigenBinder, LymphNodeProduct, LymphNodeResource};
+
+#[derive(Clone, Debug)]
+pub struct CompilationDiagnostic {
+    /// Nodes the problem comes from, ordered from upstream towards the
+    /// diagnosed one
+    pub steps: Vec<DiagnosticStep>,
+
+    /// What went wrong
+    pub reason: String,
+
+    /// What the player can do about it
+    pub suggestion: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiagnosticStep {
+    pub node: Entity,
+    pub pos: LevelPoint,
+
+    /// What the node brings in (its product or resource), if it matters for
+    /// this particular problem
+    pub input: Option<LymphNodeProduct>,
+
+    /// Whether compilation fails at this step
+    pub is_failing: bool,
+}
+
+impl DiagnosticStep {
+    pub fn label(&self) -> String {
+        format!("[{}, {}]", self.pos.x, self.pos.y)
+    }
+}
+
+pub fn describe_product(product: Option<&LymphNodeProduct>) -> String {
+    match product {
+        Some(LymphNodeProduct::Resource(resource)) => {
+            describe_resource(*resource)
+        }
+
+        Some(LymphNodeProduct::Leukocyte(cell)) => {
+            format!("{:?} leukocyte ({:.1} hp)", cell.kind, cell.props.hp)
+        }
+
+        Some(LymphNodeProduct::Pathogen(pathogen)) => {
+            format!("{:?}", pathogen.kind).to_lowercase()
+        }
+
+        None => "nothing".into(),
+    }
+}
+
+pub fn describe_resource(resource: LymphNodeResource) -> String {
+    match resource {
+        LymphNodeResource::Antigen(AntigenBinder(antigen)) => {
+            format!("{:?} antigen", antigen)
+        }
+        LymphNodeResource::Body(body) => format!("{:?} body", body),
+        LymphNodeResource::Protein(protein) => format!("{:?} protein", protein),
+    }
+}
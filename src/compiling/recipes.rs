@@ -0,0 +1,431 @@
+//! Registry of recipes lymph nodes get compiled against (see `recipes.toml`).
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::systems::bio::{
+    Leukocyte, LeukocyteKind, LeukocyteProps, LymphNodeResource,
+};
+
+const RECIPES_PATH: &str = "recipes.toml";
+
+/// Copy of `recipes.toml` from the time the game was built - used when the
+/// file is missing (e.g. on the web) or broken
+const BUILTIN_RECIPES: &str = include_str!("../../recipes.toml");
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Recipes {
+    #[serde(default, rename = "Recipe")]
+    recipes: Vec<Recipe>,
+
+    #[serde(default, rename = "Modifier")]
+    modifiers: Vec<Modifier>,
+}
+
+/// Turns two (or three) resources into a product.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Recipe {
+    /// Resources the recipe consumes, in any order
+    inputs: Vec<ResourcePattern>,
+    product: RecipeProduct,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum RecipeProduct {
+    /// Leukocyte, which takes its body and antigen binder from the inputs
+    #[serde(rename_all = "PascalCase")]
+    Leukocyte { kind: LeukocyteKind, hp: f32 },
+}
+
+/// Changes stats of a leukocyte that passes through a lymph node with given
+/// resource.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Modifier {
+    input: ResourcePattern,
+
+    /// Kind of leukocytes the modifier applies to; `None` stands for all of
+    /// them
+    #[serde(default)]
+    kind: Option<LeukocyteKind>,
+
+    #[serde(default)]
+    hp_add: f32,
+
+    #[serde(default = "one")]
+    hp_mul: f32,
+
+    #[serde(default = "one")]
+    speed_mul: f32,
+
+    #[serde(default = "one")]
+    damage_mul: f32,
+
+    #[serde(default)]
+    regen_add: f32,
+
+    /// Caps leukocyte's lifespan at given number of seconds
+    #[serde(default)]
+    lifespan: Option<f32>,
+
+    #[serde(default = "one")]
+    radius_mul: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ResourcePattern {
+    /// Matches any resource of given kind, e.g. `"Body"`
+    Kind(ResourceKind),
+
+    /// Matches just given resource, e.g. `{ Body = "Circle" }`
+    Resource(LymphNodeResource),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum ResourceKind {
+    Antigen,
+    Body,
+    Protein,
+}
+
+impl Recipes {
+    /// Maximum number of resources a single recipe can consume
+    pub const MAX_INPUTS: usize = 3;
+
+    /// Loads recipes from `recipes.toml` or, if there's no such file, returns
+    /// the built-in ones.
+    pub fn load() -> Result<Self> {
+        if !Path::new(RECIPES_PATH).exists() {
+            return Ok(Self::builtin());
+        }
+
+        let recipes = fs::read_to_string(RECIPES_PATH)
+            .with_context(|| format!("Couldn't read `{}`", RECIPES_PATH))?;
+
+        Self::parse(&recipes)
+            .with_context(|| format!("Couldn't load `{}`", RECIPES_PATH))
+    }
+
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_RECIPES).expect("Built-in recipes are invalid")
+    }
+
+    pub fn parse(src: &str) -> Result<Self> {
+        let recipes: Self = toml::from_str(src)?;
+
+        for (idx, recipe) in recipes.recipes.iter().enumerate() {
+            recipe
+                .validate()
+                .with_context(|| format!("Recipe #{} is invalid", idx + 1))?;
+        }
+
+        for (idx, modifier) in recipes.modifiers.iter().enumerate() {
+            modifier
+                .validate()
+                .with_context(|| format!("Modifier #{} is invalid", idx + 1))?;
+        }
+
+        Ok(recipes)
+    }
+
+    /// Returns leukocyte created out of given resources, if there's a recipe
+    /// that consumes exactly them.
+    pub fn craft(&self, resources: &[LymphNodeResource]) -> Option<Leukocyte> {
+        self.recipes
+            .iter()
+            .find_map(|recipe| recipe.craft(resources))
+    }
+
+    /// Returns leukocyte modified by given resource, if there's a modifier
+    /// for it.
+    pub fn modify(
+        &self,
+        mut cell: Leukocyte,
+        res: LymphNodeResource,
+    ) -> Option<Leukocyte> {
+        let modifier = self.modifiers.iter().find(|modifier| {
+            modifier.input.matches(res)
+                && modifier.kind.map_or(true, |kind| kind == cell.kind)
+        })?;
+
+        modifier.apply(&mut cell.props);
+
+        if let LymphNodeResource::Protein(protein) = res {
+            cell.proteins.push(protein);
+        }
+
+        Some(cell)
+    }
+}
+
+impl Recipe {
+    fn craft(&self, resources: &[LymphNodeResource]) -> Option<Leukocyte> {
+        if resources.len() != self.inputs.len() {
+            return None;
+        }
+
+        let matches =
+            resources
+                .iter()
+                .permutations(resources.len())
+                .any(|resources| {
+                    resources
+                        .into_iter()
+                        .zip(&self.inputs)
+                        .all(|(&res, input)| input.matches(res))
+                });
+
+        if !matches {
+            return None;
+        }
+
+        match self.product {
+            RecipeProduct::Leukocyte { kind, hp } => {
+                let body = resources.iter().find_map(|res| match res {
+                    LymphNodeResource::Body(body) => Some(*body),
+                    _ => None,
+                })?;
+
+                let binder = resources.iter().find_map(|res| match res {
+                    LymphNodeResource::Antigen(binder) => Some(*binder),
+                    _ => None,
+                })?;
+
+                let proteins = resources
+                    .iter()
+                    .filter_map(|res| match res {
+                        LymphNodeResource::Protein(protein) => Some(*protein),
+                        _ => None,
+                    })
+                    .collect();
+
+                Some(Leukocyte {
+                    body,
+                    binder,
+                    kind,
+                    props: LeukocyteProps::new(kind, hp),
+                    proteins,
+                })
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(2..=Recipes::MAX_INPUTS).contains(&self.inputs.len()) {
+            bail!(
+                "Recipe has to have two or three `Inputs` (found {})",
+                self.inputs.len()
+            );
+        }
+
+        match self.product {
+            RecipeProduct::Leukocyte { hp, .. } => {
+                let has_input =
+                    |kind| self.inputs.iter().any(|input| input.kind() == kind);
+
+                if !has_input(ResourceKind::Antigen)
+                    || !has_input(ResourceKind::Body)
+                {
+                    bail!(
+                        "Leukocyte's recipe has to consume an antigen and a \
+                         body"
+                    );
+                }
+
+                if hp <= 0.0 {
+                    bail!("Leukocyte's `Hp` has to be positive");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Modifier {
+    fn apply(&self, props: &mut LeukocyteProps) {
+        props.hp = props.hp * self.hp_mul + self.hp_add;
+        props.speed *= self.speed_mul;
+        props.damage *= self.damage_mul;
+        props.regen += self.regen_add;
+        props.radius *= self.radius_mul;
+
+        if let Some(lifespan) = self.lifespan {
+            props.lifespan =
+                Some(props.lifespan.map_or(lifespan, |l| l.min(lifespan)));
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let multipliers = [
+            ("HpMul", self.hp_mul),
+            ("SpeedMul", self.speed_mul),
+            ("DamageMul", self.damage_mul),
+            ("RadiusMul", self.radius_mul),
+        ];
+
+        for (name, value) in multipliers {
+            if value <= 0.0 {
+                bail!("`{}` has to be positive", name);
+            }
+        }
+
+        if self.lifespan.map_or(false, |lifespan| lifespan <= 0.0) {
+            bail!("`Lifespan` has to be positive");
+        }
+
+        Ok(())
+    }
+}
+
+impl ResourcePattern {
+    fn matches(self, res: LymphNodeResource) -> bool {
+        match self {
+            Self::Kind(kind) => ResourceKind::of(res) == kind,
+            Self::Resource(res2) => res == res2,
+        }
+    }
+
+    fn kind(self) -> ResourceKind {
+        match self {
+            Self::Kind(kind) => kind,
+            Self::Resource(res) => ResourceKind::of(res),
+        }
+    }
+}
+
+impl ResourceKind {
+    fn of(res: LymphNodeResource) -> Self {
+        match res {
+            LymphNodeResource::Antigen(_) => Self::Antigen,
+            LymphNodeResource::Body(_) => Self::Body,
+            LymphNodeResource::Protein(_) => Self::Protein,
+        }
+    }
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::bio::{Antigen, AntigenBinder, Body, Protein};
+
+    const ANTIGEN: LymphNodeResource =
+        LymphNodeResource::Antigen(AntigenBinder(Antigen::Triangle));
+
+    const CIRCLE: LymphNodeResource = LymphNodeResource::Body(Body::Circle);
+
+    const DUMBBELL: LymphNodeResource =
+        LymphNodeResource::Protein(Protein::Dumbbell);
+
+    const RECIPE: &str = r#"
+        [[Recipe]]
+        Inputs = ["Antigen", "Body"]
+        Product = { Leukocyte = { Kind = "Killer", Hp = 1.0 } }
+    "#;
+
+    fn parse_err(src: &str) -> String {
+        format!("{:?}", Recipes::parse(src).unwrap_err())
+    }
+
+    #[test]
+    fn builtin_recipes() {
+        Recipes::parse(BUILTIN_RECIPES).unwrap();
+    }
+
+    #[test]
+    fn craft() {
+        let recipes = Recipes::builtin();
+
+        let killer = recipes.craft(&[CIRCLE, ANTIGEN]).unwrap();
+
+        assert_eq!(LeukocyteKind::Killer, killer.kind);
+        assert_eq!(Body::Circle, killer.body);
+
+        let cager = recipes.craft(&[DUMBBELL, ANTIGEN, CIRCLE]).unwrap();
+
+        assert_eq!(LeukocyteKind::Cager, cager.kind);
+        assert_eq!(vec![Protein::Dumbbell], cager.proteins);
+
+        assert!(recipes.craft(&[ANTIGEN, DUMBBELL]).is_none());
+        assert!(recipes.craft(&[ANTIGEN]).is_none());
+    }
+
+    #[test]
+    fn invalid_toml() {
+        assert!(Recipes::parse("[[Recipe]").is_err());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let src = RECIPE.replace("Inputs", "Ingredients");
+
+        assert!(parse_err(&src).contains("Ingredients"));
+    }
+
+    #[test]
+    fn invalid_recipes() {
+        let cases = [
+            (r#"["Antigen", "Body"]"#, r#"["Antigen"]"#, "two or three"),
+            (
+                r#"["Antigen", "Body"]"#,
+                r#"["Antigen", "Body", "Body", "Protein"]"#,
+                "two or three",
+            ),
+            (
+                r#"["Antigen", "Body"]"#,
+                r#"["Antigen", "Protein"]"#,
+                "consume an antigen and a body",
+            ),
+            ("Hp = 1.0", "Hp = 0.0", "has to be positive"),
+        ];
+
+        for (from, to, expected) in cases {
+            let err = parse_err(&RECIPE.replace(from, to));
+
+            assert!(
+                err.contains("Recipe #1 is invalid") && err.contains(expected),
+                "{} -> {}: {}",
+                from,
+                to,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_modifiers() {
+        let cases = [
+            ("SpeedMul = 0.0", "`SpeedMul` has to be positive"),
+            ("RadiusMul = -1.0", "`RadiusMul` has to be positive"),
+            ("Lifespan = 0.0", "`Lifespan` has to be positive"),
+        ];
+
+        for (field, expected) in cases {
+            let src = format!(
+                "{}\n[[Modifier]]\nInput = \"Body\"\n{}\n",
+                RECIPE, field
+            );
+
+            let err = parse_err(&src);
+
+            assert!(
+                err.contains("Modifier #1 is invalid")
+                    && err.contains(expected),
+                "{}: {}",
+                field,
+                err
+            );
+        }
+    }
+}
@@ -2,11 +2,12 @@ use std::collections::HashSet;
 
 use bevy::prelude::*;
 
-use super::{CompilationWarning, Compiler};
+use super::{CompilationWarning, Compiler, Recipes};
 use crate::systems::bio::{
     DeadLymphNodeConnection, LymphNode, LymphNodeConnection, LymphNodeProduct,
-    LymphNodeTarget, LymphNodeWarning,
+    LymphNodeWarning,
 };
+use crate::systems::immunity::ImmuneMemory;
 
 // TODO(pwy) replace with `LymphNodeConfigurationChanged`?
 #[derive(Clone, Debug)]
@@ -14,46 +15,63 @@ pub struct RecompileEvent;
 
 pub(super) fn compile(
     mut events: EventReader<RecompileEvent>,
-    mut nodes: Query<(Entity, &mut LymphNode, &Children)>,
+    recipes: Res<Recipes>,
+    memory: Res<ImmuneMemory>,
+    mut compiler: Local<Compiler>,
+    mut nodes: Query<(Entity, &mut LymphNode, Option<&Children>)>,
     mut warnings: Query<&mut LymphNodeWarning>,
 ) {
     if events.iter().next().is_none() {
         return;
     }
 
-    let mut compiler = Compiler::default();
+    compiler.remember(memory.memory_cells());
 
-    for (entity, node, _) in nodes.iter() {
-        compiler.add(entity, node);
-    }
+    // Only nodes affected by the changes get returned here - the rest of them
+    // keep whatever they've got from the previous compilations
+    let compiled = compiler.compile(
+        &recipes,
+        nodes.iter().map(|(entity, node, _)| (entity, node)),
+    );
 
-    for (entity, (product, parent, state)) in compiler.compile() {
+    for (entity, compiled) in compiled {
         let (_, mut node, children) = nodes.get_mut(entity).unwrap();
+        let node = &mut *node;
+
+        node.product = compiled.product;
+        node.parents = compiled.parents;
+
+        // Products received from nodes that are no longer linked here won't
+        // ever get consumed
+        node.stock.retain(|parent, _| node.parents.contains(parent));
 
-        node.product = product;
-        node.parent = parent;
-        node.state = state;
+        node.state = compiled.state;
+        node.diagnostic = compiled.diagnostic;
 
         node.warning =
             if matches!(node.product, Some(LymphNodeProduct::Pathogen(_))) {
                 Some(CompilationWarning::Infected)
+            } else if compiled.is_in_cycle {
+                Some(CompilationWarning::NodeIsInCycle)
             } else if node.state.is_paused {
                 Some(CompilationWarning::NodeIsPaused)
             } else if node.state.is_awaiting_resources {
                 Some(CompilationWarning::NodeIsAwaitingResources)
+            } else if compiled.has_conflicting_inputs {
+                Some(CompilationWarning::NodeHasConflictingInputs)
             } else if node.product.is_none() {
                 Some(CompilationWarning::NodeHasNoProduct)
             } else if matches!(
                 node.product,
                 Some(LymphNodeProduct::Resource(_))
-            ) && !matches!(node.target, LymphNodeTarget::LymphNode(_))
+            ) && node.targets.is_empty()
             {
                 Some(CompilationWarning::NodeHasNoChild)
             } else {
                 None
             };
 
-        for child in children.iter() {
+        for child in children.iter().flat_map(|children| children.iter()) {
             if let Ok(mut warn) = warnings.get_mut(*child) {
                 warn.set(node.warning.map(|warn| warn.asset_path()));
             }
@@ -82,24 +100,23 @@ pub(super) fn link(
     }
 
     for (source, source_node, &source_transform) in nodes.iter() {
-        let target =
-            if let LymphNodeTarget::LymphNode(target) = source_node.target {
-                target
-            } else {
-                continue;
-            };
+        for &target in &source_node.targets {
+            required_connections.insert((source, target));
 
-        required_connections.insert((source, target));
+            if existing_connections.contains(&(source, target)) {
+                continue;
+            }
 
-        let (_, _, &target_transform) = nodes.get(target).unwrap();
+            let (_, _, &target_transform) = nodes.get(target).unwrap();
 
-        LymphNodeConnection::new(
-            source,
-            source_transform.translation.truncate(),
-            target,
-            target_transform.translation.truncate(),
-        )
-        .spawn(&mut commands);
+            LymphNodeConnection::new(
+                source,
+                source_transform.translation.truncate(),
+                target,
+                target_transform.translation.truncate(),
+            )
+            .spawn(&mut commands);
+        }
     }
 
     let unnecessary_connections =
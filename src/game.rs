@@ -1,51 +1,169 @@
 mod progress_bars;
+mod save;
+mod time;
 
+use std::env;
+use std::f32::consts::TAU;
+
+use bevy::math::vec2;
 use bevy::prelude::*;
-use instant::{Duration, Instant};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+pub use self::save::{LoadGameEvent, SaveGameEvent};
+pub use self::time::{GameSpeed, GameTime, TickGameTime};
 use crate::compiling::RecompileEvent;
-use crate::level::{Level, LevelPoint, LevelWaveOp};
+use crate::level::{
+    Difficulty, Level, LevelGeneratorKind, LevelPoint, LevelVictory,
+    LevelWaveCondition, LevelWaveOp,
+};
 use crate::pathfinding::LevelLayoutChanged;
+use crate::rng::GameRng;
 use crate::systems::bio::{
-    LymphNode, LymphNodeState, LymphNodeTarget, Wall, WallFadeIn, WallFadeOut,
+    Leukocyte, LymphNode, LymphNodeAssets, LymphNodeConnection, LymphNodeFlow,
+    LymphNodeState, Pathogen, Wall, WallFadeIn, WallFadeOut,
 };
+use crate::systems::enemy_ai;
+use crate::systems::evolution::Evolution;
+use crate::systems::immunity::ImmuneMemory;
+use crate::systems::physics::PHYSICS_SCALE;
 use crate::systems::units::Alignment;
 use crate::tutorial::TutorialState;
 
+/// Delay between executing consecutive ops of a wave (in seconds)
+const OP_DELAY: f32 = 0.002;
+
+/// Delay between starting a level and its first wave (in seconds)
+const START_DELAY: f32 = 5.0;
+
+/// Entities that make up a running game - they get despawned when another
+/// game is started or loaded
+type GameEntity = Or<(
+    With<LymphNode>,
+    With<Leukocyte>,
+    With<Pathogen>,
+    With<Wall>,
+    With<LymphNodeConnection>,
+)>;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Level::start())
+        let mut rng = app
+            .world
+            .remove_resource::<GameRng>()
+            .unwrap_or_else(GameRng::from_env);
+
+        info!("Using seed: {}", rng.seed());
+
+        app.insert_resource(load_level(&mut rng))
+            .insert_resource(rng)
             .insert_resource(GameState::default())
+            .insert_resource(GameTime::default())
             .insert_resource(TutorialState::default())
+            .add_event::<StartGameEvent>()
+            .add_system(start_game)
             .add_system(progress)
-            .add_system(game_over);
+            .add_system(check_outcome);
+
+        save::initialize(app);
+        time::initialize(app);
+    }
+}
+
+/// Displays game's progress (current wave, number of viruses etc.) and handles
+/// game-related keys; not used in headless mode.
+pub struct GameHudPlugin;
 
+impl Plugin for GameHudPlugin {
+    fn build(&self, app: &mut App) {
         progress_bars::initialize(app);
+        save::initialize_keys(app);
     }
 }
 
+/// Loads level pointed at by the `LEVEL` environment variable or, if it's not
+/// set, generates one (using generator and difficulty pointed at by
+/// `GENERATOR` and `DIFFICULTY`).
+fn load_level(rng: &mut GameRng) -> Level {
+    let path = if let Ok(path) = env::var("LEVEL") {
+        path
+    } else {
+        return Level::start(
+            LevelGeneratorKind::from_env(),
+            Difficulty::from_env(),
+            rng,
+        );
+    };
+
+    match Level::load(&path, rng) {
+        Ok(level) => level,
+
+        Err(err) => {
+            error!("{:?}", err);
+            warn!("Falling back to a generated level");
+
+            Level::start(
+                LevelGeneratorKind::from_env(),
+                Difficulty::from_env(),
+                rng,
+            )
+        }
+    }
+}
+
+/// Abandons the current game and starts playing given level from scratch
+pub struct StartGameEvent {
+    pub level: Level,
+}
+
 pub struct GameState {
     pub tutorial: bool,
     pub seconds: f32,
     pub vm: LevelVm,
-    pub game_over: bool,
+    pub outcome: Option<GameOutcome>,
+    pub message: Option<GameMessage>,
+}
+
+impl GameState {
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// Returns whether the current wave's script has been fully executed
+    pub fn is_wave_done(&self) -> bool {
+        matches!(
+            self.vm,
+            LevelVm::AwaitingWaveEnd | LevelVm::AwaitingWaveStart { .. }
+        )
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    Victory,
+    Defeat,
+}
+
+/// Message displayed by the level's script (see: `LevelWaveOp::ShowMessage`)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameMessage {
+    pub text: String,
+
+    /// Moment (see: `GameTime::elapsed_seconds()`) the message disappears at
+    pub until: f32,
+}
+
+/// State of the level's script; all the deadlines are expressed in terms of
+/// `GameTime::elapsed_seconds()`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum LevelVm {
     Idle,
-    AwaitingStart {
-        at: Instant,
-    },
-    Asleep {
-        until: Option<Instant>,
-        op_idx: usize,
-    },
+    AwaitingStart { at: f32 },
+    Asleep { until: Option<f32>, op_idx: usize },
     AwaitingWaveEnd,
-    AwaitingWaveStart {
-        at: Instant,
-    },
+    AwaitingWaveStart { at: f32 },
 }
 
 impl Default for LevelVm {
@@ -60,27 +178,80 @@ impl Default for GameState {
             tutorial: true,
             seconds: Default::default(),
             vm: LevelVm::default(),
-            game_over: false,
+            outcome: None,
+            message: None,
         }
     }
 }
 
+fn start_game(
+    mut events: EventReader<StartGameEvent>,
+    mut commands: Commands,
+    time: Res<GameTime>,
+    mut enemy_ai: ResMut<enemy_ai::State>,
+    mut evolution: ResMut<Evolution>,
+    mut memory: ResMut<ImmuneMemory>,
+    mut level: ResMut<Level>,
+    mut state: ResMut<GameState>,
+    entities: Query<Entity, GameEntity>,
+    mut level_changed_tx: EventWriter<LevelLayoutChanged>,
+) {
+    let event = if let Some(event) = events.iter().last() {
+        event
+    } else {
+        return;
+    };
+
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *level = event.level.clone();
+
+    *state = GameState {
+        tutorial: false,
+        vm: LevelVm::AwaitingStart {
+            at: time.elapsed_seconds() + START_DELAY,
+        },
+        ..Default::default()
+    };
+
+    enemy_ai.target = None;
+    *evolution = Default::default();
+    *memory = Default::default();
+    level_changed_tx.send(LevelLayoutChanged);
+}
+
 fn progress(
+    time: Res<GameTime>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    assets: Res<AssetServer>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+    assets: Option<Res<AssetServer>>,
     mut state: ResMut<GameState>,
     walls: Query<(Entity, &Wall)>,
     mut level: ResMut<Level>,
+    mut rng: ResMut<GameRng>,
     mut level_changed_tx: EventWriter<LevelLayoutChanged>,
     mut recompile_event_tx: EventWriter<RecompileEvent>,
+    mut enemy_ai: ResMut<enemy_ai::State>,
     lymph_nodes: Query<&Alignment, With<LymphNode>>,
+    pathogens: Query<(), With<Pathogen>>,
 ) {
-    if state.game_over {
+    if state.is_over() {
         return;
     }
 
+    if !matches!(state.vm, LevelVm::Idle | LevelVm::AwaitingStart { .. }) {
+        state.seconds += time.delta_seconds();
+    }
+
+    let now = time.elapsed_seconds();
+
+    if state.message.as_ref().map_or(false, |msg| msg.until <= now) {
+        state.message = None;
+    }
+
     loop {
         match state.vm {
             LevelVm::Idle => {
@@ -88,7 +259,7 @@ fn progress(
             }
 
             LevelVm::AwaitingStart { at } => {
-                if Instant::now() < at {
+                if now < at {
                     return;
                 }
 
@@ -100,30 +271,39 @@ fn progress(
 
             LevelVm::Asleep { until, op_idx } => {
                 if let Some(until) = until {
-                    if Instant::now() < until {
+                    if now < until {
                         return;
                     }
                 }
 
-                let op = &level.wave.ops[op_idx];
-                let mut sleep = true;
+                let op = if let Some(op) = level.wave.ops.get(op_idx) {
+                    op
+                } else {
+                    state.vm = LevelVm::AwaitingWaveEnd;
+                    continue;
+                };
+
+                // How long to wait (in seconds) before executing the next op;
+                // by default we wait a bit, so that e.g. walls get built one
+                // by one instead of all at once
+                let mut sleep = Some(OP_DELAY);
 
                 match op {
                     LevelWaveOp::AddWall { x, y } => {
                         Wall {
                             pos: LevelPoint::new(*x, *y),
                         }
-                        .spawn(&mut commands, &assets);
+                        .spawn(&mut commands, assets.as_deref());
 
                         level_changed_tx.send(LevelLayoutChanged);
                     }
 
                     LevelWaveOp::RemoveWall { x, y } => {
-                        sleep = false;
+                        sleep = None;
 
                         for (entity, wall) in walls.iter() {
                             if wall.pos.x == *x && wall.pos.y == *y {
-                                sleep = true;
+                                sleep = Some(OP_DELAY);
 
                                 commands.entity(entity).remove::<WallFadeIn>();
 
@@ -136,68 +316,138 @@ fn progress(
                         }
                     }
 
-                    LevelWaveOp::AddLymphNode { x, y, alignment } => {
+                    LevelWaveOp::AddLymphNode {
+                        x,
+                        y,
+                        alignment,
+                        resource,
+                    } => {
                         LymphNode {
                             pos: LevelPoint::new(*x, *y),
-                            resource: None,
-                            target: LymphNodeTarget::Outside,
+                            resource: *resource,
+                            targets: vec![],
                             product: None,
-                            parent: None,
+                            parents: vec![],
                             warning: None,
+                            diagnostic: None,
                             state: LymphNodeState {
                                 is_paused: false,
                                 is_awaiting_resources: false,
                             },
+                            production: Default::default(),
                             production_tt: 0.0,
+                            rally: None,
+                            stock: Default::default(),
+                            flow: LymphNodeFlow::Idle,
                         }
                         .spawn(
                             &mut commands,
-                            &mut meshes,
-                            &mut materials,
-                            &assets,
+                            LymphNodeAssets::new(
+                                assets.as_deref(),
+                                meshes.as_deref_mut(),
+                                materials.as_deref_mut(),
+                            ),
                             *alignment,
                         );
 
                         level_changed_tx.send(LevelLayoutChanged);
                         recompile_event_tx.send(RecompileEvent);
                     }
-                }
 
-                state.vm = if level.wave.ops.get(op_idx + 1).is_some() {
-                    let until = if sleep {
-                        Some(Instant::now() + Duration::from_millis(2))
-                    } else {
-                        None
-                    };
+                    LevelWaveOp::SpawnPathogens {
+                        pos,
+                        vel,
+                        body,
+                        antigen,
+                        kind,
+                        count,
+                    } => {
+                        let pathogen = Pathogen {
+                            body: *body,
+                            antigen: *antigen,
+                            kind: *kind,
+                            hp: level.stage().pathogen_hp,
+                        };
+
+                        for _ in 0..*count {
+                            pathogen.spawn(
+                                &mut commands,
+                                assets.as_deref(),
+                                *pos + random_offset(&mut *rng),
+                                *vel,
+                            );
+                        }
+                    }
+
+                    LevelWaveOp::Sleep { secs } => {
+                        sleep = Some(*secs);
+                    }
+
+                    LevelWaveOp::ShowMessage { text, secs } => {
+                        sleep = None;
 
-                    LevelVm::Asleep {
-                        until,
-                        op_idx: op_idx + 1,
+                        state.message = Some(GameMessage {
+                            text: text.to_owned(),
+                            until: now + secs,
+                        });
                     }
-                } else {
-                    LevelVm::AwaitingWaveEnd
+
+                    LevelWaveOp::WaitUntil { condition } => {
+                        let is_met = match condition {
+                            LevelWaveCondition::AllEnemyNodesCaptured => {
+                                !lymph_nodes.iter().any(|a| a.is_enemy())
+                            }
+
+                            LevelWaveCondition::PathogensRemaining(count) => {
+                                pathogens.iter().count() <= *count
+                            }
+                        };
+
+                        if !is_met {
+                            return;
+                        }
+
+                        sleep = None;
+                    }
+
+                    LevelWaveOp::SetEnemyTarget { pos } => {
+                        sleep = None;
+                        enemy_ai.target = pos.map(|pos| pos * PHYSICS_SCALE);
+                    }
+                }
+
+                state.vm = LevelVm::Asleep {
+                    until: sleep.map(|secs| now + secs),
+                    op_idx: op_idx + 1,
                 };
             }
 
             LevelVm::AwaitingWaveEnd => {
-                if lymph_nodes.iter().all(|a| a.is_player()) {
-                    state.vm = LevelVm::AwaitingWaveStart {
-                        at: Instant::now() + Duration::from_secs(25),
-                    };
+                if let Some(starts_at) = level.next_wave_starts_at() {
+                    let delay = (starts_at - state.seconds).max(0.0);
+
+                    state.vm = LevelVm::AwaitingWaveStart { at: now + delay };
+                } else if level.has_next_wave()
+                    && lymph_nodes.iter().all(|a| a.is_player())
+                {
+                    let delay =
+                        level.difficulty.stage(level.wave_idx + 1).wave_delay;
+
+                    state.vm = LevelVm::AwaitingWaveStart { at: now + delay };
                 } else {
                     return;
                 }
             }
 
             LevelVm::AwaitingWaveStart { at } => {
-                if Instant::now() <= at {
+                if now <= at {
                     return;
                 }
 
-                level.progress();
+                level.progress(&mut *rng);
 
                 state.vm = LevelVm::Asleep {
-                    until: Some(Instant::now()),
+                    until: Some(now),
                     op_idx: 0,
                 };
             }
@@ -205,21 +455,78 @@ fn progress(
     }
 }
 
-fn game_over(
+/// Returns a small random offset (in physics units), so that pathogens spawned
+/// as a group don't all end up in the very same spot.
+fn random_offset(rng: &mut impl Rng) -> Vec2 {
+    let angle = rng.gen_range(0.0..TAU);
+    let distance = rng.gen_range(0.0..0.5);
+
+    vec2(angle.cos(), angle.sin()) * distance
+}
+
+fn check_outcome(
     mut state: ResMut<GameState>,
-    lymph_nodes: Query<(&Transform, &Alignment, &LymphNode), With<LymphNode>>,
+    level: Res<Level>,
+    lymph_nodes: Query<(&LymphNode, &Alignment)>,
+    pathogens: Query<(), With<Pathogen>>,
 ) {
+    if state.is_over() {
+        return;
+    }
+
     let enemy_owned_lymph_nodes = lymph_nodes
         .iter()
-        .filter(|(_, alignment, _)| alignment.is_enemy())
+        .filter(|(_, alignment)| alignment.is_enemy())
         .count();
 
     let player_owned_lymph_nodes = lymph_nodes
         .iter()
-        .filter(|(_, alignment, _)| alignment.is_player())
+        .filter(|(_, alignment)| alignment.is_player())
         .count();
 
     if enemy_owned_lymph_nodes > 0 && player_owned_lymph_nodes == 0 {
-        state.game_over = true;
+        state.outcome = Some(GameOutcome::Defeat);
+        return;
     }
+
+    let victory = if let Some(victory) = level.victory {
+        victory
+    } else {
+        return;
+    };
+
+    state.outcome = match victory {
+        LevelVictory::SurviveWaves(waves) => {
+            let is_done = level.wave_idx > waves
+                || (level.wave_idx == waves
+                    && state.is_wave_done()
+                    && pathogens.iter().next().is_none());
+
+            is_done.then(|| GameOutcome::Victory)
+        }
+
+        LevelVictory::CaptureAllEnemyNodes => {
+            let is_done = enemy_owned_lymph_nodes == 0
+                && state.is_wave_done()
+                && !level.has_next_wave();
+
+            is_done.then(|| GameOutcome::Victory)
+        }
+
+        LevelVictory::KeepNodeAlive { x, y, secs } => {
+            // Until the level's setup spawns the node, there's nothing to check
+            let alignment = lymph_nodes
+                .iter()
+                .find(|(node, _)| node.pos == LevelPoint::new(x, y))
+                .map(|(_, alignment)| *alignment);
+
+            match alignment {
+                Some(Alignment::Player) if state.seconds >= secs => {
+                    Some(GameOutcome::Victory)
+                }
+                Some(Alignment::Player) | None => None,
+                Some(_) => Some(GameOutcome::Defeat),
+            }
+        }
+    };
 }
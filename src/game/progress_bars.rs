@@ -1,9 +1,11 @@
 use bevy::prelude::*;
-use instant::Instant;
+use itertools::Itertools;
 
-use super::{GameState, LevelVm};
+use super::{GameOutcome, GameState, GameTime, LevelVm};
 use crate::level::Level;
-use crate::systems::bio::Pathogen;
+use crate::rng::GameRng;
+use crate::systems::bio::{LymphNode, LymphNodeProduct, Pathogen};
+use crate::systems::evolution::{Evolution, Strain};
 
 const FONT_SIZE: f32 = 30.0;
 const TOP_MARGIN: f32 = 10.0;
@@ -20,14 +22,25 @@ struct WaveText;
 #[derive(Component)]
 struct NumberOfVirusesText;
 
+#[derive(Component)]
+struct SeedText;
+
+#[derive(Component)]
+struct MessageText;
+
+#[derive(Component)]
+struct StrainsText;
+
 pub fn initialize(app: &mut App) {
     app.add_startup_system(setup)
         .add_system(position_text)
         .add_system(update_wave_text)
-        .add_system(update_number_of_viruses_text);
+        .add_system(update_number_of_viruses_text)
+        .add_system(update_message_text)
+        .add_system(update_strains_text);
 }
 
-fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+fn setup(mut commands: Commands, assets: Res<AssetServer>, rng: Res<GameRng>) {
     let font = assets.load("fat-pixels.regular.ttf");
 
     let text_style = TextStyle {
@@ -76,6 +89,69 @@ fn setup(mut commands: Commands, assets: Res<AssetServer>) {
         })
         .insert(NumberOfVirusesText)
         .insert(ProgressText { offset: Vec3::ZERO });
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                String::default(),
+                text_style.clone(),
+                text_alignment,
+            ),
+            transform: Transform::from_translation(Vec3::new(
+                0.0,
+                0.0,
+                TEXT_Z_OFFSET,
+            )),
+            ..Default::default()
+        })
+        .insert(MessageText)
+        .insert(ProgressText {
+            offset: Vec3::Y * -100.0,
+        });
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                format!("Seed: {}", rng.seed()),
+                TextStyle {
+                    font_size: FONT_SIZE / 2.0,
+                    ..text_style
+                },
+                text_alignment,
+            ),
+            transform: Transform::from_translation(Vec3::new(
+                0.0,
+                0.0,
+                TEXT_Z_OFFSET,
+            )),
+            ..Default::default()
+        })
+        .insert(SeedText)
+        .insert(ProgressText {
+            offset: Vec3::Y * -60.0,
+        });
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                String::default(),
+                TextStyle {
+                    font_size: FONT_SIZE / 2.0,
+                    ..text_style
+                },
+                text_alignment,
+            ),
+            transform: Transform::from_translation(Vec3::new(
+                0.0,
+                0.0,
+                TEXT_Z_OFFSET,
+            )),
+            ..Default::default()
+        })
+        .insert(StrainsText)
+        .insert(ProgressText {
+            offset: Vec3::Y * -140.0,
+        });
 }
 
 fn position_text(
@@ -98,34 +174,41 @@ fn position_text(
 }
 
 fn update_wave_text(
+    time: Res<GameTime>,
     state: Res<GameState>,
     level: Res<Level>,
     mut query: Query<(&mut Text, &WaveText)>,
 ) {
     let (mut text, _) = query.single_mut();
 
-    if state.game_over {
-        text.sections[0].value = "Game over".into();
-        return;
+    match state.outcome {
+        Some(GameOutcome::Victory) => {
+            text.sections[0].value = "Victory!".into();
+            return;
+        }
+
+        Some(GameOutcome::Defeat) => {
+            text.sections[0].value = "Game over".into();
+            return;
+        }
+
+        None => (),
     }
 
     text.sections[0].value = String::default();
 
     match state.vm {
         LevelVm::AwaitingStart { at } => {
-            if let Some(d) = at.checked_duration_since(Instant::now()) {
+            if let Some(d) = seconds_until(&time, at) {
                 text.sections[0].value =
-                    format!("Game begins in {} seconds", d.as_secs());
+                    format!("Game begins in {} seconds", d);
             }
         }
 
         LevelVm::AwaitingWaveStart { at } => {
-            if let Some(d) = at.checked_duration_since(Instant::now()) {
-                text.sections[0].value = format!(
-                    "Wave {} starts in {} seconds",
-                    level.wave_idx,
-                    d.as_secs(),
-                );
+            if let Some(d) = seconds_until(&time, at) {
+                text.sections[0].value =
+                    format!("Wave {} starts in {} seconds", level.wave_idx, d,);
             }
         }
 
@@ -133,6 +216,16 @@ fn update_wave_text(
     }
 }
 
+fn seconds_until(time: &GameTime, at: f32) -> Option<u32> {
+    let d = at - time.elapsed_seconds();
+
+    if d >= 0.0 {
+        Some(d as u32)
+    } else {
+        None
+    }
+}
+
 fn update_number_of_viruses_text(
     viruses: Query<&Pathogen>,
     mut query: Query<(&mut Text, &NumberOfVirusesText)>,
@@ -144,3 +237,60 @@ fn update_number_of_viruses_text(
 
     text.sections[0].value = format!("{} viruses remaining", num_of_viruses);
 }
+
+fn update_message_text(
+    state: Res<GameState>,
+    mut query: Query<(&mut Text, &MessageText)>,
+) {
+    let (mut text, _) = query.single_mut();
+
+    text.sections[0].value = state
+        .message
+        .as_ref()
+        .map(|message| message.text.clone())
+        .unwrap_or_default();
+}
+
+/// Lists strains produced by infected lymph nodes, marking the ones that have
+/// just emerged through antigenic drift.
+fn update_strains_text(
+    evolution: Res<Evolution>,
+    lymph_nodes: Query<&LymphNode>,
+    mut query: Query<(&mut Text, &StrainsText)>,
+) {
+    let (mut text, _) = query.single_mut();
+
+    let strains = lymph_nodes
+        .iter()
+        .filter_map(|node| {
+            if let Some(LymphNodeProduct::Pathogen(pathogen)) = &node.product {
+                Some(Strain::of(pathogen))
+            } else {
+                None
+            }
+        })
+        .counts();
+
+    text.sections[0].value = if strains.is_empty() {
+        String::default()
+    } else {
+        let strains = strains
+            .into_iter()
+            .sorted()
+            .map(|(strain, count)| {
+                let mut label = format!(
+                    "{:?} {:?} x{}",
+                    strain.body, strain.antigen, count
+                );
+
+                if evolution.has_emerged(strain) {
+                    label += " (new)";
+                }
+
+                label
+            })
+            .join(", ");
+
+        format!("Strains: {}", strains)
+    };
+}
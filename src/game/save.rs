@@ -0,0 +1,770 @@
+//! Saves & loads the entire game (F5 / F9).
+//!
+//! Since entities can't be persisted as-is, links between lymph nodes are
+//! stored as indices into the saved list of lymph nodes and get re-created on
+//! load; compilation results (products, parents, warnings) aren't saved at all,
+//! since they are simply recompiled afterwards.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::RigidBodyVelocityComponent;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    GameEntity, GameMessage, GameOutcome, GameState, GameTime, LevelVm,
+};
+use crate::compiling::RecompileEvent;
+use crate::level::{Level, LevelPoint};
+use crate::pathfinding::LevelLayoutChanged;
+use crate::rng::GameRng;
+use crate::systems::bio::{
+    DeadLymphNodeConnection, Leukocyte, LymphNode, LymphNodeAssets,
+    LymphNodeConnection, LymphNodeFlow, LymphNodeProduct, LymphNodeProduction,
+    LymphNodeRally, LymphNodeResource, LymphNodeState, Pathogen, ProducedBy,
+    Wall, WallFadeOut,
+};
+use crate::systems::enemy_ai;
+use crate::systems::evolution::Evolution;
+use crate::systems::immunity::ImmuneMemory;
+use crate::systems::physics::PHYSICS_SCALE;
+use crate::systems::units::{Alignment, Health, Unit};
+
+pub fn initialize(app: &mut App) {
+    app.init_resource::<SavePath>()
+        .add_event::<SaveGameEvent>()
+        .add_event::<LoadGameEvent>()
+        .add_system(save)
+        .add_system(load);
+}
+
+/// Handles F5 / F9; not used in headless mode.
+pub fn initialize_keys(app: &mut App) {
+    app.add_system(handle_keys);
+}
+
+pub struct SaveGameEvent;
+
+pub struct LoadGameEvent;
+
+/// File the game gets saved to and loaded from.
+pub struct SavePath(pub PathBuf);
+
+impl Default for SavePath {
+    fn default() -> Self {
+        Self("save.ron".into())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    seed: u64,
+
+    /// See: `GameRng::draws()`
+    draws: u64,
+
+    level: Level,
+    state: SavedGameState,
+    lymph_nodes: Vec<SavedLymphNode>,
+    cells: Vec<SavedCell>,
+    walls: Vec<SavedWall>,
+
+    #[serde(default)]
+    memory: ImmuneMemory,
+
+    #[serde(default)]
+    evolution: Evolution,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedGameState {
+    tutorial: bool,
+    seconds: f32,
+    vm: LevelVm,
+    outcome: Option<GameOutcome>,
+    message: Option<GameMessage>,
+
+    /// See: `GameTime::elapsed_seconds()`
+    elapsed_seconds: f32,
+
+    /// See: `enemy_ai::State::target`
+    enemy_target: Option<Vec2>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedLymphNode {
+    x: i32,
+    y: i32,
+    resource: Option<LymphNodeResource>,
+
+    #[serde(default)]
+    targets: Vec<SavedLink>,
+
+    is_paused: bool,
+
+    #[serde(default)]
+    production: LymphNodeProduction,
+
+    alignment: Alignment,
+    health: f32,
+    production_tt: f32,
+
+    /// Pathogen produced by an infected lymph node; it's chosen randomly when
+    /// the node gets infected, so it can't be just recompiled
+    pathogen: Option<Pathogen>,
+
+    #[serde(default)]
+    rally: Option<SavedRally>,
+
+    /// Products received from the parents and not consumed yet, as pairs of
+    /// the parent's index (within `SavedGame::lymph_nodes`) and the number of
+    /// products
+    #[serde(default)]
+    stock: Vec<(usize, usize)>,
+
+    /// Index of the targeted lymph node, as saved before lymph nodes could
+    /// target more than one node (see: `Self::links()`)
+    #[serde(default, skip_serializing)]
+    target: Option<usize>,
+
+    /// Products travelling towards `Self::target`
+    #[serde(default, skip_serializing)]
+    packets: Vec<f32>,
+}
+
+impl SavedLymphNode {
+    /// Returns links of this node, including the one coming from an older
+    /// save.
+    fn links(&self) -> Vec<SavedLink> {
+        let legacy_link = self.target.map(|target| SavedLink {
+            target,
+            packets: self.packets.clone(),
+        });
+
+        self.targets.iter().cloned().chain(legacy_link).collect()
+    }
+}
+
+/// Link between lymph nodes, along with the products travelling along it
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedLink {
+    /// Index of the targeted lymph node (within `SavedGame::lymph_nodes`)
+    target: usize,
+
+    /// See: `LymphNodeConnection::packets`
+    #[serde(default)]
+    packets: Vec<f32>,
+}
+
+/// See: `LymphNodeRally`
+#[derive(Serialize, Deserialize)]
+enum SavedRally {
+    Point(Vec2),
+
+    /// Index of the lymph node (within `SavedGame::lymph_nodes`)
+    LymphNode(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+enum SavedCell {
+    Leukocyte {
+        cell: Leukocyte,
+        pos: Vec2,
+        vel: Vec2,
+        health: f32,
+        unit: Unit,
+
+        /// Index of the lymph node that's produced the cell (within
+        /// `SavedGame::lymph_nodes`)
+        #[serde(default)]
+        producer: Option<usize>,
+    },
+
+    Pathogen {
+        cell: Pathogen,
+        pos: Vec2,
+        vel: Vec2,
+        health: f32,
+        unit: Unit,
+
+        /// See: `SavedCell::Leukocyte::producer`
+        #[serde(default)]
+        producer: Option<usize>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedWall {
+    x: i32,
+    y: i32,
+}
+
+fn handle_keys(
+    keys: Res<Input<KeyCode>>,
+    mut save_tx: EventWriter<SaveGameEvent>,
+    mut load_tx: EventWriter<LoadGameEvent>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        save_tx.send(SaveGameEvent);
+    }
+
+    if keys.just_pressed(KeyCode::F9) {
+        load_tx.send(LoadGameEvent);
+    }
+}
+
+fn save(
+    mut events: EventReader<SaveGameEvent>,
+    path: Res<SavePath>,
+    rng: Res<GameRng>,
+    time: Res<GameTime>,
+    enemy_ai: Res<enemy_ai::State>,
+    memory: Res<ImmuneMemory>,
+    evolution: Res<Evolution>,
+    level: Res<Level>,
+    state: Res<GameState>,
+    lymph_nodes: Query<(Entity, &LymphNode, &Alignment, &Health)>,
+    connections: Query<&LymphNodeConnection, Without<DeadLymphNodeConnection>>,
+    leukocytes: Query<(
+        &Leukocyte,
+        &Transform,
+        &RigidBodyVelocityComponent,
+        &Health,
+        &Unit,
+        Option<&ProducedBy>,
+    )>,
+    pathogens: Query<(
+        &Pathogen,
+        &Transform,
+        &RigidBodyVelocityComponent,
+        &Health,
+        &Unit,
+        Option<&ProducedBy>,
+    )>,
+    walls: Query<&Wall, Without<WallFadeOut>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    let node_entities: Vec<_> =
+        lymph_nodes.iter().map(|(entity, ..)| entity).collect();
+
+    let node_idx =
+        |node: Entity| node_entities.iter().position(|&entity| entity == node);
+
+    let lymph_nodes = lymph_nodes
+        .iter()
+        .map(|(entity, node, alignment, health)| {
+            let targets = node
+                .targets
+                .iter()
+                .filter_map(|&target| {
+                    let packets = connections
+                        .iter()
+                        .find(|connection| {
+                            connection.source == entity
+                                && connection.target == target
+                        })
+                        .map(|connection| connection.packets.clone())
+                        .unwrap_or_default();
+
+                    Some(SavedLink {
+                        target: node_idx(target)?,
+                        packets,
+                    })
+                })
+                .collect();
+
+            let rally = match node.rally {
+                Some(LymphNodeRally::Point(pos)) => {
+                    Some(SavedRally::Point(pos))
+                }
+
+                Some(LymphNodeRally::LymphNode(rally)) => {
+                    node_idx(rally).map(SavedRally::LymphNode)
+                }
+
+                None => None,
+            };
+
+            let pathogen = if let Some(LymphNodeProduct::Pathogen(pathogen)) =
+                node.product
+            {
+                Some(pathogen)
+            } else {
+                None
+            };
+
+            SavedLymphNode {
+                x: node.pos.x,
+                y: node.pos.y,
+                resource: node.resource,
+                targets,
+                is_paused: node.state.is_paused,
+                production: node.production,
+                alignment: *alignment,
+                health: health.health,
+                production_tt: node.production_tt,
+                pathogen,
+                rally,
+                stock: node
+                    .stock
+                    .iter()
+                    .filter_map(|(&parent, &count)| {
+                        Some((node_idx(parent)?, count))
+                    })
+                    .collect(),
+                target: None,
+                packets: vec![],
+            }
+        })
+        .collect();
+
+    let producer = |produced_by: Option<&ProducedBy>| {
+        let ProducedBy(node) = produced_by?;
+
+        node_idx(*node)
+    };
+
+    let leukocytes = leukocytes.iter().map(
+        |(cell, transform, vel, health, unit, produced_by)| {
+            SavedCell::Leukocyte {
+                cell: cell.clone(),
+                pos: transform.translation.truncate() / PHYSICS_SCALE,
+                vel: Vec2::new(vel.linvel.x, vel.linvel.y),
+                health: health.health,
+                unit: unit.clone(),
+                producer: producer(produced_by),
+            }
+        },
+    );
+
+    let pathogens = pathogens.iter().map(
+        |(cell, transform, vel, health, unit, produced_by)| {
+            SavedCell::Pathogen {
+                cell: *cell,
+                pos: transform.translation.truncate() / PHYSICS_SCALE,
+                vel: Vec2::new(vel.linvel.x, vel.linvel.y),
+                health: health.health,
+                unit: unit.clone(),
+                producer: producer(produced_by),
+            }
+        },
+    );
+
+    let walls = walls
+        .iter()
+        .map(|wall| SavedWall {
+            x: wall.pos.x,
+            y: wall.pos.y,
+        })
+        .collect();
+
+    let game = SavedGame {
+        seed: rng.seed(),
+        draws: rng.draws(),
+        level: level.clone(),
+        state: SavedGameState {
+            tutorial: state.tutorial,
+            seconds: state.seconds,
+            vm: state.vm,
+            outcome: state.outcome,
+            message: state.message.clone(),
+            elapsed_seconds: time.elapsed_seconds(),
+            enemy_target: enemy_ai.target,
+        },
+        lymph_nodes,
+        cells: leukocytes.chain(pathogens).collect(),
+        walls,
+        memory: memory.clone(),
+        evolution: evolution.clone(),
+    };
+
+    match write(&path.0, &game) {
+        Ok(()) => info!("Game saved to `{}`", path.0.display()),
+        Err(err) => error!("{:?}", err),
+    }
+}
+
+fn write(path: &Path, game: &SavedGame) -> Result<()> {
+    let game = ron::ser::to_string_pretty(game, Default::default())
+        .context("Couldn't serialize game")?;
+
+    fs::write(path, game)
+        .with_context(|| format!("Couldn't write `{}`", path.display()))
+}
+
+fn read(path: &Path) -> Result<SavedGame> {
+    let game = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+    ron::from_str(&game)
+        .with_context(|| format!("Couldn't load `{}`", path.display()))
+}
+
+fn load(
+    mut events: EventReader<LoadGameEvent>,
+    path: Res<SavePath>,
+    mut commands: Commands,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+    assets: Option<Res<AssetServer>>,
+    mut rng: ResMut<GameRng>,
+    mut enemy_ai: ResMut<enemy_ai::State>,
+    mut memory: ResMut<ImmuneMemory>,
+    mut evolution: ResMut<Evolution>,
+    mut time: ResMut<GameTime>,
+    mut level: ResMut<Level>,
+    mut state: ResMut<GameState>,
+    entities: Query<Entity, GameEntity>,
+    mut level_changed_tx: EventWriter<LevelLayoutChanged>,
+    mut recompile_event_tx: EventWriter<RecompileEvent>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    let game = match read(&path.0) {
+        Ok(game) => game,
+
+        Err(err) => {
+            error!("{:?}", err);
+            return;
+        }
+    };
+
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *rng = GameRng::restore(game.seed, game.draws);
+    *level = game.level;
+
+    *state = GameState {
+        tutorial: game.state.tutorial,
+        seconds: game.state.seconds,
+        vm: game.state.vm,
+        outcome: game.state.outcome,
+        message: game.state.message,
+    };
+
+    time.set_elapsed_seconds(game.state.elapsed_seconds);
+    enemy_ai.target = game.state.enemy_target;
+    *memory = game.memory;
+    *evolution = game.evolution;
+
+    for wall in game.walls {
+        Wall {
+            pos: LevelPoint::new(wall.x, wall.y),
+        }
+        .spawn(&mut commands, assets.as_deref());
+    }
+
+    let lymph_nodes: Vec<_> = game
+        .lymph_nodes
+        .iter()
+        .map(|saved| {
+            let node = LymphNode {
+                pos: LevelPoint::new(saved.x, saved.y),
+                resource: saved.resource,
+                targets: vec![],
+                product: saved.pathogen.map(LymphNodeProduct::Pathogen),
+                parents: vec![],
+                warning: None,
+                diagnostic: None,
+                state: LymphNodeState {
+                    is_paused: saved.is_paused,
+                    is_awaiting_resources: false,
+                },
+                production: saved.production,
+                production_tt: saved.production_tt,
+                rally: None,
+                stock: Default::default(),
+                flow: LymphNodeFlow::Idle,
+            };
+
+            let entity = node.spawn(
+                &mut commands,
+                LymphNodeAssets::new(
+                    assets.as_deref(),
+                    meshes.as_deref_mut(),
+                    materials.as_deref_mut(),
+                ),
+                saved.alignment,
+            );
+
+            commands.entity(entity).insert(Health {
+                health: saved.health,
+                ..Health::lymph_node()
+            });
+
+            (entity, node)
+        })
+        .collect();
+
+    // Now that all the lymph nodes have got their entities, we can restore the
+    // links (and rally points, and products exchanged) between them
+    for (saved, (entity, node)) in game.lymph_nodes.iter().zip(&lymph_nodes) {
+        let mut targets = Vec::new();
+
+        for link in saved.links() {
+            let (target, target_node) =
+                if let Some(target) = lymph_nodes.get(link.target) {
+                    target
+                } else {
+                    continue;
+                };
+
+            // Connections are usually spawned after compiling the nodes, but
+            // since there might be products travelling along them, we have to
+            // spawn them here
+            let mut connection = LymphNodeConnection::new(
+                *entity,
+                Level::local_to_world(node.pos),
+                *target,
+                Level::local_to_world(target_node.pos),
+            );
+
+            connection.packets = link.packets;
+            connection.spawn(&mut commands);
+
+            targets.push(*target);
+        }
+
+        let rally = match saved.rally {
+            Some(SavedRally::Point(pos)) => Some(LymphNodeRally::Point(pos)),
+
+            Some(SavedRally::LymphNode(rally)) => lymph_nodes
+                .get(rally)
+                .map(|&(rally, _)| LymphNodeRally::LymphNode(rally)),
+
+            None => None,
+        };
+
+        let stock = saved
+            .stock
+            .iter()
+            .filter_map(|&(parent, count)| {
+                let &(parent, _) = lymph_nodes.get(parent)?;
+
+                Some((parent, count))
+            })
+            .collect();
+
+        commands.entity(*entity).insert(LymphNode {
+            targets,
+            rally,
+            stock,
+            ..node.clone()
+        });
+    }
+
+    for cell in game.cells {
+        let (entity, producer) = match cell {
+            SavedCell::Leukocyte {
+                cell,
+                pos,
+                vel,
+                health,
+                unit,
+                producer,
+            } => {
+                let entity =
+                    cell.spawn(&mut commands, assets.as_deref(), pos, vel);
+
+                commands
+                    .entity(entity)
+                    .insert(Health {
+                        health,
+                        regen_rate: cell.props.regen,
+                        ..Health::with_health(cell.props.hp)
+                    })
+                    .insert(unit);
+
+                (entity, producer)
+            }
+
+            SavedCell::Pathogen {
+                cell,
+                pos,
+                vel,
+                health,
+                unit,
+                producer,
+            } => {
+                let entity =
+                    cell.spawn(&mut commands, assets.as_deref(), pos, vel);
+
+                commands
+                    .entity(entity)
+                    .insert(Health {
+                        health,
+                        ..Health::with_health(cell.max_hp())
+                    })
+                    .insert(unit);
+
+                (entity, producer)
+            }
+        };
+
+        if let Some(&(node, _)) =
+            producer.and_then(|producer| lymph_nodes.get(producer))
+        {
+            commands.entity(entity).insert(ProducedBy(node));
+        }
+    }
+
+    level_changed_tx.send(LevelLayoutChanged);
+    recompile_event_tx.send(RecompileEvent);
+
+    info!("Game loaded from `{}`", path.0.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use bevy::ecs::system::CommandQueue;
+    use bevy::math::vec2;
+    use rand::Rng;
+
+    use super::*;
+
+    /// Returns a world with everything `save()` and `load()` need; the game
+    /// gets saved into a temporary file named after `name`.
+    fn world(name: &str) -> World {
+        let path = env::temp_dir().join(format!(
+            "save-{}-{}.ron",
+            name,
+            process::id()
+        ));
+
+        let mut world = World::new();
+
+        world.insert_resource(SavePath(path));
+        world.insert_resource(GameRng::new(1234));
+        world.insert_resource(GameTime::default());
+        world.insert_resource(Level::start(&mut GameRng::new(0)));
+        world.insert_resource(GameState::default());
+        world.insert_resource(ImmuneMemory::default());
+        world.insert_resource(Evolution::default());
+        world.insert_resource(enemy_ai::State::default());
+        world.insert_resource(Events::<SaveGameEvent>::default());
+        world.insert_resource(Events::<LoadGameEvent>::default());
+        world.insert_resource(Events::<LevelLayoutChanged>::default());
+        world.insert_resource(Events::<RecompileEvent>::default());
+        world
+    }
+
+    fn draw(world: &mut World) -> Vec<u64> {
+        let mut rng = world.get_resource_mut::<GameRng>().unwrap();
+
+        (0..5).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = world("round-trip");
+
+        let pathogen = {
+            let mut queue = CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, &world);
+
+            let pathogen = Pathogen::random(&mut GameRng::new(0)).spawn(
+                &mut commands,
+                None,
+                vec2(1.0, 2.0),
+                vec2(0.5, 0.0),
+            );
+
+            queue.apply(&mut world);
+            pathogen
+        };
+
+        {
+            let mut unit = world.get_mut::<Unit>(pathogen).unwrap();
+
+            unit.target = Some(vec2(300.0, 400.0));
+            unit.path = vec![vec2(200.0, 300.0), vec2(300.0, 400.0)];
+            unit.step = 1;
+            unit.caged_for = 2.5;
+            unit.lifespan = Some(7.5);
+        }
+
+        world.get_mut::<Health>(pathogen).unwrap().health = 0.5;
+
+        // Move the generator away from its initial state, so that restarting
+        // it from the seed can't pass for restoring it
+        draw(&mut world);
+
+        world
+            .get_resource_mut::<Events<SaveGameEvent>>()
+            .unwrap()
+            .send(SaveGameEvent);
+
+        SystemStage::single(save).run(&mut world);
+
+        let expected_draws = draw(&mut world);
+
+        world
+            .get_resource_mut::<Events<LoadGameEvent>>()
+            .unwrap()
+            .send(LoadGameEvent);
+
+        SystemStage::single(load).run(&mut world);
+
+        assert_eq!(expected_draws, draw(&mut world));
+
+        let pathogens: Vec<_> = world
+            .query::<(Entity, &Pathogen, &Unit, &Health, &Transform)>()
+            .iter(&world)
+            .collect();
+
+        assert_eq!(1, pathogens.len());
+
+        let (entity, _, unit, health, transform) = pathogens[0];
+
+        assert_ne!(pathogen, entity);
+        assert_eq!(Some(vec2(300.0, 400.0)), unit.target);
+        assert_eq!(vec![vec2(200.0, 300.0), vec2(300.0, 400.0)], unit.path);
+        assert_eq!(1, unit.step);
+        assert_eq!(2.5, unit.caged_for);
+        assert_eq!(Some(7.5), unit.lifespan);
+        assert_eq!(0.5, health.health);
+
+        assert_eq!(
+            vec2(1.0, 2.0) * PHYSICS_SCALE,
+            transform.translation.truncate()
+        );
+
+        let _ = fs::remove_file(&world.get_resource::<SavePath>().unwrap().0);
+    }
+
+    #[test]
+    fn legacy_lymph_node_link() {
+        let node: SavedLymphNode = ron::from_str(
+            "(
+                x: 0,
+                y: 0,
+                resource: None,
+                target: Some(1),
+                is_paused: false,
+                alignment: Player,
+                health: 1.0,
+                production_tt: 0.0,
+                pathogen: None,
+                packets: [0.5],
+            )",
+        )
+        .unwrap();
+
+        let links = node.links();
+
+        assert_eq!(1, links.len());
+        assert_eq!(1, links[0].target);
+        assert_eq!(vec![0.5], links[0].packets);
+    }
+}
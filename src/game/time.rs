@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Real-time frames longer than this get truncated, so that e.g. a hiccup
+/// while loading assets doesn't make the simulation jump ahead (in seconds)
+const MAX_REAL_DELTA: f32 = 0.1;
+
+pub fn initialize(app: &mut App) {
+    app.add_system_to_stage(CoreStage::PreUpdate, tick.label(TickGameTime));
+}
+
+/// Label of the system that advances `GameTime` at the beginning of each
+/// frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub struct TickGameTime;
+
+/// Clock that drives the simulation - unlike `Time`, it can be paused and sped
+/// up or slowed down by the player.
+///
+/// Everything that affects the game's outcome (the wave VM, lymph nodes'
+/// production, health regeneration, physics etc.) should use this clock
+/// instead of `Time` or `Instant::now()`.
+#[derive(Clone, Debug, Default)]
+pub struct GameTime {
+    speed: GameSpeed,
+    fixed_delta: Option<f32>,
+    delta: f32,
+    elapsed: f32,
+}
+
+impl GameTime {
+    /// Creates a clock that advances by `delta` each frame, regardless of how
+    /// much real time has passed - used in headless mode to simulate games as
+    /// fast as possible.
+    pub fn fixed(delta: f32) -> Self {
+        Self {
+            fixed_delta: Some(delta),
+            ..Default::default()
+        }
+    }
+
+    /// Returns how many (in-game) seconds have passed since the last frame
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    /// Returns how many (in-game) seconds have passed since the game started
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn set_elapsed_seconds(&mut self, elapsed: f32) {
+        self.elapsed = elapsed;
+    }
+
+    pub fn speed(&self) -> GameSpeed {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: GameSpeed) {
+        self.speed = speed;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.speed == GameSpeed::Paused
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameSpeed {
+    Paused,
+    Half,
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl GameSpeed {
+    pub fn variants() -> impl Iterator<Item = Self> {
+        [
+            Self::Paused,
+            Self::Half,
+            Self::Normal,
+            Self::Double,
+            Self::Quadruple,
+        ]
+        .into_iter()
+    }
+
+    pub fn factor(self) -> f32 {
+        match self {
+            Self::Paused => 0.0,
+            Self::Half => 0.5,
+            Self::Normal => 1.0,
+            Self::Double => 2.0,
+            Self::Quadruple => 4.0,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Paused => "||",
+            Self::Half => "0.5x",
+            Self::Normal => "1x",
+            Self::Double => "2x",
+            Self::Quadruple => "4x",
+        }
+    }
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+fn tick(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    let real_delta = game_time
+        .fixed_delta
+        .unwrap_or_else(|| time.delta_seconds().min(MAX_REAL_DELTA));
+
+    game_time.delta = real_delta * game_time.speed.factor();
+    game_time.elapsed += game_time.delta;
+}
@@ -0,0 +1,154 @@
+//! Runs the game without a window or renderer - used for balancing, where we
+//! want to simulate lots of games in a row and just look at their outcomes.
+//!
+//! Since there's no player, lymph nodes are configured by a simple autoplayer
+//! (see `autoplayer.rs`).
+
+mod autoplayer;
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bevy::core::CorePlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::transform::TransformPlugin;
+
+use crate::game::{GameOutcome, GamePlugin, GameState, GameTime, LevelVm};
+use crate::level::Level;
+use crate::rng::GameRng;
+use crate::systems::bio::LymphNode;
+use crate::systems::units::Alignment;
+use crate::systems::{bio, enemy_ai, evolution, immunity, physics, units};
+use crate::{compiling, pathfinding};
+
+/// Length of a single simulated frame, in (in-game) seconds; since the game is
+/// driven by `GameTime`, we don't have to wait for the real time to pass and so
+/// the simulation runs as fast as the CPU allows.
+const FRAME_DURATION: f32 = 1.0 / 60.0;
+
+// LogPlugin can be initialized only once per process
+static IS_LOGGING_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug)]
+pub struct HeadlessConfig {
+    pub seed: u64,
+
+    /// Maximum (in-game) duration of the game, in seconds
+    pub max_seconds: f32,
+
+    /// Maximum number of waves to play, after which the game is stopped
+    pub max_waves: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Outcome {
+    pub seed: u64,
+    pub waves_survived: usize,
+    pub nodes_held: usize,
+    pub enemy_nodes: usize,
+    pub seconds: f32,
+
+    /// `None` if the game has been stopped before being won or lost
+    pub result: Option<GameOutcome>,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed={} result={} waves_survived={} nodes_held={} enemy_nodes={} \
+             time={:.1}s",
+            self.seed,
+            match self.result {
+                Some(GameOutcome::Victory) => "victory",
+                Some(GameOutcome::Defeat) => "defeat",
+                None => "survived",
+            },
+            self.waves_survived,
+            self.nodes_held,
+            self.enemy_nodes,
+            self.seconds,
+        )
+    }
+}
+
+/// Plays a single game until it's lost or until it reaches one of the limits
+/// from `config`, and returns how it went.
+pub fn simulate(config: HeadlessConfig) -> Outcome {
+    let mut app = App::new();
+
+    app.insert_resource(GameRng::new(config.seed))
+        .add_plugin(CorePlugin::default())
+        .add_plugin(TransformPlugin::default());
+
+    if !IS_LOGGING_INITIALIZED.swap(true, Ordering::SeqCst) {
+        app.add_plugin(LogPlugin::default());
+    }
+
+    app.add_plugin(compiling::CompilingPlugin)
+        .add_plugin(pathfinding::PathfindingPlugin)
+        .add_plugin(GamePlugin)
+        .insert_resource(GameState {
+            tutorial: false,
+            vm: LevelVm::AwaitingStart { at: 0.0 },
+            ..Default::default()
+        })
+        .insert_resource(GameTime::fixed(FRAME_DURATION));
+
+    bio::initialize_simulation(&mut app);
+    enemy_ai::initialize(&mut app);
+    evolution::initialize(&mut app);
+    immunity::initialize(&mut app);
+    physics::initialize_simulation(&mut app);
+    units::initialize_simulation(&mut app);
+    autoplayer::initialize(&mut app);
+
+    loop {
+        app.update();
+
+        let state = app.world.get_resource::<GameState>().unwrap();
+        let level = app.world.get_resource::<Level>().unwrap();
+
+        if state.is_over()
+            || state.seconds >= config.max_seconds
+            || level.wave_idx > config.max_waves
+        {
+            break;
+        }
+    }
+
+    outcome(&mut app.world, config.seed)
+}
+
+fn outcome(world: &mut World, seed: u64) -> Outcome {
+    let (nodes_held, enemy_nodes) = world
+        .query_filtered::<&Alignment, With<LymphNode>>()
+        .iter(world)
+        .fold((0, 0), |(held, enemies), alignment| match alignment {
+            Alignment::Player => (held + 1, enemies),
+            Alignment::Enemy => (held, enemies + 1),
+            Alignment::Unaligned => (held, enemies),
+        });
+
+    let state = world.get_resource::<GameState>().unwrap();
+    let level = world.get_resource::<Level>().unwrap();
+
+    // Unless we're already waiting for the next wave, the current one hasn't
+    // been survived yet
+    let waves_survived =
+        if matches!(state.vm, LevelVm::AwaitingWaveStart { .. }) {
+            level.wave_idx
+        } else {
+            level.wave_idx.saturating_sub(1)
+        };
+
+    Outcome {
+        seed,
+        waves_survived,
+        nodes_held,
+        enemy_nodes,
+        seconds: state.seconds,
+        result: state.outcome,
+    }
+}
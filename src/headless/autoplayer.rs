@@ -0,0 +1,148 @@
+//! A very simple player: it pairs idle lymph nodes into `Body -> Antigen`
+//! chains producing leukocytes against the most common pathogen and then
+//! periodically sends the whole army towards the closest enemy lymph node.
+
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use itertools::Itertools;
+
+use crate::compiling::RecompileEvent;
+use crate::game::GameTime;
+use crate::pathfinding::NavigateUnit;
+use crate::systems::bio::{
+    Antigen, AntigenBinder, Body, Leukocyte, LymphNode, LymphNodeResource,
+    Pathogen,
+};
+use crate::systems::units::Alignment;
+
+/// How often (in seconds) the autoplayer reconsiders its moves
+const THINKING_INTERVAL: f32 = 5.0;
+
+pub fn initialize(app: &mut App) {
+    app.insert_resource(State::default())
+        .add_system(configure_lymph_nodes)
+        .add_system(attack_lymph_nodes);
+}
+
+#[derive(Default)]
+struct State {
+    configure_tt: f32,
+    attack_tt: f32,
+}
+
+fn configure_lymph_nodes(
+    time: Res<GameTime>,
+    mut state: ResMut<State>,
+    mut lymph_nodes: Query<(Entity, &mut LymphNode, &Alignment, &Transform)>,
+    pathogens: Query<&Pathogen>,
+    mut recompile_tx: EventWriter<RecompileEvent>,
+) {
+    state.configure_tt += time.delta_seconds();
+
+    if state.configure_tt < THINKING_INTERVAL {
+        return;
+    }
+
+    state.configure_tt = 0.0;
+
+    let is_idle = |node: &LymphNode| {
+        node.resource.is_none()
+            && node.parents.is_empty()
+            && node.targets.is_empty()
+    };
+
+    let mut idle_nodes = lymph_nodes
+        .iter()
+        .filter(|(_, node, alignment, _)| {
+            alignment.is_player() && is_idle(node)
+        })
+        .map(|(entity, _, _, transform)| {
+            (entity, transform.translation.truncate())
+        })
+        .collect_vec();
+
+    if idle_nodes.len() < 2 {
+        return;
+    }
+
+    let antigen = most_common_antigen(&pathogens);
+    let mut body = Body::variants().cycle();
+
+    while let Some((source, source_pos)) = idle_nodes.pop() {
+        let target = idle_nodes
+            .iter()
+            .enumerate()
+            .min_by(|(_, (_, lhs)), (_, (_, rhs))| {
+                lhs.distance(source_pos)
+                    .partial_cmp(&rhs.distance(source_pos))
+                    .unwrap_or(Ordering::Greater)
+            })
+            .map(|(idx, _)| idx);
+
+        let (target, _) = if let Some(target) = target {
+            idle_nodes.remove(target)
+        } else {
+            break;
+        };
+
+        if let Ok((_, mut node, _, _)) = lymph_nodes.get_mut(source) {
+            node.resource = body.next().map(LymphNodeResource::Body);
+            node.targets = vec![target];
+        }
+
+        if let Ok((_, mut node, _, _)) = lymph_nodes.get_mut(target) {
+            node.resource =
+                Some(LymphNodeResource::Antigen(AntigenBinder(antigen)));
+        }
+    }
+
+    recompile_tx.send(RecompileEvent);
+}
+
+fn most_common_antigen(pathogens: &Query<&Pathogen>) -> Antigen {
+    Antigen::variants()
+        .max_by_key(|&antigen| {
+            pathogens
+                .iter()
+                .filter(|pathogen| pathogen.antigen == antigen)
+                .count()
+        })
+        .unwrap()
+}
+
+fn attack_lymph_nodes(
+    time: Res<GameTime>,
+    mut state: ResMut<State>,
+    lymph_nodes: Query<(&Alignment, &Transform), With<LymphNode>>,
+    leukocytes: Query<(Entity, &Transform), With<Leukocyte>>,
+    mut navigate_tx: EventWriter<NavigateUnit>,
+) {
+    state.attack_tt += time.delta_seconds();
+
+    if state.attack_tt < THINKING_INTERVAL {
+        return;
+    }
+
+    state.attack_tt = 0.0;
+
+    let enemy_lymph_nodes = lymph_nodes
+        .iter()
+        .filter(|(alignment, _)| alignment.is_enemy())
+        .map(|(_, transform)| transform.translation.truncate())
+        .collect_vec();
+
+    for (entity, transform) in leukocytes.iter() {
+        let pos = transform.translation.truncate();
+
+        let target = enemy_lymph_nodes.iter().min_by(|lhs, rhs| {
+            lhs.distance(pos)
+                .partial_cmp(&rhs.distance(pos))
+                .unwrap_or(Ordering::Greater)
+        });
+
+        if let Some(&target) = target {
+            navigate_tx.send(NavigateUnit { entity, target });
+        }
+    }
+}
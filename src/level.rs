@@ -1,32 +1,136 @@
+mod difficulty;
 mod gen;
+mod layout;
+mod load;
+mod validate;
 
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::Path;
+
+use anyhow::Result;
 use bevy::math::{vec2, Vec2};
 use nalgebra::Point2;
-
-use crate::systems::bio::Wall;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub use self::difficulty::{Difficulty, DifficultyStage};
+pub use self::gen::{LevelGenerator, LevelGeneratorKind};
+pub use self::layout::{LevelLayout, LevelLayoutNode};
+pub use self::validate::LevelIssues;
+use crate::systems::bio::{
+    Antigen, Body, LymphNodeResource, PathogenKind, Wall,
+};
 use crate::systems::physics::PHYSICS_SCALE;
 use crate::systems::units::Alignment;
 
 pub type LevelPoint = Point2<i32>;
 
 // TODO(pwy) since there's just one level anyway, we could use a better name (World?)
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Level {
     pub chambers: Vec<LevelChamber>,
     pub corridors: Vec<LevelCorridor>,
+    pub areas: Vec<LevelArea>,
     pub wave: LevelWave,
     pub wave_idx: usize,
+    pub upcoming_waves: VecDeque<LevelWave>,
+    pub kind: LevelKind,
+
+    /// Controls how the level grows (if it's generated) and how tough its
+    /// pathogens are
+    pub difficulty: Difficulty,
+
+    /// Walls and lymph nodes built by the waves preceding the current one
+    pub map: LevelMap,
+
+    /// Condition the player has to fulfill to win; levels without one (e.g.
+    /// the generated ones) go on until the player loses
+    pub victory: Option<LevelVictory>,
 }
 
 impl Level {
     pub const FIELD_SIZE: f32 = Wall::SIZE * PHYSICS_SCALE;
 
-    pub fn start() -> Self {
-        gen::start()
+    pub fn start(
+        generator: LevelGeneratorKind,
+        difficulty: Difficulty,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut level = Self {
+            kind: LevelKind::Generated(generator),
+            difficulty,
+            ..Self::empty()
+        };
+
+        gen::start(&mut level, generator, rng);
+        level
+    }
+
+    pub fn load(path: impl AsRef<Path>, rng: &mut impl Rng) -> Result<Self> {
+        load::load(path.as_ref(), rng)
+    }
+
+    pub fn parse(src: &str, rng: &mut impl Rng) -> Result<Self> {
+        load::parse(src, rng)
+    }
+
+    /// Returns a level without any walls, lymph nodes or waves - used as a
+    /// blank canvas by the level editor.
+    pub fn empty() -> Self {
+        Self {
+            chambers: vec![],
+            corridors: vec![],
+            areas: vec![],
+            wave: Default::default(),
+            wave_idx: 0,
+            upcoming_waves: Default::default(),
+            kind: LevelKind::Authored,
+            difficulty: Default::default(),
+            map: Default::default(),
+            victory: None,
+        }
+    }
+
+    pub fn progress(&mut self, rng: &mut impl Rng) {
+        self.map.apply(&self.wave.ops);
+
+        match self.kind {
+            LevelKind::Generated(generator) => {
+                gen::progress(self, generator, rng);
+            }
+
+            LevelKind::Authored => {
+                self.wave = self.upcoming_waves.pop_front().unwrap_or_default();
+                self.wave_idx += 1;
+            }
+        }
+    }
+
+    /// Returns whether there's any wave left to play - generated levels go on
+    /// forever, while authored ones end after their last wave.
+    pub fn has_next_wave(&self) -> bool {
+        match self.kind {
+            LevelKind::Generated(_) => true,
+            LevelKind::Authored => !self.upcoming_waves.is_empty(),
+        }
+    }
+
+    /// Returns the stage of difficulty that applies to the current wave.
+    pub fn stage(&self) -> &DifficultyStage {
+        self.difficulty.stage(self.wave_idx)
+    }
+
+    /// Returns the moment (in seconds since the game started) at which the
+    /// next wave should begin, if the level specifies one.
+    pub fn next_wave_starts_at(&self) -> Option<f32> {
+        self.upcoming_waves.front().and_then(|wave| wave.starts_at)
     }
 
-    pub fn progress(&mut self) {
-        gen::progress(self)
+    /// Checks whether all of the lymph nodes (including the ones the current
+    /// wave is about to build) can be reached by the player's units.
+    pub fn validate(&self) -> LevelIssues {
+        validate::validate(self)
     }
 
     pub fn bounds(&self) -> (i32, i32, i32, i32) {
@@ -42,6 +146,15 @@ impl Level {
             max_y = max_y.max(chamber.y + chamber.r);
         }
 
+        for area in &self.areas {
+            for &(x, y) in &area.fields {
+                min_x = min_x.min(x - 1);
+                min_y = min_y.min(y - 1);
+                max_x = max_x.max(x + 1);
+                max_y = max_y.max(y + 1);
+            }
+        }
+
         (min_x, min_y, max_x, max_y)
     }
 
@@ -51,6 +164,12 @@ impl Level {
         LevelPoint::new(pos.x as i32, pos.y as i32)
     }
 
+    /// Like `world_to_local()`, but rounds to the nearest field instead of
+    /// truncating towards zero.
+    pub fn world_to_nearest_local(pos: Vec2) -> LevelPoint {
+        Self::world_to_local(pos + pos.signum() * Self::FIELD_SIZE / 2.0)
+    }
+
     pub fn local_to_world(pos: LevelPoint) -> Vec2 {
         vec2(
             (pos.x as f32) * Self::FIELD_SIZE,
@@ -59,7 +178,32 @@ impl Level {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelKind {
+    /// Level is procedurally generated (using given generator) and grows
+    /// with each wave
+    Generated(LevelGeneratorKind),
+
+    /// Level has been loaded from a file and consists of a fixed set of waves
+    Authored,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LevelVictory {
+    /// Player wins after given number of waves has been played and all the
+    /// pathogens from the last one have been killed
+    SurviveWaves(usize),
+
+    /// Player wins after the level's script is done and all of the
+    /// enemy-owned lymph nodes have been captured
+    CaptureAllEnemyNodes,
+
+    /// Player wins if the lymph node at given position remains player-owned
+    /// for given number of (in-game) seconds
+    KeepNodeAlive { x: i32, y: i32, secs: f32 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LevelChamber {
     pub x: i32,
     pub y: i32,
@@ -81,7 +225,53 @@ impl LevelChamber {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Free-form chamber, described by the fields it consists of - used by the
+/// generators whose chambers aren't circles.
+///
+/// Generator surrounds the area with walls on its own.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LevelArea {
+    pub fields: BTreeSet<(i32, i32)>,
+}
+
+impl LevelArea {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.fields.contains(&(x, y))
+    }
+
+    /// Returns the field closest to the area's center of mass
+    fn center(&self) -> (i32, i32) {
+        let len = self.fields.len().max(1) as f32;
+
+        let (sum_x, sum_y) =
+            self.fields.iter().fold((0.0, 0.0), |(sx, sy), &(x, y)| {
+                (sx + x as f32, sy + y as f32)
+            });
+
+        let (cx, cy) = (sum_x / len, sum_y / len);
+
+        self.fields
+            .iter()
+            .copied()
+            .min_by(|&(x1, y1), &(x2, y2)| {
+                let d1 = (x1 as f32 - cx).powi(2) + (y1 as f32 - cy).powi(2);
+                let d2 = (x2 as f32 - cx).powi(2) + (y2 as f32 - cy).powi(2);
+
+                d1.partial_cmp(&d2).unwrap_or(Ordering::Greater)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns fields that aren't adjacent to any wall - those are where
+    /// lymph nodes can be placed
+    fn interior(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.fields.iter().copied().filter(|&(x, y)| {
+            (-1..=1).all(|dx| (-1..=1).all(|dy| self.contains(x + dx, y + dy)))
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LevelCorridor {
     pub x1: i32,
     pub y1: i32,
@@ -162,12 +352,44 @@ impl LevelCorridor {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// Walls and lymph nodes the level consists of, as built by its wave ops.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LevelMap {
+    pub walls: BTreeSet<(i32, i32)>,
+    pub lymph_nodes: BTreeMap<(i32, i32), Alignment>,
+}
+
+impl LevelMap {
+    pub fn apply(&mut self, ops: &[LevelWaveOp]) {
+        for op in ops {
+            match *op {
+                LevelWaveOp::AddWall { x, y } => {
+                    self.walls.insert((x, y));
+                }
+
+                LevelWaveOp::RemoveWall { x, y } => {
+                    self.walls.remove(&(x, y));
+                }
+
+                LevelWaveOp::AddLymphNode {
+                    x, y, alignment, ..
+                } => {
+                    self.lymph_nodes.insert((x, y), alignment);
+                }
+
+                _ => (),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LevelWave {
     pub ops: Vec<LevelWaveOp>,
+    pub starts_at: Option<f32>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum LevelWaveOp {
     AddWall {
         x: i32,
@@ -183,5 +405,50 @@ pub enum LevelWaveOp {
         x: i32,
         y: i32,
         alignment: Alignment,
+        resource: Option<LymphNodeResource>,
+    },
+
+    /// Spawns a group of pathogens; `pos` and `vel` are in physics units
+    SpawnPathogens {
+        pos: Vec2,
+        vel: Vec2,
+        body: Body,
+        antigen: Antigen,
+
+        #[serde(default)]
+        kind: PathogenKind,
+
+        count: usize,
+    },
+
+    /// Pauses the script for given number of (in-game) seconds
+    Sleep {
+        secs: f32,
+    },
+
+    /// Displays a message on the screen for given number of seconds
+    ShowMessage {
+        text: String,
+        secs: f32,
+    },
+
+    /// Pauses the script until given condition is met
+    WaitUntil {
+        condition: LevelWaveCondition,
     },
+
+    /// Makes the enemy attack given position (in physics units) instead of the
+    /// closest player-owned lymph node; `None` restores the default behavior
+    SetEnemyTarget {
+        pos: Option<Vec2>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelWaveCondition {
+    /// All of the enemy-owned lymph nodes have been captured by the player
+    AllEnemyNodesCaptured,
+
+    /// At most given number of pathogens remains alive
+    PathogensRemaining(usize),
 }
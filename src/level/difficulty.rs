@@ -0,0 +1,391 @@
+//! Difficulty profiles, which control how generated levels grow wave after
+//! wave (see `levels/difficulty.toml`).
+
+use std::collections::BTreeSet;
+use std::{env, fs};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bevy::log::{error, warn};
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::systems::bio::PathogenKind;
+
+const DIFFICULTY_PATH: &str = "levels/difficulty.toml";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Difficulty {
+    pub name: String,
+
+    /// Stages, ordered by the wave they start at
+    #[serde(rename = "Stage")]
+    pub stages: Vec<DifficultyStage>,
+}
+
+/// Describes waves starting at given index (up to the next stage).
+///
+/// Ranges are inclusive and get rolled anew for each wave.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct DifficultyStage {
+    pub wave: usize,
+
+    /// Number of chambers (or groups of areas, depending on the generator)
+    /// added in each wave
+    pub chambers: (usize, usize),
+
+    /// Radius of the chambers, in fields; used only by the `chambers`
+    /// generator
+    pub radius: (i32, i32),
+
+    /// Number of enemy-owned lymph nodes per chamber
+    pub enemy_nodes: (usize, usize),
+
+    /// Number of existing chambers each new chamber gets connected to
+    pub corridors: (usize, usize),
+
+    /// Seconds between clearing a wave and the start of the next one
+    pub wave_delay: f32,
+
+    /// Health of the pathogens produced by enemy-owned lymph nodes
+    pub pathogen_hp: f32,
+
+    /// Kinds of pathogens enemy-owned lymph nodes can produce; each node
+    /// picks one of them at random when it gets captured
+    #[serde(default = "default_pathogen_kinds")]
+    pub pathogen_kinds: Vec<PathogenKind>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct DifficultiesDef {
+    profile: Vec<Difficulty>,
+}
+
+impl Difficulty {
+    /// Loads all the profiles from `levels/difficulty.toml`.
+    pub fn load_all() -> Result<Vec<Self>> {
+        let profiles = fs::read_to_string(DIFFICULTY_PATH)
+            .with_context(|| format!("Couldn't read `{}`", DIFFICULTY_PATH))?;
+
+        Self::parse_all(&profiles)
+            .with_context(|| format!("Couldn't load `{}`", DIFFICULTY_PATH))
+    }
+
+    pub fn parse_all(src: &str) -> Result<Vec<Self>> {
+        let profiles: DifficultiesDef = toml::from_str(src)?;
+        let mut names = BTreeSet::new();
+
+        for profile in &profiles.profile {
+            if !names.insert(&profile.name) {
+                bail!("Profile `{}` is defined twice", profile.name);
+            }
+
+            profile.validate().with_context(|| {
+                format!("Profile `{}` is invalid", profile.name)
+            })?;
+        }
+
+        Ok(profiles.profile)
+    }
+
+    /// Returns profile pointed at by the `DIFFICULTY` environment variable
+    /// or, if it's not set, the default one.
+    pub fn from_env() -> Self {
+        let name = if let Ok(name) = env::var("DIFFICULTY") {
+            name
+        } else {
+            return Default::default();
+        };
+
+        let profile: Result<_> = try {
+            Self::load_all()?
+                .into_iter()
+                .find(|profile| profile.name == name)
+                .ok_or_else(|| anyhow!("Unknown difficulty `{}`", name))?
+        };
+
+        profile.unwrap_or_else(|err| {
+            error!("{:?}", err);
+            warn!("Falling back to the default difficulty");
+
+            Default::default()
+        })
+    }
+
+    /// Returns stage that applies to given wave.
+    pub fn stage(&self, wave_idx: usize) -> &DifficultyStage {
+        self.stages
+            .iter()
+            .rev()
+            .find(|stage| stage.wave <= wave_idx)
+            .or_else(|| self.stages.first())
+            .expect("Difficulty has no stages")
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.stages.is_empty() {
+            bail!("Profile has no stages");
+        }
+
+        for [a, b] in self.stages.array_windows() {
+            if a.wave >= b.wave {
+                bail!(
+                    "Stages have to be ordered by their `Wave` (found {} \
+                     before {})",
+                    a.wave,
+                    b.wave
+                );
+            }
+        }
+
+        for stage in &self.stages {
+            stage.validate().with_context(|| {
+                format!("Stage starting at wave {} is invalid", stage.wave)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Difficulty {
+    /// Returns the `normal` profile, so that the game remains playable
+    /// without `levels/difficulty.toml`.
+    fn default() -> Self {
+        let stage = DifficultyStage {
+            wave: 0,
+            chambers: (1, 1),
+            radius: (8, 19),
+            enemy_nodes: (2, 2),
+            corridors: (1, 3),
+            wave_delay: 25.0,
+            pathogen_hp: 1.0,
+            pathogen_kinds: default_pathogen_kinds(),
+        };
+
+        Self {
+            name: "normal".into(),
+            stages: vec![
+                stage.clone(),
+                DifficultyStage {
+                    wave: 3,
+                    chambers: (1, 3),
+                    enemy_nodes: (4, 4),
+                    pathogen_kinds: vec![
+                        PathogenKind::Virus,
+                        PathogenKind::Bacterium,
+                        PathogenKind::Spore,
+                    ],
+                    ..stage
+                },
+            ],
+        }
+    }
+}
+
+impl DifficultyStage {
+    pub fn chambers(&self, rng: &mut impl Rng) -> usize {
+        rng.gen_range(self.chambers.0..=self.chambers.1)
+    }
+
+    pub fn radius(&self, rng: &mut impl Rng) -> i32 {
+        rng.gen_range(self.radius.0..=self.radius.1)
+    }
+
+    pub fn enemy_nodes(&self, rng: &mut impl Rng) -> usize {
+        rng.gen_range(self.enemy_nodes.0..=self.enemy_nodes.1)
+    }
+
+    pub fn corridors(&self, rng: &mut impl Rng) -> usize {
+        rng.gen_range(self.corridors.0..=self.corridors.1)
+    }
+
+    pub fn pathogen_kind(&self, rng: &mut impl Rng) -> PathogenKind {
+        self.pathogen_kinds.choose(rng).copied().unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let ranges = [
+            ("Chambers", self.chambers.0 as i32, self.chambers.1 as i32),
+            ("Radius", self.radius.0, self.radius.1),
+            (
+                "EnemyNodes",
+                self.enemy_nodes.0 as i32,
+                self.enemy_nodes.1 as i32,
+            ),
+            (
+                "Corridors",
+                self.corridors.0 as i32,
+                self.corridors.1 as i32,
+            ),
+        ];
+
+        for (name, min, max) in ranges {
+            if min > max {
+                bail!("`{}` is empty ({} > {})", name, min, max);
+            }
+        }
+
+        if self.radius.0 < 4 {
+            bail!("`Radius` has to be at least 4");
+        }
+
+        // Otherwise new chambers wouldn't be reachable
+        if self.corridors.0 < 1 {
+            bail!("`Corridors` has to be at least 1");
+        }
+
+        if self.wave_delay < 0.0 {
+            bail!("`WaveDelay` cannot be negative");
+        }
+
+        if self.pathogen_hp <= 0.0 {
+            bail!("`PathogenHp` has to be positive");
+        }
+
+        if self.pathogen_kinds.is_empty() {
+            bail!("`PathogenKinds` cannot be empty");
+        }
+
+        Ok(())
+    }
+}
+
+fn default_pathogen_kinds() -> Vec<PathogenKind> {
+    vec![PathogenKind::Virus]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+        [[Profile]]
+        Name = "test"
+
+        [[Profile.Stage]]
+        Wave = 0
+        Chambers = [1, 2]
+        Radius = [8, 12]
+        EnemyNodes = [1, 3]
+        Corridors = [1, 2]
+        WaveDelay = 20.0
+        PathogenHp = 1.0
+    "#;
+
+    fn parse_err(src: &str) -> String {
+        format!("{:?}", Difficulty::parse_all(src).unwrap_err())
+    }
+
+    #[test]
+    fn builtin_profiles() {
+        let profiles =
+            Difficulty::parse_all(include_str!("../../levels/difficulty.toml"))
+                .unwrap();
+
+        let names: Vec<_> = profiles.iter().map(|p| p.name.as_str()).collect();
+
+        assert_eq!(vec!["easy", "normal", "hard", "custom"], names);
+
+        // The default profile is used when `levels/difficulty.toml` is missing,
+        // so it should be the same as the one defined there
+        assert_eq!(Difficulty::default(), profiles[1]);
+    }
+
+    #[test]
+    fn default_profile_is_valid() {
+        Difficulty::default().validate().unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        let profiles = Difficulty::parse_all(PROFILE).unwrap();
+
+        assert_eq!(1, profiles.len());
+
+        let stage = profiles[0].stage(10);
+
+        assert_eq!((1, 2), stage.chambers);
+        assert_eq!((1, 3), stage.enemy_nodes);
+        assert_eq!(vec![PathogenKind::Virus], stage.pathogen_kinds);
+    }
+
+    #[test]
+    fn stage() {
+        let difficulty = Difficulty::default();
+
+        assert_eq!(0, difficulty.stage(0).wave);
+        assert_eq!(0, difficulty.stage(2).wave);
+        assert_eq!(3, difficulty.stage(3).wave);
+        assert_eq!(3, difficulty.stage(100).wave);
+    }
+
+    #[test]
+    fn duplicated_profile() {
+        let src = format!("{}{}", PROFILE, PROFILE);
+
+        assert!(parse_err(&src).contains("`test` is defined twice"));
+    }
+
+    #[test]
+    fn unknown_field() {
+        let src = PROFILE.replace("Corridors", "Tunnels");
+
+        assert!(parse_err(&src).contains("Tunnels"));
+    }
+
+    #[test]
+    fn profile_without_stages() {
+        let err = parse_err(
+            r#"
+                [[Profile]]
+                Name = "test"
+                Stage = []
+            "#,
+        );
+
+        assert!(err.contains("Profile has no stages"));
+    }
+
+    #[test]
+    fn unordered_stages() {
+        let src = format!(
+            "{}{}",
+            PROFILE,
+            PROFILE
+                .split("[[Profile.Stage]]")
+                .nth(1)
+                .map(|stage| format!("[[Profile.Stage]]{}", stage))
+                .unwrap()
+        );
+
+        assert!(parse_err(&src).contains("found 0 before 0"));
+    }
+
+    #[test]
+    fn invalid_stages() {
+        let cases = [
+            ("EnemyNodes = [1, 3]", "EnemyNodes = [3, 1]", "`EnemyNodes`"),
+            ("Radius = [8, 12]", "Radius = [2, 12]", "at least 4"),
+            ("Corridors = [1, 2]", "Corridors = [0, 2]", "at least 1"),
+            ("WaveDelay = 20.0", "WaveDelay = -1.0", "cannot be negative"),
+            ("PathogenHp = 1.0", "PathogenHp = 0.0", "has to be positive"),
+        ];
+
+        for (from, to, expected) in cases {
+            let err = parse_err(&PROFILE.replace(from, to));
+
+            assert!(
+                err.contains("Stage starting at wave 0 is invalid")
+                    && err.contains(expected),
+                "{} -> {}: {}",
+                from,
+                to,
+                err
+            );
+        }
+    }
+}
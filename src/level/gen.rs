@@ -1,191 +1,245 @@
-//! lord forgive me for i have sinned, but at some point i _will_ refactor it
-
-use std::f32::consts::TAU;
-
+//! Procedural level generators.
+//!
+//! All of them build the level by emitting `LevelWaveOp`s into its current
+//! wave, so that the level gets built (and grows) through the very same VM as
+//! the hand-authored ones.
+
+mod areas;
+mod bsp;
+mod caves;
+mod chambers;
+mod shapes;
+
+use std::env;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use bevy::log::{error, warn};
 use itertools::Itertools;
 use rand::prelude::SliceRandom;
-use rand::Rng;
-
-use super::{Level, LevelChamber, LevelCorridor, LevelWave, LevelWaveOp};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use self::bsp::BspGenerator;
+use self::caves::CavesGenerator;
+use self::chambers::ChambersGenerator;
+use self::shapes::ShapesGenerator;
+use super::{Level, LevelWaveOp};
+use crate::systems::bio::LymphNodeResource;
 use crate::systems::units::Alignment;
 
-pub fn start() -> Level {
-    let mut ops = Vec::new();
-
-    add_circle_wall(&mut ops, 0, 0, 15);
-    add_lymph_node(&mut ops, 0, 0, Alignment::Player);
-    add_lymph_node(&mut ops, -4, -4, Alignment::Player);
-    add_lymph_node(&mut ops, 4, -4, Alignment::Player);
-    add_lymph_node(&mut ops, -4, 4, Alignment::Player);
-    add_lymph_node(&mut ops, 4, 4, Alignment::Player);
-
-    Level {
-        chambers: vec![LevelChamber { x: 0, y: 0, r: 15 }],
-        corridors: vec![],
-        wave: LevelWave { ops },
-        wave_idx: 0,
-    }
+/// How many times a layout gets regenerated when it turns out to be invalid,
+/// before we give up on it
+const MAX_ATTEMPTS: usize = 5;
+
+pub trait LevelGenerator {
+    /// Builds the level's starting layout, with player-owned lymph nodes,
+    /// into `level.wave`.
+    fn start(&self, level: &mut Level, rng: &mut dyn RngCore);
+
+    /// Extends the level with new chambers (and enemy-owned lymph nodes) for
+    /// the upcoming wave; `level.wave` is already cleared at this point.
+    fn progress(&self, level: &mut Level, rng: &mut dyn RngCore);
 }
 
-pub fn progress(level: &mut Level) {
-    level.wave = Default::default();
-    level.wave_idx += 1;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelGeneratorKind {
+    /// Circular chambers connected with corridors
+    Chambers,
 
-    let mut rng = rand::thread_rng();
+    /// Caves grown through cellular automata
+    Caves,
 
-    let chamber_count: i32 = if level.wave_idx < 3 {
-        1
-    } else {
-        rng.gen_range(1..=3)
-    };
+    /// Rectangular rooms laid out through binary space partitioning
+    Rooms,
 
-    for _ in 0..chamber_count {
-        let chamber = spawn_chamber(level);
+    /// Rectangular and elliptical chambers
+    Shapes,
+}
 
-        add_circle_wall(&mut level.wave.ops, chamber.x, chamber.y, chamber.r);
+impl LevelGeneratorKind {
+    pub fn variants() -> impl Iterator<Item = Self> {
+        [Self::Chambers, Self::Caves, Self::Rooms, Self::Shapes].into_iter()
+    }
 
-        if level.wave_idx < 3 {
-            for _ in 0..2 {
-                spawn_chamber_lymph_node(&mut level.wave.ops, &chamber, true);
-            }
-        } else {
-            for n in 0..10 {
-                spawn_chamber_lymph_node(&mut level.wave.ops, &chamber, n <= 3);
-            }
-        }
+    /// Returns generator pointed at by the `GENERATOR` environment variable
+    /// or, if it's not set, the default one.
+    pub fn from_env() -> Self {
+        let generator = env::var("GENERATOR").ok().and_then(|generator| {
+            match generator.parse() {
+                Ok(generator) => Some(generator),
 
-        let chambers = level
-            .chambers
-            .iter()
-            .sorted_by(|a, b| {
-                let a = a.distance_to_squared(&chamber);
-                let b = b.distance_to_squared(&chamber);
+                Err(err) => {
+                    warn!("{}", err);
+                    None
+                }
+            }
+        });
 
-                a.cmp(&b)
-            })
-            .take(rng.gen_range(1..=3))
-            .cloned()
-            .collect_vec();
+        generator.unwrap_or_default()
+    }
 
-        level.chambers.push(chamber.clone());
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Chambers => "chambers",
+            Self::Caves => "caves",
+            Self::Rooms => "rooms",
+            Self::Shapes => "shapes",
+        }
+    }
 
-        for linked in chambers {
-            spawn_corridor(level, &chamber, &linked);
+    pub fn generator(self) -> &'static dyn LevelGenerator {
+        match self {
+            Self::Chambers => &ChambersGenerator,
+            Self::Caves => &CavesGenerator,
+            Self::Rooms => &BspGenerator,
+            Self::Shapes => &ShapesGenerator,
         }
     }
+}
 
-    for corridor in &level.corridors {
-        let (_, remove) = corridor.walls(&level.chambers);
-        remove_walls(&mut level.wave.ops, remove);
+impl Default for LevelGeneratorKind {
+    fn default() -> Self {
+        Self::Chambers
     }
 }
 
-fn spawn_chamber(level: &Level) -> LevelChamber {
-    let mut rng = rand::thread_rng();
-    let (mut min_x, mut min_y, mut max_x, mut max_y) = level.bounds();
+impl FromStr for LevelGeneratorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::variants()
+            .find(|generator| generator.label() == s)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown generator `{}` (expected one of: {})",
+                    s,
+                    Self::variants()
+                        .map(|generator| generator.label())
+                        .join(", ")
+                )
+            })
+    }
+}
 
-    for _ in 0..100 {
-        let r = rng.gen_range(8..20);
-        let xs = (min_x + r)..(max_x - r);
-        let ys = (min_y + r)..(max_y - r);
+/// Builds the level's starting layout, falling back to the `Chambers`
+/// generator when the chosen one doesn't yield a valid layout; returns whether
+/// the layout the level ended up with is valid.
+pub(super) fn start(
+    level: &mut Level,
+    generator: LevelGeneratorKind,
+    rng: &mut impl Rng,
+) -> bool {
+    start_with(level, generator.generator(), generator.label(), rng)
+}
 
-        if xs.is_empty() || ys.is_empty() {
-            continue;
-        }
+fn start_with(
+    level: &mut Level,
+    generator: &dyn LevelGenerator,
+    label: &str,
+    rng: &mut impl Rng,
+) -> bool {
+    let fallback = LevelGeneratorKind::Chambers;
 
-        let chamber = LevelChamber {
-            x: rng.gen_range(xs),
-            y: rng.gen_range(ys),
-            r,
-        };
+    if generate(level, rng, |level, rng| generator.start(level, rng)) {
+        return true;
+    }
 
-        let collides = level
-            .chambers
-            .iter()
-            .any(|chamber2| chamber2.collides_with(&chamber));
+    warn!(
+        "Couldn't generate a valid starting layout using the `{}` generator, \
+         falling back to the `{}` one",
+        label,
+        fallback.label(),
+    );
 
-        if !collides {
-            return chamber;
-        }
+    if generate(level, rng, |level, rng| {
+        fallback.generator().start(level, rng);
+    }) {
+        return true;
     }
 
-    let chamber = LevelChamber {
-        x: rng.gen_range(8..20),
-        y: rng.gen_range(10..30),
-        r: rng.gen_range(10..30),
-    };
-
-    match rng.gen_range(0..4) {
-        0 => {
-            min_x -= chamber.x + chamber.r;
+    // The player has to start somewhere, so as a last resort we go with
+    // whatever the fallback generator yields
+    error!(
+        "Couldn't generate a valid starting layout using the `{}` generator \
+         either",
+        fallback.label(),
+    );
 
-            LevelChamber {
-                x: min_x,
-                ..chamber
-            }
-        }
+    fallback.generator().start(level, rng);
+    false
+}
 
-        1 => {
-            min_y -= chamber.y + chamber.r;
+/// Generates the level's layout for the upcoming wave; returns whether it
+/// succeeded - when it doesn't, the wave doesn't build anything.
+pub(super) fn progress(
+    level: &mut Level,
+    generator: LevelGeneratorKind,
+    rng: &mut impl Rng,
+) -> bool {
+    level.wave_idx += 1;
 
-            LevelChamber {
-                y: min_y,
-                ..chamber
-            }
-        }
+    let is_valid = generate(level, rng, |level, rng| {
+        level.wave = Default::default();
+        generator.generator().progress(level, rng);
+    });
 
-        2 => {
-            max_x += chamber.x + chamber.r;
+    if !is_valid {
+        warn!(
+            "Couldn't generate a valid layout for wave {}, leaving the level \
+             as it is",
+            level.wave_idx
+        );
 
-            LevelChamber {
-                x: max_x,
-                ..chamber
-            }
-        }
-
-        _ => {
-            max_y += chamber.y + chamber.r;
-
-            LevelChamber {
-                y: max_y,
-                ..chamber
-            }
-        }
+        level.wave = Default::default();
     }
+
+    is_valid
 }
 
-fn spawn_chamber_lymph_node(
-    ops: &mut Vec<LevelWaveOp>,
-    c: &LevelChamber,
-    force: bool,
-) {
-    let mut rng = rand::thread_rng();
+/// Runs given generator until it yields a valid layout, returning whether it
+/// succeeded; when it doesn't, the level is left as it was before the call.
+///
+/// Walls built on top of lymph nodes get removed, while layouts with lymph
+/// nodes the player cannot get to are regenerated from scratch.
+fn generate(
+    level: &mut Level,
+    rng: &mut impl Rng,
+    generator: impl Fn(&mut Level, &mut dyn RngCore),
+) -> bool {
+    let original = level.clone();
 
-    while force {
-        let angle = rng.gen_range(0f32..=TAU);
-        let direction = rng.gen_range(0..(c.r - 1)) as f32;
+    for attempt in 1..=MAX_ATTEMPTS {
+        generator(level, rng);
 
-        let x = (angle.sin() * direction) as i32;
-        let y = (angle.cos() * direction) as i32;
+        let buried = level.validate().buried;
 
-        if c.contains(c.x + x, c.y + y) {
-            let collides = ops.iter().any(|op| {
-                if let LevelWaveOp::AddLymphNode { x: x2, y: y2, .. } = op {
-                    *x2 == x && *y2 == y
-                } else {
-                    false
-                }
-            });
+        remove_walls(&mut level.wave.ops, buried);
 
-            if !collides {
-                add_lymph_node(ops, c.x + x, c.y + y, Alignment::Enemy);
-                return;
-            }
+        let issues = level.validate();
+
+        if issues.is_empty() {
+            return true;
         }
+
+        warn!(
+            "Generated layout is invalid ({}), regenerating (attempt {}/{})",
+            issues, attempt, MAX_ATTEMPTS
+        );
+
+        *level = original.clone();
     }
+
+    false
 }
 
-fn add_circle_wall(ops: &mut Vec<LevelWaveOp>, x: i32, y: i32, r: i32) {
+pub(super) fn add_circle_wall(
+    ops: &mut Vec<LevelWaveOp>,
+    rng: &mut impl Rng,
+    x: i32,
+    y: i32,
+    r: i32,
+) {
     let mut coords = Vec::new();
     let mut remove_coords = Vec::new();
 
@@ -201,45 +255,36 @@ fn add_circle_wall(ops: &mut Vec<LevelWaveOp>, x: i32, y: i32, r: i32) {
         }
     }
 
-    add_walls(ops, coords);
+    add_walls(ops, rng, coords);
 
     for (x, y) in remove_coords {
         ops.push(LevelWaveOp::RemoveWall { x, y });
     }
 }
 
-fn spawn_corridor(level: &mut Level, c1: &LevelChamber, c2: &LevelChamber) {
-    let corridor = LevelCorridor {
-        x1: c1.x,
-        y1: c1.y,
-        x2: c2.x,
-        y2: c2.y,
-    };
-
-    let (add, remove) = corridor.walls(&level.chambers);
-
-    add_walls(&mut level.wave.ops, add);
-    remove_walls(&mut level.wave.ops, remove);
-
-    level.corridors.push(corridor);
-}
-
-fn add_lymph_node(
+pub(super) fn add_lymph_node(
     ops: &mut Vec<LevelWaveOp>,
     x: i32,
     y: i32,
     alignment: Alignment,
+    resource: Option<LymphNodeResource>,
 ) {
-    ops.push(LevelWaveOp::AddLymphNode { x, y, alignment });
+    ops.push(LevelWaveOp::AddLymphNode {
+        x,
+        y,
+        alignment,
+        resource,
+    });
 }
 
-fn add_walls(
+pub(super) fn add_walls(
     ops: &mut Vec<LevelWaveOp>,
+    rng: &mut impl Rng,
     walls: impl IntoIterator<Item = (i32, i32)>,
 ) {
     let mut walls = walls.into_iter().collect_vec();
 
-    walls.shuffle(&mut rand::thread_rng());
+    walls.shuffle(rng);
 
     ops.extend(
         walls
@@ -248,7 +293,7 @@ fn add_walls(
     );
 }
 
-fn remove_walls(
+pub(super) fn remove_walls(
     ops: &mut Vec<LevelWaveOp>,
     walls: impl IntoIterator<Item = (i32, i32)>,
 ) {
@@ -258,3 +303,52 @@ fn remove_walls(
             .map(|(x, y)| LevelWaveOp::RemoveWall { x, y }),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::LevelChamber;
+    use crate::rng::GameRng;
+
+    /// Generator that always walls off its enemy-owned lymph node
+    struct WalledOffGenerator;
+
+    impl LevelGenerator for WalledOffGenerator {
+        fn start(&self, level: &mut Level, _: &mut dyn RngCore) {
+            let ops = &mut level.wave.ops;
+
+            add_lymph_node(ops, 0, 0, Alignment::Player, None);
+            add_lymph_node(ops, 6, 0, Alignment::Enemy, None);
+
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if (dx, dy) != (0, 0) {
+                        ops.push(LevelWaveOp::AddWall { x: 6 + dx, y: dy });
+                    }
+                }
+            }
+
+            level.chambers.push(LevelChamber { x: 0, y: 0, r: 10 });
+        }
+
+        fn progress(&self, _: &mut Level, _: &mut dyn RngCore) {}
+    }
+
+    #[test]
+    fn invalid_starting_layout_falls_back_to_chambers() {
+        let mut rng = GameRng::new(0);
+        let mut level = Level::empty();
+
+        assert!(start_with(
+            &mut level,
+            &WalledOffGenerator,
+            "test",
+            &mut rng
+        ));
+        assert!(level.validate().is_empty());
+
+        // Nothing built by the invalid layout should remain
+        assert_eq!(1, level.chambers.len());
+        assert_eq!(15, level.chambers[0].r);
+    }
+}
@@ -0,0 +1,252 @@
+//! Building blocks shared by the generators that lay levels out of free-form
+//! areas (see `LevelArea`) instead of circular chambers.
+
+use itertools::Itertools;
+use rand::prelude::SliceRandom;
+use rand::Rng;
+
+use super::{add_lymph_node, add_walls, remove_walls};
+use crate::level::{Level, LevelArea, LevelCorridor};
+use crate::systems::units::Alignment;
+
+/// Space (in fields) left between the level and a newly generated group of
+/// areas
+const GAP: i32 = 6;
+
+/// Minimum distance (in fields) between two lymph nodes
+const LYMPH_NODE_SPACING: i32 = 3;
+
+const PLAYER_LYMPH_NODES: usize = 5;
+
+pub trait AreaGenerator {
+    /// Generates a group of areas, positioned anywhere - they get moved into
+    /// place afterwards; consecutive areas get connected with corridors.
+    fn generate(&self, rng: &mut impl Rng) -> Vec<LevelArea>;
+}
+
+pub fn start(
+    generator: &impl AreaGenerator,
+    level: &mut Level,
+    rng: &mut impl Rng,
+) {
+    let mut areas = generator.generate(rng);
+    let (min_x, min_y, max_x, max_y) = bounds(&areas);
+
+    translate(&mut areas, -(min_x + max_x) / 2, -(min_y + max_y) / 2);
+
+    // Player's lymph nodes should be close to each other, so instead of
+    // picking the fields randomly we go from the middle of the level
+    let fields = areas
+        .iter()
+        .flat_map(|area| area.interior())
+        .sorted_by_key(|(x, y)| x.pow(2) + y.pow(2))
+        .collect_vec();
+
+    add_areas(level, rng, &areas);
+    add_lymph_nodes(level, fields, PLAYER_LYMPH_NODES, Alignment::Player);
+}
+
+pub fn progress(
+    generator: &impl AreaGenerator,
+    level: &mut Level,
+    rng: &mut impl Rng,
+) {
+    let stage = level.stage().clone();
+
+    for _ in 0..stage.chambers(rng) {
+        let mut areas = generator.generate(rng);
+
+        if areas.is_empty() {
+            continue;
+        }
+
+        move_next_to_level(level, rng, &mut areas);
+
+        // Pick chambers we'll connect the new areas to before adding them, so
+        // that they don't get connected to themselves
+        let center = areas[0].center();
+
+        let linked = level
+            .chambers
+            .iter()
+            .map(|chamber| (chamber.x, chamber.y))
+            .chain(level.areas.iter().map(|area| area.center()))
+            .sorted_by_key(|&(x, y)| {
+                (x - center.0).pow(2) + (y - center.1).pow(2)
+            })
+            .take(stage.corridors(rng))
+            .collect_vec();
+
+        let mut fields =
+            areas.iter().flat_map(|area| area.interior()).collect_vec();
+
+        fields.shuffle(rng);
+
+        add_areas(level, rng, &areas);
+        let node_count = stage.enemy_nodes(rng);
+
+        add_lymph_nodes(level, fields, node_count, Alignment::Enemy);
+
+        for target in linked {
+            add_corridor(level, rng, center, target);
+        }
+    }
+
+    // New areas could've been built on top of the existing corridors, so
+    // let's make sure they remain passable
+    for corridor in &level.corridors {
+        let (_, remove) = corridor.walls(&level.chambers);
+        remove_walls(&mut level.wave.ops, remove);
+    }
+}
+
+/// Builds given areas, connecting the consecutive ones with corridors.
+fn add_areas(level: &mut Level, rng: &mut impl Rng, areas: &[LevelArea]) {
+    for (idx, area) in areas.iter().enumerate() {
+        let is_taken = |x, y| {
+            is_open(level, x, y)
+                || areas
+                    .iter()
+                    .enumerate()
+                    .any(|(idx2, area2)| idx2 != idx && area2.contains(x, y))
+        };
+
+        let walls = area
+            .fields
+            .iter()
+            .flat_map(|&(x, y)| {
+                (-1..=1).flat_map(move |dx| {
+                    (-1..=1).map(move |dy| (x + dx, y + dy))
+                })
+            })
+            .filter(|&(x, y)| !area.contains(x, y) && !is_taken(x, y))
+            .unique()
+            .collect_vec();
+
+        add_walls(&mut level.wave.ops, rng, walls);
+        remove_walls(&mut level.wave.ops, area.fields.iter().copied());
+    }
+
+    level.areas.extend(areas.iter().cloned());
+
+    for [a, b] in areas.array_windows() {
+        add_corridor(level, rng, a.center(), b.center());
+    }
+}
+
+fn add_corridor(
+    level: &mut Level,
+    rng: &mut impl Rng,
+    (x1, y1): (i32, i32),
+    (x2, y2): (i32, i32),
+) {
+    let corridor = LevelCorridor { x1, y1, x2, y2 };
+    let (add, remove) = corridor.walls(&level.chambers);
+
+    // `LevelCorridor::walls()` knows only about circular chambers, so we have
+    // to make sure the corridor doesn't wall off any of the areas by ourselves
+    let add = add
+        .into_iter()
+        .filter(|&(x, y)| !level.areas.iter().any(|area| area.contains(x, y)))
+        .collect_vec();
+
+    add_walls(&mut level.wave.ops, rng, add);
+    remove_walls(&mut level.wave.ops, remove);
+
+    level.corridors.push(corridor);
+}
+
+/// Places up to `count` lymph nodes on given fields, in order, skipping fields
+/// that are too close to the already placed lymph nodes.
+fn add_lymph_nodes(
+    level: &mut Level,
+    fields: Vec<(i32, i32)>,
+    count: usize,
+    alignment: Alignment,
+) {
+    let mut placed: Vec<(i32, i32)> = Vec::new();
+
+    for (x, y) in fields {
+        if placed.len() >= count {
+            break;
+        }
+
+        let is_too_close = placed.iter().any(|&(x2, y2)| {
+            (x - x2).abs() < LYMPH_NODE_SPACING
+                && (y - y2).abs() < LYMPH_NODE_SPACING
+        });
+
+        if !is_too_close {
+            add_lymph_node(&mut level.wave.ops, x, y, alignment, None);
+            placed.push((x, y));
+        }
+    }
+}
+
+/// Moves areas so that they lie next to (but not on) the existing level, on
+/// one of its randomly chosen sides.
+fn move_next_to_level(
+    level: &Level,
+    rng: &mut impl Rng,
+    areas: &mut [LevelArea],
+) {
+    let (min_x, min_y, max_x, max_y) = level.bounds();
+    let (a_min_x, a_min_y, a_max_x, a_max_y) = bounds(areas);
+
+    let mut between = |a: i32, b: i32| rng.gen_range(a.min(b)..=a.max(b));
+
+    let (dx, dy) = match between(0, 3) {
+        0 => (
+            min_x - GAP - a_max_x,
+            between(min_y - a_min_y, max_y - a_max_y),
+        ),
+
+        1 => (
+            max_x + GAP - a_min_x,
+            between(min_y - a_min_y, max_y - a_max_y),
+        ),
+
+        2 => (
+            between(min_x - a_min_x, max_x - a_max_x),
+            min_y - GAP - a_max_y,
+        ),
+
+        _ => (
+            between(min_x - a_min_x, max_x - a_max_x),
+            max_y + GAP - a_min_y,
+        ),
+    };
+
+    translate(areas, dx, dy);
+}
+
+fn translate(areas: &mut [LevelArea], dx: i32, dy: i32) {
+    for area in areas {
+        area.fields =
+            area.fields.iter().map(|&(x, y)| (x + dx, y + dy)).collect();
+    }
+}
+
+fn bounds(areas: &[LevelArea]) -> (i32, i32, i32, i32) {
+    let fields = areas.iter().flat_map(|area| area.fields.iter());
+
+    let (min_x, max_x) = fields
+        .clone()
+        .map(|(x, _)| *x)
+        .minmax()
+        .into_option()
+        .unwrap_or_default();
+
+    let (min_y, max_y) = fields
+        .map(|(_, y)| *y)
+        .minmax()
+        .into_option()
+        .unwrap_or_default();
+
+    (min_x, min_y, max_x, max_y)
+}
+
+fn is_open(level: &Level, x: i32, y: i32) -> bool {
+    level.chambers.iter().any(|chamber| chamber.contains(x, y))
+        || level.areas.iter().any(|area| area.contains(x, y))
+}
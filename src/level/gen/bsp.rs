@@ -0,0 +1,117 @@
+use rand::{Rng, RngCore};
+
+use super::areas::{self, AreaGenerator};
+use crate::level::{Level, LevelArea, LevelGenerator};
+
+/// Leaves smaller than this (in fields) don't get split any further
+const MIN_LEAF_SIZE: i32 = 12;
+
+/// Smallest room that fits into a leaf (in fields)
+const MIN_ROOM_SIZE: i32 = 6;
+
+/// Generates groups of rectangular rooms by recursively splitting a rectangle
+/// into smaller ones (binary space partitioning) and putting a room into each
+/// of them.
+pub struct BspGenerator;
+
+impl LevelGenerator for BspGenerator {
+    fn start(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::start(self, level, &mut rng);
+    }
+
+    fn progress(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::progress(self, level, &mut rng);
+    }
+}
+
+impl AreaGenerator for BspGenerator {
+    fn generate(&self, rng: &mut impl Rng) -> Vec<LevelArea> {
+        let w = rng.gen_range(32..=48);
+        let h = rng.gen_range(32..=48);
+
+        let mut rooms = Vec::new();
+
+        split(rng, Rect { x: 0, y: 0, w, h }, &mut rooms);
+
+        // Leaves are visited depth-first, so consecutive rooms are siblings
+        // (or close relatives) and connecting them in order yields corridors
+        // between neighbors
+        rooms
+            .into_iter()
+            .map(|room| LevelArea {
+                fields: (room.x..room.x + room.w)
+                    .flat_map(|x| {
+                        (room.y..room.y + room.h).map(move |y| (x, y))
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+fn split(rng: &mut impl Rng, leaf: Rect, rooms: &mut Vec<Rect>) {
+    let can_split_x = leaf.w >= 2 * MIN_LEAF_SIZE;
+    let can_split_y = leaf.h >= 2 * MIN_LEAF_SIZE;
+
+    let split_x = match (can_split_x, can_split_y) {
+        (true, true) => rng.gen_bool(0.5),
+        (true, false) => true,
+        (false, true) => false,
+
+        (false, false) => {
+            rooms.push(room(rng, leaf));
+            return;
+        }
+    };
+
+    if split_x {
+        let w = rng.gen_range(MIN_LEAF_SIZE..=(leaf.w - MIN_LEAF_SIZE));
+
+        split(rng, Rect { w, ..leaf }, rooms);
+
+        split(
+            rng,
+            Rect {
+                x: leaf.x + w,
+                w: leaf.w - w,
+                ..leaf
+            },
+            rooms,
+        );
+    } else {
+        let h = rng.gen_range(MIN_LEAF_SIZE..=(leaf.h - MIN_LEAF_SIZE));
+
+        split(rng, Rect { h, ..leaf }, rooms);
+
+        split(
+            rng,
+            Rect {
+                y: leaf.y + h,
+                h: leaf.h - h,
+                ..leaf
+            },
+            rooms,
+        );
+    }
+}
+
+/// Returns a room that fits into given leaf, leaving space for walls around it
+fn room(rng: &mut impl Rng, leaf: Rect) -> Rect {
+    let w = rng.gen_range(MIN_ROOM_SIZE..=(leaf.w - 2));
+    let h = rng.gen_range(MIN_ROOM_SIZE..=(leaf.h - 2));
+
+    Rect {
+        x: leaf.x + rng.gen_range(1..=(leaf.w - w - 1)),
+        y: leaf.y + rng.gen_range(1..=(leaf.h - h - 1)),
+        w,
+        h,
+    }
+}
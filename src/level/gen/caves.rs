@@ -0,0 +1,143 @@
+use std::collections::BTreeSet;
+
+use rand::{Rng, RngCore};
+
+use super::areas::{self, AreaGenerator};
+use crate::level::{Level, LevelArea, LevelGenerator};
+
+/// Number of smoothing passes of the automaton
+const ITERATIONS: usize = 5;
+
+/// Chance of a field starting as a wall
+const FILL_CHANCE: f64 = 0.45;
+
+/// Caves smaller than this (as a fraction of their bounding box) get
+/// regenerated
+const MIN_COVERAGE: f32 = 0.3;
+
+const MAX_ATTEMPTS: usize = 10;
+
+/// Generates caves using cellular automata: each cave starts as random noise
+/// which then gets smoothed out, after which its largest connected part is
+/// kept.
+pub struct CavesGenerator;
+
+impl LevelGenerator for CavesGenerator {
+    fn start(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::start(self, level, &mut rng);
+    }
+
+    fn progress(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::progress(self, level, &mut rng);
+    }
+}
+
+impl AreaGenerator for CavesGenerator {
+    fn generate(&self, rng: &mut impl Rng) -> Vec<LevelArea> {
+        let w = rng.gen_range(24..=36);
+        let h = rng.gen_range(24..=36);
+
+        let mut best = BTreeSet::new();
+
+        for _ in 0..MAX_ATTEMPTS {
+            let fields = largest_region(&automaton(rng, w, h), w, h);
+
+            if fields.len() > best.len() {
+                best = fields;
+            }
+
+            if best.len() as f32 >= MIN_COVERAGE * (w * h) as f32 {
+                break;
+            }
+        }
+
+        vec![LevelArea { fields: best }]
+    }
+}
+
+/// Returns a `w x h` grid, where `true` stands for a wall.
+fn automaton(rng: &mut impl Rng, w: i32, h: i32) -> Vec<Vec<bool>> {
+    let is_border = |x, y| x == 0 || y == 0 || x == w - 1 || y == h - 1;
+
+    let mut grid: Vec<Vec<bool>> = (0..w)
+        .map(|x| {
+            (0..h)
+                .map(|y| is_border(x, y) || rng.gen_bool(FILL_CHANCE))
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..ITERATIONS {
+        grid = (0..w)
+            .map(|x| {
+                (0..h)
+                    .map(|y| {
+                        if is_border(x, y) {
+                            return true;
+                        }
+
+                        let walls = (-1..=1)
+                            .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                            .filter(|&(dx, dy)| (dx, dy) != (0, 0))
+                            .filter(|&(dx, dy)| {
+                                grid[(x + dx) as usize][(y + dy) as usize]
+                            })
+                            .count();
+
+                        match walls {
+                            0..=3 => false,
+                            4 => grid[x as usize][y as usize],
+                            _ => true,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    grid
+}
+
+/// Returns fields of the largest region of (4-way) connected empty fields.
+fn largest_region(grid: &[Vec<bool>], w: i32, h: i32) -> BTreeSet<(i32, i32)> {
+    let mut visited = BTreeSet::new();
+    let mut best = BTreeSet::new();
+
+    for x in 0..w {
+        for y in 0..h {
+            if grid[x as usize][y as usize] || visited.contains(&(x, y)) {
+                continue;
+            }
+
+            let mut region = BTreeSet::new();
+            let mut pending = vec![(x, y)];
+
+            while let Some((x, y)) = pending.pop() {
+                if x < 0
+                    || y < 0
+                    || x >= w
+                    || y >= h
+                    || grid[x as usize][y as usize]
+                    || !visited.insert((x, y))
+                {
+                    continue;
+                }
+
+                region.insert((x, y));
+
+                pending.extend([
+                    (x - 1, y),
+                    (x + 1, y),
+                    (x, y - 1),
+                    (x, y + 1),
+                ]);
+            }
+
+            if region.len() > best.len() {
+                best = region;
+            }
+        }
+    }
+
+    best
+}
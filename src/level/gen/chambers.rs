@@ -0,0 +1,246 @@
+//! lord forgive me for i have sinned, but at some point i _will_ refactor it
+
+use std::f32::consts::TAU;
+
+use itertools::Itertools;
+use rand::{Rng, RngCore};
+
+use super::{add_circle_wall, add_lymph_node, add_walls, remove_walls};
+use crate::level::{
+    DifficultyStage, Level, LevelChamber, LevelCorridor, LevelGenerator,
+    LevelWaveOp,
+};
+use crate::systems::units::Alignment;
+
+/// Generates circular chambers connected with L-shaped corridors
+pub struct ChambersGenerator;
+
+impl LevelGenerator for ChambersGenerator {
+    fn start(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        start(level, &mut rng);
+    }
+
+    fn progress(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        progress(level, &mut rng);
+    }
+}
+
+fn start(level: &mut Level, rng: &mut impl Rng) {
+    let ops = &mut level.wave.ops;
+
+    add_circle_wall(ops, rng, 0, 0, 15);
+    add_lymph_node(ops, 0, 0, Alignment::Player, None);
+    add_lymph_node(ops, -4, -4, Alignment::Player, None);
+    add_lymph_node(ops, 4, -4, Alignment::Player, None);
+    add_lymph_node(ops, -4, 4, Alignment::Player, None);
+    add_lymph_node(ops, 4, 4, Alignment::Player, None);
+
+    level.chambers.push(LevelChamber { x: 0, y: 0, r: 15 });
+}
+
+fn progress(level: &mut Level, rng: &mut impl Rng) {
+    let stage = level.stage().clone();
+
+    for _ in 0..stage.chambers(rng) {
+        let chamber = spawn_chamber(level, rng, &stage);
+
+        add_circle_wall(
+            &mut level.wave.ops,
+            rng,
+            chamber.x,
+            chamber.y,
+            chamber.r,
+        );
+
+        for _ in 0..stage.enemy_nodes(rng) {
+            spawn_chamber_lymph_node(&mut level.wave.ops, rng, &chamber);
+        }
+
+        let chambers = level
+            .chambers
+            .iter()
+            .sorted_by(|a, b| {
+                let a = a.distance_to_squared(&chamber);
+                let b = b.distance_to_squared(&chamber);
+
+                a.cmp(&b)
+            })
+            .take(stage.corridors(rng))
+            .cloned()
+            .collect_vec();
+
+        level.chambers.push(chamber.clone());
+
+        for linked in chambers {
+            spawn_corridor(level, rng, &chamber, &linked);
+        }
+    }
+
+    for corridor in &level.corridors {
+        let (_, remove) = corridor.walls(&level.chambers);
+        remove_walls(&mut level.wave.ops, remove);
+    }
+}
+
+fn spawn_chamber(
+    level: &Level,
+    rng: &mut impl Rng,
+    stage: &DifficultyStage,
+) -> LevelChamber {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = level.bounds();
+
+    for _ in 0..100 {
+        let r = stage.radius(rng);
+        let xs = (min_x + r)..(max_x - r);
+        let ys = (min_y + r)..(max_y - r);
+
+        if xs.is_empty() || ys.is_empty() {
+            continue;
+        }
+
+        let chamber = LevelChamber {
+            x: rng.gen_range(xs),
+            y: rng.gen_range(ys),
+            r,
+        };
+
+        let collides = level
+            .chambers
+            .iter()
+            .any(|chamber2| chamber2.collides_with(&chamber));
+
+        if !collides {
+            return chamber;
+        }
+    }
+
+    let chamber = LevelChamber {
+        x: rng.gen_range(8..20),
+        y: rng.gen_range(10..30),
+        r: rng.gen_range(10..30),
+    };
+
+    match rng.gen_range(0..4) {
+        0 => {
+            min_x -= chamber.x + chamber.r;
+
+            LevelChamber {
+                x: min_x,
+                ..chamber
+            }
+        }
+
+        1 => {
+            min_y -= chamber.y + chamber.r;
+
+            LevelChamber {
+                y: min_y,
+                ..chamber
+            }
+        }
+
+        2 => {
+            max_x += chamber.x + chamber.r;
+
+            LevelChamber {
+                x: max_x,
+                ..chamber
+            }
+        }
+
+        _ => {
+            max_y += chamber.y + chamber.r;
+
+            LevelChamber {
+                y: max_y,
+                ..chamber
+            }
+        }
+    }
+}
+
+fn spawn_chamber_lymph_node(
+    ops: &mut Vec<LevelWaveOp>,
+    rng: &mut impl Rng,
+    c: &LevelChamber,
+) {
+    loop {
+        let angle = rng.gen_range(0f32..=TAU);
+        let direction = rng.gen_range(0..(c.r - 1)) as f32;
+
+        let x = (angle.sin() * direction) as i32;
+        let y = (angle.cos() * direction) as i32;
+
+        if c.contains(c.x + x, c.y + y) {
+            let collides = ops.iter().any(|op| {
+                if let LevelWaveOp::AddLymphNode { x: x2, y: y2, .. } = op {
+                    *x2 == c.x + x && *y2 == c.y + y
+                } else {
+                    false
+                }
+            });
+
+            if !collides {
+                add_lymph_node(ops, c.x + x, c.y + y, Alignment::Enemy, None);
+                return;
+            }
+        }
+    }
+}
+
+fn spawn_corridor(
+    level: &mut Level,
+    rng: &mut impl Rng,
+    c1: &LevelChamber,
+    c2: &LevelChamber,
+) {
+    let corridor = LevelCorridor {
+        x1: c1.x,
+        y1: c1.y,
+        x2: c2.x,
+        y2: c2.y,
+    };
+
+    let (add, remove) = corridor.walls(&level.chambers);
+
+    add_walls(&mut level.wave.ops, rng, add);
+    remove_walls(&mut level.wave.ops, remove);
+
+    level.corridors.push(corridor);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::rng::GameRng;
+
+    #[test]
+    fn chamber_lymph_nodes_do_not_overlap() {
+        let chamber = LevelChamber {
+            x: 40,
+            y: -25,
+            r: 8,
+        };
+
+        for seed in 0..20 {
+            let mut rng = GameRng::new(seed);
+            let mut ops = Vec::new();
+
+            for _ in 0..8 {
+                spawn_chamber_lymph_node(&mut ops, &mut rng, &chamber);
+            }
+
+            let nodes: BTreeSet<_> = ops
+                .iter()
+                .filter_map(|op| match *op {
+                    LevelWaveOp::AddLymphNode { x, y, .. } => Some((x, y)),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(8, nodes.len(), "seed={}", seed);
+        }
+    }
+}
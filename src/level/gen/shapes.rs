@@ -0,0 +1,40 @@
+use rand::{Rng, RngCore};
+
+use super::areas::{self, AreaGenerator};
+use crate::level::{Level, LevelArea, LevelGenerator};
+
+/// Generates rectangular and elliptical chambers.
+pub struct ShapesGenerator;
+
+impl LevelGenerator for ShapesGenerator {
+    fn start(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::start(self, level, &mut rng);
+    }
+
+    fn progress(&self, level: &mut Level, mut rng: &mut dyn RngCore) {
+        areas::progress(self, level, &mut rng);
+    }
+}
+
+impl AreaGenerator for ShapesGenerator {
+    fn generate(&self, rng: &mut impl Rng) -> Vec<LevelArea> {
+        let rx: i32 = rng.gen_range(6..=16);
+        let ry: i32 = rng.gen_range(6..=16);
+
+        let fields = (-rx..=rx).flat_map(|x| (-ry..=ry).map(move |y| (x, y)));
+
+        let fields = if rng.gen_bool(0.5) {
+            fields.collect()
+        } else {
+            let (rx2, ry2) = (rx.pow(2) as f32, ry.pow(2) as f32);
+
+            fields
+                .filter(|&(x, y)| {
+                    (x.pow(2) as f32) / rx2 + (y.pow(2) as f32) / ry2 <= 1.0
+                })
+                .collect()
+        };
+
+        vec![LevelArea { fields }]
+    }
+}
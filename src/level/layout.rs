@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::Result;
+use rand::Rng;
+
+use super::gen::{add_circle_wall, add_lymph_node, add_walls, remove_walls};
+use super::{
+    load, LevelChamber, LevelCorridor, LevelMap, LevelPoint, LevelWaveOp,
+};
+use crate::systems::bio::LymphNodeResource;
+use crate::systems::units::Alignment;
+
+/// Static part of an authored level - its chambers, corridors, walls and lymph
+/// nodes - i.e. everything that's spawned before the first wave.
+///
+/// It's what `levels/*.toml` specify in their `Setup` section and what the
+/// level editor works with.
+#[derive(Clone, Debug, Default)]
+pub struct LevelLayout {
+    pub chambers: Vec<LevelChamber>,
+    pub corridors: Vec<LevelCorridor>,
+
+    /// Walls built on top of the chambers and corridors
+    pub walls: Vec<LevelPoint>,
+
+    /// Walls removed from the chambers and corridors
+    pub holes: Vec<LevelPoint>,
+
+    pub lymph_nodes: Vec<LevelLayoutNode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LevelLayoutNode {
+    pub pos: LevelPoint,
+    pub alignment: Alignment,
+    pub resource: Option<LymphNodeResource>,
+}
+
+impl LevelLayout {
+    /// Loads layout of given level, ignoring its waves.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        load::load_layout(path.as_ref())
+    }
+
+    /// Saves layout into given level; if the file already exists, its waves
+    /// and victory condition are preserved.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        load::save_layout(path.as_ref(), self)
+    }
+
+    /// Returns ops that build this layout.
+    pub fn ops(&self, rng: &mut impl Rng) -> Vec<LevelWaveOp> {
+        let mut ops = Vec::new();
+
+        for chamber in &self.chambers {
+            add_circle_wall(&mut ops, rng, chamber.x, chamber.y, chamber.r);
+        }
+
+        // Corridors get carved only after all the chambers have been built,
+        // since otherwise a chamber could wall off a corridor leading to it
+        let corridors: Vec<_> = self
+            .corridors
+            .iter()
+            .map(|corridor| corridor.walls(&self.chambers))
+            .collect();
+
+        for (add, _) in &corridors {
+            add_walls(&mut ops, rng, add.iter().copied());
+        }
+
+        for (_, remove) in corridors {
+            remove_walls(&mut ops, remove);
+        }
+
+        add_walls(&mut ops, rng, self.walls.iter().map(|pos| (pos.x, pos.y)));
+        remove_walls(&mut ops, self.holes.iter().map(|pos| (pos.x, pos.y)));
+
+        for node in &self.lymph_nodes {
+            add_lymph_node(
+                &mut ops,
+                node.pos.x,
+                node.pos.y,
+                node.alignment,
+                node.resource,
+            );
+        }
+
+        ops
+    }
+
+    /// Returns positions of all the walls this layout ends up with.
+    pub fn wall_cells(&self) -> BTreeSet<(i32, i32)> {
+        let mut map = LevelMap::default();
+
+        // Ops are shuffled only within groups of walls that are added at once,
+        // so the outcome doesn't depend on the generator
+        map.apply(&self.ops(&mut rand::thread_rng()));
+        map.walls
+    }
+
+    pub fn lymph_node_at(&self, pos: LevelPoint) -> Option<usize> {
+        self.lymph_nodes.iter().position(|node| node.pos == pos)
+    }
+}
@@ -0,0 +1,948 @@
+//! Loads hand-authored levels (see `levels/*.toml`).
+//!
+//! All positions and velocities in those files are expressed in physics units
+//! (i.e. the same units `Cell::spawn()` works with), which get snapped to the
+//! level's grid where necessary.
+//!
+//! The level's `Setup` describes its layout (see `LevelLayout`) - chambers,
+//! corridors between them, additional walls (`Wall`), removed walls (`Hole`)
+//! and lymph nodes, optionally with a `Resource` they start with.
+//!
+//! Each wave first spawns its `Wave.Virus` groups (optionally with a
+//! `Pathogen` kind other than `Virus`, e.g. `Pathogen = "Parasite"`) and then
+//! executes its `Wave.Op`s, in order - e.g.:
+//!
+//! ```toml
+//! [[Wave.Op]]
+//! Kind = "ShowMessage"
+//! Text = "Here they come!"
+//!
+//! [[Wave.Op]]
+//! Kind = "WaitUntil"
+//! Condition = { PathogensRemaining = 5 }
+//! ```
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bevy::math::{vec2, Vec2};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Level, LevelChamber, LevelCorridor, LevelKind, LevelLayout,
+    LevelLayoutNode, LevelPoint, LevelVictory, LevelWave, LevelWaveCondition,
+    LevelWaveOp,
+};
+use crate::systems::bio::{Antigen, Body, LymphNodeResource, PathogenKind};
+use crate::systems::physics::PHYSICS_SCALE;
+use crate::systems::units::Alignment;
+
+const MIN_CHAMBER_RADIUS: i32 = 8;
+const CHAMBER_MARGIN: i32 = 4;
+const DEFAULT_MESSAGE_SECS: f32 = 5.0;
+
+pub fn load(path: &Path, rng: &mut impl Rng) -> Result<Level> {
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+    parse(&src, rng)
+        .with_context(|| format!("Couldn't load `{}`", path.display()))
+}
+
+pub fn parse(src: &str, rng: &mut impl Rng) -> Result<Level> {
+    let def: LevelDef = toml::from_str(src)?;
+
+    def.into_level(rng)
+}
+
+pub fn load_layout(path: &Path) -> Result<LevelLayout> {
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+    let result: Result<_> = try {
+        let def: LevelDef = toml::from_str(&src)?;
+
+        def.setup.into_layout()?
+    };
+
+    result.with_context(|| format!("Couldn't load `{}`", path.display()))
+}
+
+/// Writes given layout as the level's `Setup`, leaving the rest of the level
+/// (e.g. its waves) as-is.
+pub fn save_layout(path: &Path, layout: &LevelLayout) -> Result<()> {
+    let mut level = if path.exists() {
+        let src = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read `{}`", path.display()))?;
+
+        toml::from_str(&src)
+            .with_context(|| format!("Couldn't load `{}`", path.display()))?
+    } else {
+        toml::value::Table::new()
+    };
+
+    let setup = toml::Value::try_from(SetupDef::from_layout(layout))
+        .context("Couldn't serialize level's layout")?;
+
+    level.insert("Setup".into(), setup);
+
+    let level = toml::to_string(&level).context("Couldn't serialize level")?;
+
+    fs::write(path, level)
+        .with_context(|| format!("Couldn't write `{}`", path.display()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct LevelDef {
+    victory: Option<VictoryDef>,
+
+    #[serde(default)]
+    setup: SetupDef,
+
+    #[serde(default)]
+    wave: Vec<WaveDef>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct SetupDef {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chamber: Vec<ChamberDef>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    corridor: Vec<CorridorDef>,
+
+    /// Walls built on top of the chambers and corridors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    wall: Vec<WallDef>,
+
+    /// Walls removed from the chambers and corridors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hole: Vec<WallDef>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    lymph_node: Vec<LymphNodeDef>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct ChamberDef {
+    pos: [f32; 2],
+    radius: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct CorridorDef {
+    from: [f32; 2],
+    to: [f32; 2],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct WallDef {
+    pos: [f32; 2],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct LymphNodeDef {
+    pos: [f32; 2],
+
+    #[serde(default = "default_alignment")]
+    alignment: Alignment,
+
+    /// Resource the lymph node starts with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resource: Option<LymphNodeResource>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct WaveDef {
+    starts_at: Option<f32>,
+
+    #[serde(default)]
+    virus: Vec<VirusDef>,
+
+    #[serde(default)]
+    op: Vec<OpDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct VirusDef {
+    pos: [f32; 2],
+
+    #[serde(default)]
+    vel: [f32; 2],
+
+    body: Body,
+    antigen: Antigen,
+
+    /// Named differently than the other fields, since `Kind` is already
+    /// taken by ops (see `OpDef`)
+    #[serde(default)]
+    pathogen: PathogenKind,
+
+    count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum VictoryDef {
+    SurviveWaves(usize),
+    CaptureAllEnemyNodes,
+
+    #[serde(rename_all = "PascalCase")]
+    KeepNodeAlive {
+        pos: [f32; 2],
+        secs: f32,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "Kind", deny_unknown_fields)]
+enum OpDef {
+    SpawnVirus(VirusDef),
+
+    #[serde(rename_all = "PascalCase")]
+    Sleep {
+        secs: f32,
+    },
+
+    #[serde(rename_all = "PascalCase")]
+    ShowMessage {
+        text: String,
+
+        #[serde(default = "default_message_secs")]
+        secs: f32,
+    },
+
+    #[serde(rename_all = "PascalCase")]
+    WaitUntil {
+        condition: LevelWaveCondition,
+    },
+
+    #[serde(rename_all = "PascalCase")]
+    SetEnemyTarget {
+        #[serde(default)]
+        pos: Option<[f32; 2]>,
+    },
+}
+
+fn default_alignment() -> Alignment {
+    Alignment::Player
+}
+
+fn default_message_secs() -> f32 {
+    DEFAULT_MESSAGE_SECS
+}
+
+impl LevelDef {
+    fn into_level(self, rng: &mut impl Rng) -> Result<Level> {
+        let mut layout = self.setup.into_layout()?;
+
+        if !layout
+            .lymph_nodes
+            .iter()
+            .any(|node| node.alignment.is_player())
+        {
+            bail!("Level must contain at least one player-owned lymph node");
+        }
+
+        let mut waves = VecDeque::new();
+        let mut prev_starts_at: Option<f32> = None;
+
+        for (wave_idx, wave) in self.wave.into_iter().enumerate() {
+            let wave = wave.into_wave().with_context(|| {
+                format!("Wave #{} is invalid", wave_idx + 1)
+            })?;
+
+            if let Some(starts_at) = wave.starts_at {
+                if prev_starts_at.map_or(false, |prev| starts_at < prev) {
+                    bail!(
+                        "Wave #{} starts before the wave preceding it",
+                        wave_idx + 1
+                    );
+                }
+
+                prev_starts_at = Some(starts_at);
+            }
+
+            waves.push_back(wave);
+        }
+
+        let victory = self
+            .victory
+            .map(|victory| victory.to_victory(&layout.lymph_nodes, waves.len()))
+            .transpose()
+            .context("`Victory` is invalid")?;
+
+        if layout.chambers.is_empty() {
+            layout
+                .chambers
+                .push(enclosing_chamber(&layout.lymph_nodes, &waves));
+        }
+
+        Ok(Level {
+            chambers: layout.chambers.clone(),
+            corridors: layout.corridors.clone(),
+            areas: vec![],
+            wave: LevelWave {
+                ops: layout.ops(rng),
+                starts_at: None,
+            },
+            wave_idx: 0,
+            upcoming_waves: waves,
+            kind: LevelKind::Authored,
+            difficulty: Default::default(),
+            map: Default::default(),
+            victory,
+        })
+    }
+}
+
+impl SetupDef {
+    fn into_layout(self) -> Result<LevelLayout> {
+        let lymph_nodes = self
+            .lymph_node
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| {
+                let pos = to_vec2(node.pos).with_context(|| {
+                    format!("Lymph node #{} has invalid `Pos`", idx + 1)
+                })?;
+
+                Ok(LevelLayoutNode {
+                    pos: to_local(pos),
+                    alignment: node.alignment,
+                    resource: node.resource,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (idx, node) in lymph_nodes.iter().enumerate() {
+            let other = lymph_nodes[..idx]
+                .iter()
+                .position(|other| other.pos == node.pos);
+
+            if let Some(other) = other {
+                bail!(
+                    "Lymph node #{} overlaps lymph node #{} (both are at {}x{})",
+                    idx + 1,
+                    other + 1,
+                    node.pos.x,
+                    node.pos.y,
+                );
+            }
+        }
+
+        let chambers = self
+            .chamber
+            .iter()
+            .enumerate()
+            .map(|(idx, chamber)| {
+                chamber
+                    .to_chamber()
+                    .with_context(|| format!("Chamber #{} is invalid", idx + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let corridors = self
+            .corridor
+            .iter()
+            .enumerate()
+            .map(|(idx, corridor)| {
+                corridor.to_corridor().with_context(|| {
+                    format!("Corridor #{} is invalid", idx + 1)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let walls = to_points(&self.wall).context("Wall is invalid")?;
+        let holes = to_points(&self.hole).context("Hole is invalid")?;
+
+        let layout = LevelLayout {
+            chambers,
+            corridors,
+            walls,
+            holes,
+            lymph_nodes,
+        };
+
+        if !layout.chambers.is_empty() {
+            let walls = layout.wall_cells();
+
+            for (idx, node) in layout.lymph_nodes.iter().enumerate() {
+                let pos = node.pos;
+
+                let is_inside = layout
+                    .chambers
+                    .iter()
+                    .any(|c| c.contains(pos.x, pos.y))
+                    || layout.corridors.iter().any(|c| {
+                        c.walls(&layout.chambers).1.contains(&(pos.x, pos.y))
+                    });
+
+                if !is_inside {
+                    bail!(
+                        "Lymph node #{} lies outside of all the chambers and \
+                         corridors",
+                        idx + 1
+                    );
+                }
+
+                if walls.contains(&(pos.x, pos.y)) {
+                    bail!("Lymph node #{} lies inside a wall", idx + 1);
+                }
+            }
+        }
+
+        Ok(layout)
+    }
+
+    fn from_layout(layout: &LevelLayout) -> Self {
+        let chamber = layout
+            .chambers
+            .iter()
+            .map(|chamber| ChamberDef {
+                pos: from_local(LevelPoint::new(chamber.x, chamber.y)),
+                radius: from_cells(chamber.r),
+            })
+            .collect();
+
+        let corridor = layout
+            .corridors
+            .iter()
+            .map(|corridor| CorridorDef {
+                from: from_local(LevelPoint::new(corridor.x1, corridor.y1)),
+                to: from_local(LevelPoint::new(corridor.x2, corridor.y2)),
+            })
+            .collect();
+
+        let to_walls = |points: &[LevelPoint]| {
+            points
+                .iter()
+                .map(|&pos| WallDef {
+                    pos: from_local(pos),
+                })
+                .collect()
+        };
+
+        let lymph_node = layout
+            .lymph_nodes
+            .iter()
+            .map(|node| LymphNodeDef {
+                pos: from_local(node.pos),
+                alignment: node.alignment,
+                resource: node.resource,
+            })
+            .collect();
+
+        Self {
+            chamber,
+            corridor,
+            wall: to_walls(&layout.walls),
+            hole: to_walls(&layout.holes),
+            lymph_node,
+        }
+    }
+}
+
+impl WaveDef {
+    fn into_wave(self) -> Result<LevelWave> {
+        if let Some(starts_at) = self.starts_at {
+            if !starts_at.is_finite() || starts_at < 0.0 {
+                bail!("`StartsAt` must be a non-negative number of seconds");
+            }
+        }
+
+        let viruses = self.virus.iter().enumerate().map(|(idx, virus)| {
+            virus
+                .to_op()
+                .with_context(|| format!("Virus #{} is invalid", idx + 1))
+        });
+
+        let ops = self.op.iter().enumerate().map(|(idx, op)| {
+            op.to_op()
+                .with_context(|| format!("Op #{} is invalid", idx + 1))
+        });
+
+        let ops = viruses.chain(ops).collect::<Result<_>>()?;
+
+        Ok(LevelWave {
+            ops,
+            starts_at: self.starts_at,
+        })
+    }
+}
+
+impl ChamberDef {
+    fn to_chamber(&self) -> Result<LevelChamber> {
+        let pos = to_local(to_vec2(self.pos)?);
+        let r = to_cells(self.radius)?;
+
+        if r < 1 {
+            bail!("`Radius` must be positive");
+        }
+
+        Ok(LevelChamber {
+            x: pos.x,
+            y: pos.y,
+            r,
+        })
+    }
+}
+
+impl CorridorDef {
+    fn to_corridor(&self) -> Result<LevelCorridor> {
+        let from = to_local(to_vec2(self.from)?);
+        let to = to_local(to_vec2(self.to)?);
+
+        Ok(LevelCorridor {
+            x1: from.x,
+            y1: from.y,
+            x2: to.x,
+            y2: to.y,
+        })
+    }
+}
+
+impl VirusDef {
+    fn to_op(&self) -> Result<LevelWaveOp> {
+        Ok(LevelWaveOp::SpawnPathogens {
+            pos: to_vec2(self.pos)?,
+            vel: to_vec2(self.vel)?,
+            body: self.body,
+            antigen: self.antigen,
+            kind: self.pathogen,
+            count: self.count,
+        })
+    }
+}
+
+impl VictoryDef {
+    fn to_victory(
+        &self,
+        nodes: &[LevelLayoutNode],
+        wave_count: usize,
+    ) -> Result<LevelVictory> {
+        Ok(match self {
+            Self::SurviveWaves(waves) => {
+                if *waves == 0 || *waves > wave_count {
+                    bail!(
+                        "`SurviveWaves` must be between 1 and the number of \
+                         waves ({})",
+                        wave_count
+                    );
+                }
+
+                LevelVictory::SurviveWaves(*waves)
+            }
+
+            Self::CaptureAllEnemyNodes => {
+                if !nodes.iter().any(|node| node.alignment.is_enemy()) {
+                    bail!("Level doesn't contain any enemy-owned lymph nodes");
+                }
+
+                LevelVictory::CaptureAllEnemyNodes
+            }
+
+            Self::KeepNodeAlive { pos, secs } => {
+                let pos = to_local(to_vec2(*pos)?);
+
+                let has_node = nodes
+                    .iter()
+                    .any(|node| node.pos == pos && node.alignment.is_player());
+
+                if !has_node {
+                    bail!("There's no player-owned lymph node at `Pos`");
+                }
+
+                LevelVictory::KeepNodeAlive {
+                    x: pos.x,
+                    y: pos.y,
+                    secs: to_secs(*secs)?,
+                }
+            }
+        })
+    }
+}
+
+impl OpDef {
+    fn to_op(&self) -> Result<LevelWaveOp> {
+        Ok(match self {
+            Self::SpawnVirus(virus) => virus.to_op()?,
+
+            Self::Sleep { secs } => LevelWaveOp::Sleep {
+                secs: to_secs(*secs)?,
+            },
+
+            Self::ShowMessage { text, secs } => LevelWaveOp::ShowMessage {
+                text: text.to_owned(),
+                secs: to_secs(*secs)?,
+            },
+
+            Self::WaitUntil { condition } => LevelWaveOp::WaitUntil {
+                condition: *condition,
+            },
+
+            Self::SetEnemyTarget { pos } => LevelWaveOp::SetEnemyTarget {
+                pos: pos.map(to_vec2).transpose()?,
+            },
+        })
+    }
+}
+
+/// Returns a chamber large enough to fit everything the level spawns, so that
+/// levels which don't care about their layout don't have to specify one.
+fn enclosing_chamber(
+    nodes: &[LevelLayoutNode],
+    waves: &VecDeque<LevelWave>,
+) -> LevelChamber {
+    let viruses = waves.iter().flat_map(|wave| &wave.ops).filter_map(|op| {
+        if let LevelWaveOp::SpawnPathogens { pos, .. } = op {
+            Some(to_local(*pos))
+        } else {
+            None
+        }
+    });
+
+    let r = nodes
+        .iter()
+        .map(|node| node.pos)
+        .chain(viruses)
+        .map(|pos| ((pos.x.pow(2) + pos.y.pow(2)) as f32).sqrt().ceil() as i32)
+        .max()
+        .unwrap_or_default();
+
+    LevelChamber {
+        x: 0,
+        y: 0,
+        r: (r + CHAMBER_MARGIN).max(MIN_CHAMBER_RADIUS),
+    }
+}
+
+fn to_vec2([x, y]: [f32; 2]) -> Result<Vec2> {
+    if x.is_finite() && y.is_finite() {
+        Ok(vec2(x, y))
+    } else {
+        Err(anyhow!("Coordinates must be finite numbers"))
+    }
+}
+
+fn to_secs(secs: f32) -> Result<f32> {
+    if secs.is_finite() && secs >= 0.0 {
+        Ok(secs)
+    } else {
+        Err(anyhow!("`Secs` must be a non-negative number of seconds"))
+    }
+}
+
+fn to_cells(len: f32) -> Result<i32> {
+    if len.is_finite() {
+        Ok((len * PHYSICS_SCALE / Level::FIELD_SIZE).round() as i32)
+    } else {
+        Err(anyhow!("Lengths must be finite numbers"))
+    }
+}
+
+fn to_local(pos: Vec2) -> LevelPoint {
+    Level::world_to_local(pos * PHYSICS_SCALE)
+}
+
+fn to_points(walls: &[WallDef]) -> Result<Vec<LevelPoint>> {
+    walls
+        .iter()
+        .map(|wall| Ok(to_local(to_vec2(wall.pos)?)))
+        .collect()
+}
+
+/// Inverse of `to_local()` - returns the middle of given field, so that the
+/// position survives being truncated back by `Level::world_to_local()`.
+fn from_local(pos: LevelPoint) -> [f32; 2] {
+    let pos = Level::local_to_world(pos);
+    let pos = (pos + pos.signum() * Level::FIELD_SIZE / 2.0) / PHYSICS_SCALE;
+
+    [round(pos.x), round(pos.y)]
+}
+
+/// Inverse of `to_cells()`
+fn from_cells(len: i32) -> f32 {
+    round(len as f32 * Level::FIELD_SIZE / PHYSICS_SCALE)
+}
+
+/// Rounds given length to a few decimal places, so that the exported levels
+/// remain readable
+fn round(len: f32) -> f32 {
+    (len * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::rng::GameRng;
+
+    const LEVEL: &str = r#"
+        [[Setup.Chamber]]
+        Pos = [0.0, 0.0]
+        Radius = 4.0
+
+        [[Setup.LymphNode]]
+        Pos = [0.0, 0.0]
+
+        [[Setup.LymphNode]]
+        Pos = [2.0, 0.0]
+        Alignment = "Enemy"
+
+        [[Wave]]
+        StartsAt = 10.0
+
+        [[Wave.Virus]]
+        Pos = [-2.0, 0.0]
+        Body = "Circle"
+        Antigen = "Triangle"
+        Count = 5
+
+        [[Wave]]
+        StartsAt = 20.0
+    "#;
+
+    fn parse_err(src: &str) -> String {
+        format!("{:?}", parse(src, &mut GameRng::new(0)).unwrap_err())
+    }
+
+    #[test]
+    fn shipped_levels() {
+        let levels = [
+            include_str!("../../levels/1.toml"),
+            include_str!("../../levels/2.toml"),
+            include_str!("../../levels/3.toml"),
+        ];
+
+        for src in levels {
+            let level = parse(src, &mut GameRng::new(0)).unwrap();
+
+            assert_eq!(LevelKind::Authored, level.kind);
+            assert!(!level.upcoming_waves.is_empty());
+            assert!(level.victory.is_some());
+        }
+    }
+
+    #[test]
+    fn parse_level() {
+        let level = parse(LEVEL, &mut GameRng::new(0)).unwrap();
+
+        assert_eq!(1, level.chambers.len());
+        assert_eq!(2, level.upcoming_waves.len());
+        assert_eq!(Some(10.0), level.upcoming_waves[0].starts_at);
+        assert_eq!(1, level.upcoming_waves[0].ops.len());
+
+        let nodes: Vec<_> = level
+            .wave
+            .ops
+            .iter()
+            .filter_map(|op| match *op {
+                LevelWaveOp::AddLymphNode { alignment, .. } => Some(alignment),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(vec![Alignment::Player, Alignment::Enemy], nodes);
+    }
+
+    #[test]
+    fn level_without_chambers_gets_an_enclosing_one() {
+        let src = LEVEL.replace("[[Setup.Chamber]]", "").replacen(
+            "Pos = [0.0, 0.0]\n        Radius = 4.0",
+            "",
+            1,
+        );
+
+        let level = parse(&src, &mut GameRng::new(0)).unwrap();
+
+        assert_eq!(1, level.chambers.len());
+        assert!(level.chambers[0].r >= MIN_CHAMBER_RADIUS);
+    }
+
+    #[test]
+    fn level_without_player_lymph_nodes() {
+        let src = LEVEL.replace(
+            "Pos = [0.0, 0.0]\n\n",
+            "Pos = [0.0, 0.0]\n        Alignment = \"Enemy\"\n\n",
+        );
+
+        assert!(parse_err(&src).contains(
+            "Level must contain at least one player-owned lymph node"
+        ));
+    }
+
+    #[test]
+    fn overlapping_lymph_nodes() {
+        let src = LEVEL.replace("Pos = [2.0, 0.0]", "Pos = [0.1, 0.0]");
+
+        assert!(parse_err(&src).contains(
+            "Lymph node #2 overlaps lymph node #1 (both are at 0x0)"
+        ));
+    }
+
+    #[test]
+    fn lymph_node_outside_chambers() {
+        let src = LEVEL.replace("Pos = [2.0, 0.0]", "Pos = [8.0, 0.0]");
+
+        assert!(parse_err(&src)
+            .contains("Lymph node #2 lies outside of all the chambers"));
+    }
+
+    #[test]
+    fn non_finite_coordinates() {
+        let src = LEVEL.replace("Pos = [2.0, 0.0]", "Pos = [nan, 0.0]");
+        let err = parse_err(&src);
+
+        assert!(err.contains("Lymph node #2 has invalid `Pos`"), "{}", err);
+        assert!(
+            err.contains("Coordinates must be finite numbers"),
+            "{}",
+            err
+        );
+
+        let src = LEVEL.replace("Radius = 4.0", "Radius = inf");
+        let err = parse_err(&src);
+
+        assert!(err.contains("Chamber #1 is invalid"), "{}", err);
+        assert!(err.contains("Lengths must be finite numbers"), "{}", err);
+    }
+
+    #[test]
+    fn unordered_waves() {
+        let src = LEVEL.replace("StartsAt = 20.0", "StartsAt = 5.0");
+
+        assert!(parse_err(&src)
+            .contains("Wave #2 starts before the wave preceding it"));
+    }
+
+    #[test]
+    fn negative_wave_start() {
+        let src = LEVEL.replace("StartsAt = 10.0", "StartsAt = -10.0");
+        let err = parse_err(&src);
+
+        assert!(err.contains("Wave #1 is invalid"), "{}", err);
+        assert!(
+            err.contains("`StartsAt` must be a non-negative number of seconds"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn victory() {
+        let src = format!("Victory = {{ SurviveWaves = 2 }}\n{}", LEVEL);
+        let level = parse(&src, &mut GameRng::new(0)).unwrap();
+
+        assert_eq!(Some(LevelVictory::SurviveWaves(2)), level.victory);
+
+        let src = format!(
+            "Victory = {{ KeepNodeAlive = {{ Pos = [0.0, 0.0], Secs = 60.0 }} \
+             }}\n{}",
+            LEVEL
+        );
+        let level = parse(&src, &mut GameRng::new(0)).unwrap();
+
+        assert_eq!(
+            Some(LevelVictory::KeepNodeAlive {
+                x: 0,
+                y: 0,
+                secs: 60.0
+            }),
+            level.victory
+        );
+    }
+
+    #[test]
+    fn survive_waves_out_of_range() {
+        for waves in [0, 3] {
+            let src =
+                format!("Victory = {{ SurviveWaves = {} }}\n{}", waves, LEVEL);
+            let err = parse_err(&src);
+
+            assert!(err.contains("`Victory` is invalid"), "{}", err);
+            assert!(
+                err.contains(
+                    "`SurviveWaves` must be between 1 and the number of waves \
+                     (2)"
+                ),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn keep_node_alive_without_player_node() {
+        let src = format!(
+            "Victory = {{ KeepNodeAlive = {{ Pos = [2.0, 0.0], Secs = 60.0 }} \
+             }}\n{}",
+            LEVEL
+        );
+
+        assert!(parse_err(&src)
+            .contains("There's no player-owned lymph node at `Pos`"));
+    }
+
+    #[test]
+    fn lymph_node_inside_wall() {
+        let src = LEVEL.replacen(
+            "[[Setup.LymphNode]]",
+            "[[Setup.Wall]]\nPos = [2.0, 0.0]\n[[Setup.LymphNode]]",
+            1,
+        );
+
+        assert!(parse_err(&src).contains("Lymph node #2 lies inside a wall"));
+    }
+
+    #[test]
+    fn layout_round_trip() {
+        let path = env::temp_dir()
+            .join(format!("layout-round-trip-{}.toml", process::id()));
+
+        let src = format!("Victory = {{ SurviveWaves = 2 }}\n{}", LEVEL);
+
+        fs::write(&path, src).unwrap();
+
+        let mut layout = load_layout(&path).unwrap();
+
+        layout.walls.push(LevelPoint::new(-3, 1));
+        layout.holes.push(LevelPoint::new(1, -3));
+        layout.corridors.push(LevelCorridor {
+            x1: 0,
+            y1: 0,
+            x2: -12,
+            y2: 5,
+        });
+
+        save_layout(&path, &layout).unwrap();
+
+        let layout2 = load_layout(&path).unwrap();
+        let level = load(&path, &mut GameRng::new(0)).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(format!("{:?}", layout), format!("{:?}", layout2));
+        assert_eq!(Some(LevelVictory::SurviveWaves(2)), level.victory);
+        assert_eq!(2, level.upcoming_waves.len());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let src = LEVEL.replace("Radius", "Radios");
+
+        assert!(parse_err(&src).contains("unknown field `Radios`"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Checks whether a level is playable, i.e. whether player's units can get to
+//! each of its lymph nodes.
+//!
+//! Generated layouts are validated after each wave gets generated (see
+//! `gen::progress()`), since chambers placed next to each other or corridors
+//! running across one another can easily wall off a part of the level.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{Level, LevelMap, LevelPoint};
+use crate::pathfinding::{DiscreteMap, Map, MapBounds, MapLymphNode, MapWall};
+use crate::systems::units::Alignment;
+
+/// Margin (in fields) added around the level's bounds, since some of the walls
+/// (e.g. the ones along corridors) can stick out of them
+const MARGIN: i32 = 2;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LevelIssues {
+    /// Lymph nodes that have got a wall built on top of them
+    pub buried: Vec<(i32, i32)>,
+
+    /// Lymph nodes that cannot be reached from the player's territory
+    pub unreachable: Vec<(i32, i32)>,
+}
+
+impl LevelIssues {
+    pub fn is_empty(&self) -> bool {
+        self.buried.is_empty() && self.unreachable.is_empty()
+    }
+}
+
+impl fmt::Display for LevelIssues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} buried and {} unreachable lymph node(s)",
+            self.buried.len(),
+            self.unreachable.len()
+        )
+    }
+}
+
+pub fn validate(level: &Level) -> LevelIssues {
+    let mut map = level.map.clone();
+
+    map.apply(&level.wave.ops);
+    check(&map, level.bounds())
+}
+
+fn check(
+    map: &LevelMap,
+    (min_x, min_y, max_x, max_y): (i32, i32, i32, i32),
+) -> LevelIssues {
+    let buried = map
+        .lymph_nodes
+        .keys()
+        .copied()
+        .filter(|pos| map.walls.contains(pos))
+        .collect();
+
+    let bounds = MapBounds {
+        min_x: min_x - MARGIN,
+        min_y: min_y - MARGIN,
+        max_x: max_x + MARGIN,
+        max_y: max_y + MARGIN,
+    };
+
+    let point = |(x, y): (i32, i32)| LevelPoint::new(x, y);
+
+    let pathfinding_map = Map {
+        lymph_nodes: map
+            .lymph_nodes
+            .keys()
+            .map(|&pos| point(pos))
+            .filter(|&pos| bounds.contains(pos))
+            .map(|pos| MapLymphNode { pos })
+            .collect(),
+
+        walls: map
+            .walls
+            .iter()
+            .map(|&pos| point(pos))
+            .filter(|&pos| bounds.contains(pos))
+            .map(|pos| MapWall { pos })
+            .collect(),
+
+        bounds,
+    };
+
+    // All of the player's lymph nodes are placed next to each other, so
+    // starting from any of them is enough - if the other ones aren't reachable
+    // from there, they get reported, too
+    let reachable = map
+        .lymph_nodes
+        .iter()
+        .find(|(_, alignment)| **alignment == Alignment::Player)
+        .map(|(&pos, _)| reachable_fields(&pathfinding_map, point(pos)))
+        .unwrap_or_default();
+
+    let unreachable = map
+        .lymph_nodes
+        .keys()
+        .copied()
+        .filter(|&(x, y)| {
+            // Lymph nodes are obstacles on their own, so what we're looking
+            // for is a reachable field next to them
+            let is_reachable = (-1..=1).any(|dx| {
+                (-1..=1).any(|dy| reachable.contains(&(x + dx, y + dy)))
+            });
+
+            !is_reachable
+        })
+        .collect();
+
+    LevelIssues {
+        buried,
+        unreachable,
+    }
+}
+
+/// Returns all fields a unit standing at `start` can walk to.
+fn reachable_fields(map: &Map, start: LevelPoint) -> BTreeSet<(i32, i32)> {
+    let map = if let Some(map) = DiscreteMap::from_local(map, start, start) {
+        map
+    } else {
+        return Default::default();
+    };
+
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![map.start()];
+
+    while let Some(node) = pending.pop() {
+        if visited.insert(node) {
+            pending.extend(map.successors(*node).map(|(node, _)| node));
+        }
+    }
+
+    visited
+        .into_iter()
+        .map(|node| {
+            let pos = map.idx_to_pos(*node);
+            (pos.x, pos.y)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{gen, LevelGeneratorKind, LevelKind};
+    use crate::rng::GameRng;
+
+    /// Returns a map enclosed in a rectangle of walls, with a player-owned
+    /// lymph node at `(0, 0)` and an enemy-owned one at `(6, 0)`
+    fn map() -> LevelMap {
+        let mut map = LevelMap::default();
+
+        for x in -3..=9 {
+            map.walls.insert((x, -3));
+            map.walls.insert((x, 3));
+        }
+
+        for y in -3..=3 {
+            map.walls.insert((-3, y));
+            map.walls.insert((9, y));
+        }
+
+        map.lymph_nodes.insert((0, 0), Alignment::Player);
+        map.lymph_nodes.insert((6, 0), Alignment::Enemy);
+        map
+    }
+
+    const BOUNDS: (i32, i32, i32, i32) = (-3, -3, 9, 3);
+
+    #[test]
+    fn valid_map() {
+        assert_eq!(LevelIssues::default(), check(&map(), BOUNDS));
+    }
+
+    #[test]
+    fn walled_off_lymph_node() {
+        let mut map = map();
+
+        for y in -2..=2 {
+            map.walls.insert((3, y));
+        }
+
+        let issues = check(&map, BOUNDS);
+
+        assert_eq!(Vec::<(i32, i32)>::new(), issues.buried);
+        assert_eq!(vec![(6, 0)], issues.unreachable);
+    }
+
+    #[test]
+    fn diagonal_gaps_are_not_passable() {
+        let mut map = map();
+
+        for y in -2..=2 {
+            map.walls.insert((3 + y % 2, y));
+        }
+
+        assert_eq!(vec![(6, 0)], check(&map, BOUNDS).unreachable);
+    }
+
+    #[test]
+    fn buried_lymph_node() {
+        let mut map = map();
+
+        map.walls.insert((6, 0));
+
+        let issues = check(&map, BOUNDS);
+
+        assert_eq!(vec![(6, 0)], issues.buried);
+        assert_eq!(Vec::<(i32, i32)>::new(), issues.unreachable);
+    }
+
+    #[test]
+    fn lymph_nodes_outside_player_territory() {
+        let mut map = map();
+
+        map.lymph_nodes.insert((20, 0), Alignment::Enemy);
+
+        assert_eq!(vec![(20, 0)], check(&map, (-3, -3, 20, 3)).unreachable);
+    }
+
+    #[test]
+    fn generated_levels() {
+        for generator in LevelGeneratorKind::variants() {
+            for seed in 0..10 {
+                let mut rng = GameRng::new(seed);
+
+                let mut level = Level {
+                    kind: LevelKind::Generated(generator),
+                    ..Level::empty()
+                };
+
+                let context = |wave| {
+                    format!(
+                        "generator={}, seed={}, wave={}",
+                        generator.label(),
+                        seed,
+                        wave
+                    )
+                };
+
+                // Invalid layouts get replaced with a fallback one (or with
+                // nothing at all), so we have to check whether the generator
+                // itself has managed to yield a valid one
+                assert!(
+                    gen::start(&mut level, generator, &mut rng),
+                    "{}: couldn't generate a valid layout",
+                    context(0)
+                );
+
+                assert_reachable(&level, context(0));
+
+                for wave in 1..=5 {
+                    level.map.apply(&level.wave.ops);
+
+                    assert!(
+                        gen::progress(&mut level, generator, &mut rng),
+                        "{}: couldn't generate a valid layout",
+                        context(wave)
+                    );
+
+                    assert_reachable(&level, context(wave));
+                }
+            }
+        }
+    }
+
+    /// Checks the level's layout (as built by its current wave) on its own,
+    /// without relying on `Level::validate()`.
+    fn assert_reachable(level: &Level, context: String) {
+        let mut map = level.map.clone();
+
+        map.apply(&level.wave.ops);
+
+        assert!(
+            map.lymph_nodes.values().any(|a| *a == Alignment::Player),
+            "{}: there are no player's lymph nodes",
+            context
+        );
+
+        let issues = check(&map, level.bounds());
+
+        assert!(issues.is_empty(), "{}: {:?}", context, issues);
+    }
+}
@@ -1,10 +1,14 @@
 #![feature(array_windows)]
 #![feature(try_blocks)]
 
+pub mod blueprints;
+pub mod campaign;
 pub mod compiling;
 pub mod game;
+pub mod headless;
 pub mod level;
 pub mod pathfinding;
+pub mod rng;
 pub mod systems;
 pub mod theme;
 pub mod tutorial;
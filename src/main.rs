@@ -2,9 +2,12 @@ use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 use bevy_prototype_debug_lines::DebugLinesPlugin;
 use unfair_advantage::systems::{
-    background, bio, camera, debug, enemy_ai, input, physics, units,
+    background, bio, camera, debug, enemy_ai, evolution, immunity, input,
+    physics, units,
+};
+use unfair_advantage::{
+    blueprints, campaign, compiling, game, pathfinding, ui,
 };
-use unfair_advantage::{compiling, game, pathfinding, ui};
 
 fn main() {
     let mut app = App::new();
@@ -18,12 +21,17 @@ fn main() {
         .add_plugin(ui::UiPlugin)
         .add_plugin(compiling::CompilingPlugin)
         .add_plugin(pathfinding::PathfindingPlugin)
-        .add_plugin(game::GamePlugin);
+        .add_plugin(game::GamePlugin)
+        .add_plugin(game::GameHudPlugin)
+        .add_plugin(campaign::CampaignPlugin)
+        .add_plugin(blueprints::BlueprintsPlugin);
 
     background::initialize(&mut app);
     bio::initialize(&mut app);
     camera::initialize(&mut app);
     enemy_ai::initialize(&mut app);
+    evolution::initialize(&mut app);
+    immunity::initialize(&mut app);
     physics::initialize(&mut app);
     units::initialize(&mut app);
 
@@ -19,13 +19,20 @@ pub struct DiscreteMap {
 
 impl DiscreteMap {
     pub fn new(map: &Map, pathseeker: Vec2, target: Vec2) -> Option<Self> {
-        let pathseeker = map
-            .bounds
-            .try_pos_to_idx(PathfindingPlugin::world_to_local(pathseeker))?;
+        Self::from_local(
+            map,
+            PathfindingPlugin::world_to_local(pathseeker),
+            PathfindingPlugin::world_to_local(target),
+        )
+    }
 
-        let target = map
-            .bounds
-            .try_pos_to_idx(PathfindingPlugin::world_to_local(target))?;
+    pub fn from_local(
+        map: &Map,
+        pathseeker: LevelPoint,
+        target: LevelPoint,
+    ) -> Option<Self> {
+        let pathseeker = map.bounds.try_pos_to_idx(pathseeker)?;
+        let target = map.bounds.try_pos_to_idx(target)?;
 
         let mut fields = vec![
             Field::default();
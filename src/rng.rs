@@ -0,0 +1,110 @@
+use std::env;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+/// Source of randomness for everything that shapes the game - the level's
+/// layout, its waves and the pathogens - so that given the same seed, the same
+/// game can be reproduced.
+///
+/// (purely cosmetic stuff, such as colors or wires, uses `thread_rng()`.)
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+
+    /// Number of values drawn so far (see: `Self::restore()`)
+    draws: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            draws: 0,
+        }
+    }
+
+    /// Re-creates RNG that's been seeded with `seed` and has had `draws` values
+    /// drawn from it - i.e. picks up where a saved game has left off.
+    pub fn restore(seed: u64, draws: u64) -> Self {
+        let mut rng = Self::new(seed);
+
+        for _ in 0..draws {
+            rng.next_u64();
+        }
+
+        rng
+    }
+
+    /// Creates RNG seeded with the `SEED` environment variable or, if it's not
+    /// set, with a random seed.
+    pub fn from_env() -> Self {
+        let seed = env::var("SEED").ok().and_then(|seed| match seed.parse() {
+            Ok(seed) => Some(seed),
+
+            Err(err) => {
+                warn!("Ignoring invalid seed `{}`: {}", seed, err);
+                None
+            }
+        });
+
+        Self::new(seed.unwrap_or_else(|| rand::thread_rng().gen()))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+// Everything goes through `next_u64()`, so that the number of calls to it is
+// all it takes to restore the generator's state
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore() {
+        let mut rng = GameRng::new(1234);
+        let mut bytes = [0; 13];
+
+        rng.gen::<f32>();
+        rng.gen::<u64>();
+        rng.fill_bytes(&mut bytes);
+
+        let mut restored = GameRng::restore(rng.seed(), rng.draws());
+
+        for _ in 0..10 {
+            assert_eq!(rng.gen::<u32>(), restored.gen::<u32>());
+        }
+    }
+}
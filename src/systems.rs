@@ -3,6 +3,8 @@ pub mod bio;
 pub mod camera;
 pub mod debug;
 pub mod enemy_ai;
+pub mod evolution;
+pub mod immunity;
 pub mod input;
 pub mod physics;
 pub mod units;
@@ -8,11 +8,13 @@ mod pathogen;
 mod protein;
 mod wall;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::f32::consts::TAU;
 
 use bevy::math::{vec2, vec3};
 use bevy::prelude::*;
 use bevy_prototype_debug_lines::DebugLines;
+use itertools::Itertools;
 use keyframe::functions::EaseInOutCubic;
 use keyframe::EasingFunction;
 use rand::Rng;
@@ -26,71 +28,285 @@ pub use self::lymph_node::*;
 pub use self::pathogen::*;
 pub use self::protein::*;
 pub use self::wall::*;
+use super::immunity::ImmuneMemory;
 use super::physics::PHYSICS_SCALE;
 use super::units::Alignment;
 use crate::compiling::RecompileEvent;
+use crate::game::GameTime;
+use crate::level::Level;
+use crate::pathfinding::NavigateUnit;
+use crate::rng::GameRng;
 
 pub fn initialize(app: &mut App) {
-    app.add_system(progress_lymph_nodes)
-        .add_system(handle_lymph_node_alignment)
-        .add_system(animate_warnings)
+    initialize_simulation(app);
+
+    app.add_system(animate_warnings)
         .add_system(animate_progress_bars)
         .add_system(animate_fresh_cells)
-        .add_system(animate_connections)
+        .add_system(animate_connections);
+}
+
+/// Registers only the systems that affect the game's outcome, skipping the
+/// purely visual ones - used on its own in headless mode.
+pub fn initialize_simulation(app: &mut App) {
+    app.add_system(progress_lymph_nodes)
+        .add_system(progress_connections)
+        .add_system(rally_produced_cells)
+        .add_system(handle_lymph_node_alignment)
+        .add_system(divide_bacteria)
         .add_system(animate_walls)
         .add_system(progress_dying_connections);
 }
 
 fn progress_lymph_nodes(
     mut commands: Commands,
-    time: Res<Time>,
-    assets: Res<AssetServer>,
-    mut query: Query<(Entity, &mut LymphNode, &Transform)>,
+    time: Res<GameTime>,
+    memory: Res<ImmuneMemory>,
+    assets: Option<Res<AssetServer>>,
+    mut rng: ResMut<GameRng>,
+    mut nodes: Query<(Entity, &mut LymphNode, &Transform)>,
+    mut connections: Query<
+        &mut LymphNodeConnection,
+        Without<DeadLymphNodeConnection>,
+    >,
+    cells: Query<&ProducedBy>,
 ) {
-    for (_, mut node, transform) in &mut query.iter_mut() {
-        if !node.is_spawner() {
+    // Gathered up-front, since the nodes get modified below
+    let producers: BTreeSet<_> = nodes
+        .iter()
+        .filter(|(_, node, _)| node.is_producer())
+        .map(|(entity, _, _)| entity)
+        .collect();
+
+    let alive_cells = cells.iter().map(|ProducedBy(node)| *node).counts();
+
+    let mut in_transit = BTreeMap::new();
+
+    for connection in connections.iter() {
+        *in_transit
+            .entry((connection.source, connection.target))
+            .or_default() += connection.packets.len();
+    }
+
+    // Nodes linked to the same parent compete for its products - each product
+    // goes to the highest-priority target that's got room for it; nodes that
+    // have got targets, but no such target, are blocked
+    let mut consumers = BTreeMap::new();
+    let mut blocked = BTreeSet::new();
+
+    for (entity, node, _) in nodes.iter() {
+        if node.targets.is_empty() {
             continue;
         }
 
-        let node = &mut *node;
+        let targets = node.targets.iter().filter_map(|&target| {
+            let (_, target_node, _) = nodes.get(target).ok()?;
+
+            let stock =
+                target_node.stock.get(&entity).copied().unwrap_or_default()
+                    + in_transit
+                        .get(&(entity, target))
+                        .copied()
+                        .unwrap_or_default();
 
-        let product = if let Some(product) = &node.product {
-            product
+            Some((target, target_node.production.priority, stock))
+        });
+
+        if let Some(consumer) = LymphNode::pick_consumer(targets) {
+            consumers.insert(entity, consumer);
         } else {
+            blocked.insert(entity);
+        }
+    }
+
+    let mut sent = Vec::new();
+
+    for (entity, mut node, transform) in nodes.iter_mut() {
+        let node = &mut *node;
+
+        if !node.is_producer() {
+            node.flow = LymphNodeFlow::Idle;
             continue;
+        }
+
+        let inputs = node.required_inputs(|parent| producers.contains(&parent));
+
+        let starved_on = inputs.iter().find(|parent| {
+            node.stock.get(parent).copied().unwrap_or_default() == 0
+        });
+
+        let is_capped = node.production.cap.map_or(false, |cap| {
+            alive_cells.get(&entity).copied().unwrap_or_default() >= cap
+        });
+
+        node.flow = if node.production.remaining == Some(0) {
+            LymphNodeFlow::Finished
+        } else if let Some(&parent) = starved_on {
+            LymphNodeFlow::Starved(parent)
+        } else if blocked.contains(&entity) {
+            LymphNodeFlow::Blocked
+        } else if is_capped {
+            LymphNodeFlow::Capped
+        } else {
+            LymphNodeFlow::Producing
         };
 
-        node.production_tt += time.delta_seconds();
+        if node.flow != LymphNodeFlow::Producing {
+            continue;
+        }
 
-        if node.production_tt >= LymphNode::PRODUCTION_DURATION {
-            node.production_tt = 0.0;
+        let speed =
+            if let Some(LymphNodeProduct::Leukocyte(cell)) = &node.product {
+                memory.production_speed(cell.binder)
+            } else {
+                1.0
+            };
 
-            match product {
-                LymphNodeProduct::Resource(_) => {
-                    // no-op
-                }
+        node.production_tt += speed * time.delta_seconds();
 
-                LymphNodeProduct::Leukocyte(leukocyte) => {
-                    let (pos, vel) =
-                        get_random_position_and_velocity(transform);
+        if node.production_tt < node.production_duration() {
+            continue;
+        }
 
-                    leukocyte.spawn(&mut commands, &assets, pos, vel);
-                }
+        node.production_tt = 0.0;
 
-                LymphNodeProduct::Pathogen(pathogen) => {
-                    let (pos, vel) =
-                        get_random_position_and_velocity(transform);
+        if let Some(remaining) = &mut node.production.remaining {
+            *remaining -= 1;
+        }
 
-                    pathogen.spawn(&mut commands, &assets, pos, vel);
-                }
+        for parent in inputs {
+            if let Some(stock) = node.stock.get_mut(&parent) {
+                *stock -= 1;
+            }
+        }
+
+        if let Some(&consumer) = consumers.get(&entity) {
+            sent.push((entity, consumer));
+            continue;
+        }
+
+        match &node.product {
+            Some(LymphNodeProduct::Leukocyte(leukocyte)) => {
+                let (pos, vel) =
+                    get_random_position_and_velocity(&mut *rng, transform);
+
+                let cell =
+                    leukocyte.spawn(&mut commands, assets.as_deref(), pos, vel);
+
+                commands
+                    .entity(cell)
+                    .insert(ProducedBy(entity))
+                    .insert(AwaitingRally);
+            }
+
+            Some(LymphNodeProduct::Pathogen(pathogen)) => {
+                let (pos, vel) =
+                    get_random_position_and_velocity(&mut *rng, transform);
+
+                let cell =
+                    pathogen.spawn(&mut commands, assets.as_deref(), pos, vel);
+
+                commands
+                    .entity(cell)
+                    .insert(ProducedBy(entity))
+                    .insert(AwaitingRally);
+            }
+
+            _ => {
+                // Resources don't get spawned outside (`is_producer()` takes
+                // care of that)
             }
         }
     }
+
+    for (source, target) in sent {
+        let connection = connections.iter_mut().find(|connection| {
+            connection.source == source && connection.target == target
+        });
+
+        if let Some(mut connection) = connection {
+            connection.packets.push(0.0);
+        } else if let Ok((_, mut target, _)) = nodes.get_mut(target) {
+            // Connection gets spawned a frame after linking the nodes, so
+            // there might be no connection yet - in that case there's nothing
+            // to travel along, and the product gets delivered right away
+            *target.stock.entry(source).or_default() += 1;
+        }
+    }
 }
 
-fn get_random_position_and_velocity(transform: &Transform) -> (Vec2, Vec2) {
-    let mut rng = rand::thread_rng();
+/// Moves products along the connections, delivering the ones that have
+/// reached their target.
+fn progress_connections(
+    time: Res<GameTime>,
+    mut connections: Query<
+        &mut LymphNodeConnection,
+        Without<DeadLymphNodeConnection>,
+    >,
+    mut nodes: Query<&mut LymphNode>,
+) {
+    for mut connection in connections.iter_mut() {
+        if connection.packets.is_empty() {
+            continue;
+        }
+
+        let duration = connection.travel_duration();
+
+        for packet in &mut connection.packets {
+            *packet += time.delta_seconds();
+        }
+
+        let arrived = connection
+            .packets
+            .iter()
+            .filter(|&&packet| packet >= duration)
+            .count();
+
+        if arrived == 0 {
+            continue;
+        }
+
+        connection.packets.retain(|&packet| packet < duration);
+
+        if let Ok(mut node) = nodes.get_mut(connection.target) {
+            *node.stock.entry(connection.source).or_default() += arrived;
+        }
+    }
+}
+
+/// Sends freshly produced cells towards their lymph node's rally point, if
+/// it's got one.
+fn rally_produced_cells(
+    mut commands: Commands,
+    cells: Query<(Entity, &ProducedBy), With<AwaitingRally>>,
+    nodes: Query<(&LymphNode, &Transform)>,
+    mut navigate_tx: EventWriter<NavigateUnit>,
+) {
+    for (entity, ProducedBy(node)) in cells.iter() {
+        commands.entity(entity).remove::<AwaitingRally>();
+
+        let target = nodes
+            .get(*node)
+            .ok()
+            .and_then(|(node, _)| node.rally)
+            .and_then(|rally| {
+                rally.pos(|node| {
+                    let (_, transform) = nodes.get(node).ok()?;
+
+                    Some(transform.translation.truncate())
+                })
+            });
+
+        if let Some(target) = target {
+            navigate_tx.send(NavigateUnit { entity, target });
+        }
+    }
+}
 
+fn get_random_position_and_velocity(
+    rng: &mut impl Rng,
+    transform: &Transform,
+) -> (Vec2, Vec2) {
     let pos = transform.translation.truncate() / PHYSICS_SCALE;
 
     let vel = {
@@ -112,22 +328,33 @@ fn get_random_position_and_velocity(transform: &Transform) -> (Vec2, Vec2) {
 }
 
 fn handle_lymph_node_alignment(
-    mut lymph_nodes: Query<(&mut LymphNode, &Alignment), Changed<Alignment>>,
+    mut rng: ResMut<GameRng>,
+    level: Res<Level>,
+    mut lymph_nodes: Query<
+        (Entity, &mut LymphNode, &Alignment),
+        Changed<Alignment>,
+    >,
     mut recompile: EventWriter<RecompileEvent>,
 ) {
     let mut sent_event = false;
     let mut parents = vec![];
 
-    for (mut node, alignment) in lymph_nodes.iter_mut() {
+    for (entity, mut node, alignment) in lymph_nodes.iter_mut() {
         match alignment {
             Alignment::Enemy => {
-                if let Some(parent) = node.parent {
-                    parents.push(parent);
-                }
+                parents.extend(
+                    node.parents.iter().map(|&parent| (parent, entity)),
+                );
 
-                node.target = LymphNodeTarget::Outside;
-                node.product =
-                    Some(LymphNodeProduct::Pathogen(Pathogen::random()));
+                node.targets.clear();
+                node.stock.clear();
+                node.production = Default::default();
+                node.rally = None;
+                node.product = Some(LymphNodeProduct::Pathogen(Pathogen {
+                    kind: level.stage().pathogen_kind(&mut *rng),
+                    hp: level.stage().pathogen_hp,
+                    ..Pathogen::random(&mut *rng)
+                }));
             }
 
             _ => {
@@ -141,13 +368,57 @@ fn handle_lymph_node_alignment(
         }
     }
 
-    for parent in parents {
-        if let Ok((mut node, _)) = lymph_nodes.get_mut(parent) {
-            node.target = LymphNodeTarget::Outside;
+    for (parent, child) in parents {
+        if let Ok((_, mut node, _)) = lymph_nodes.get_mut(parent) {
+            node.targets.retain(|&target| target != child);
         }
     }
 }
 
+fn divide_bacteria(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    time: Res<GameTime>,
+    assets: Option<Res<AssetServer>>,
+    mut bacteria: Query<(&Pathogen, &Transform, &mut Division)>,
+    leukocytes: Query<&Transform, With<Leukocyte>>,
+) {
+    let mut count = bacteria.iter().count();
+
+    for (pathogen, transform, mut division) in bacteria.iter_mut() {
+        let pos = transform.translation.truncate();
+
+        let is_disturbed = leukocytes.iter().any(|leukocyte| {
+            leukocyte.translation.truncate().distance(pos)
+                <= Division::DISTURBANCE_RADIUS
+        });
+
+        if is_disturbed {
+            division.tt = 0.0;
+            continue;
+        }
+
+        division.tt += time.delta_seconds();
+
+        if division.tt < Division::DURATION || count >= Division::MAX_BACTERIA {
+            continue;
+        }
+
+        division.tt = 0.0;
+        count += 1;
+
+        let angle = rng.gen_range(0.0..TAU);
+        let offset = vec2(angle.cos(), angle.sin()) * 2.0 * Cell::SIZE;
+
+        pathogen.spawn(
+            &mut commands,
+            assets.as_deref(),
+            pos / PHYSICS_SCALE + offset,
+            Vec2::ZERO,
+        );
+    }
+}
+
 fn animate_warnings(
     assets: Res<AssetServer>,
     time: Res<Time>,
@@ -190,8 +461,8 @@ fn animate_progress_bars(
     for (parent, mut transform) in progress_bars.iter_mut() {
         let node = nodes.get(**parent).unwrap();
 
-        let progress = if node.is_spawner() {
-            node.production_tt / LymphNode::PRODUCTION_DURATION
+        let progress = if node.flow != LymphNodeFlow::Idle {
+            node.production_tt / node.production_duration()
         } else {
             0.0
         };
@@ -238,6 +509,25 @@ fn animate_connections(
             connection.tt += time.delta_seconds();
         }
 
+        if dead_tag.is_none() {
+            let duration = connection.travel_duration();
+
+            for &packet in &connection.packets {
+                let pos = connection
+                    .source_pos
+                    .lerp(connection.target_pos, (packet / duration).min(1.0));
+
+                for dir in [vec2(1.0, 1.0), vec2(1.0, -1.0)] {
+                    debug_lines.line_colored(
+                        (pos - 6.0 * dir).extend(0.6),
+                        (pos + 6.0 * dir).extend(0.6),
+                        0.0,
+                        Color::rgba_linear(0.6, 0.9, 0.9, 1.0),
+                    );
+                }
+            }
+        }
+
         let budget = connection.tt;
 
         for wire in &mut connection.wires {
@@ -281,7 +571,7 @@ fn animate_connections(
 
 fn animate_walls(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut fade_ins: Query<
         (Entity, &mut Transform, &mut WallFadeIn),
         (With<Wall>, Without<WallFadeOut>),
@@ -320,7 +610,7 @@ fn animate_walls(
 
 fn progress_dying_connections(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut connections: Query<(
         Entity,
         &mut LymphNodeConnection,
@@ -2,12 +2,15 @@ use std::f32::consts::TAU;
 
 use bevy::prelude::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use super::{Body, CellFadeIn};
 use crate::systems::bio::Cell;
 use crate::systems::physics::PHYSICS_SCALE;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub enum Antigen {
     Rectangle,
     Semicircle,
@@ -3,7 +3,7 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use rand::Rng;
 
-use super::{Antigen, Leukocyte, Pathogen, Protein};
+use super::{Antigen, Division, Leukocyte, Pathogen, PathogenKind, Protein};
 use crate::systems::input::{Collider, Selector};
 use crate::systems::physics::PHYSICS_SCALE;
 use crate::systems::units::combat::Weapon;
@@ -11,7 +11,10 @@ use crate::systems::units::{Alignment, DeathBehavior, Health, Unit};
 use crate::theme;
 
 #[derive(Component)]
-pub struct CellBody;
+pub struct CellBody {
+    /// Size of the cell, relative to `Cell::SIZE`
+    pub scale: f32,
+}
 
 pub enum Cell<'a> {
     Leukocyte(&'a Leukocyte),
@@ -21,15 +24,20 @@ pub enum Cell<'a> {
 impl<'a> Cell<'a> {
     pub const SIZE: f32 = 0.125;
 
+    /// Spawns the cell; when `assets` is `None` (i.e. in headless mode), the
+    /// cell gets spawned without any sprites.
     pub fn spawn(
         &self,
         commands: &mut Commands,
-        assets: &AssetServer,
+        assets: Option<&AssetServer>,
         pos: Vec2,
         vel: Vec2,
-    ) {
+    ) -> Entity {
         let mut rng = rand::thread_rng();
+        let scale = self.scale();
+        let radius = (Self::SIZE + Antigen::SIZE) * scale;
         let mut entity = commands.spawn();
+        let entity_id = entity.id();
 
         entity
             .insert(Transform::from_translation(
@@ -56,9 +64,7 @@ impl<'a> Cell<'a> {
                 ..Default::default()
             })
             .insert_bundle(ColliderBundle {
-                shape: ColliderShapeComponent(ColliderShape::ball(
-                    Self::SIZE + Antigen::SIZE,
-                )),
+                shape: ColliderShapeComponent(ColliderShape::ball(radius)),
                 material: ColliderMaterialComponent(ColliderMaterial {
                     friction: 0.1,
                     restitution: 0.5,
@@ -72,7 +78,7 @@ impl<'a> Cell<'a> {
             })
             .insert(RigidBodyPositionSync::Discrete)
             .insert(Collider::Circle {
-                radius: (Self::SIZE + Antigen::SIZE) * PHYSICS_SCALE,
+                radius: radius * PHYSICS_SCALE,
             })
             .insert(CellFadeIn::default());
 
@@ -80,24 +86,45 @@ impl<'a> Cell<'a> {
             Cell::Leukocyte(cell) => {
                 entity.insert(Weapon::AntigenBinder(cell.binder));
                 entity.insert(Alignment::Player);
-                entity.insert(Health::with_health(cell.props.hp as f32));
+                entity.insert(Health {
+                    regen_rate: cell.props.regen,
+                    ..Health::with_health(cell.props.hp)
+                });
                 entity.insert((*cell).to_owned());
             }
             Cell::Pathogen(cell) => {
                 entity.insert(Weapon::Antigen(cell.antigen));
                 entity.insert(Alignment::Enemy);
-                entity.insert(Health::default());
+                entity.insert(Health::with_health(cell.max_hp()));
                 entity.insert((*cell).to_owned());
+
+                if cell.kind == PathogenKind::Bacterium {
+                    entity.insert(Division::default());
+                }
             }
         }
 
+        let lifespan = match self {
+            Cell::Leukocyte(cell) => cell.props.lifespan,
+            Cell::Pathogen(_) => None,
+        };
+
         entity
-            .insert(Unit::default())
+            .insert(Unit {
+                lifespan,
+                ..Default::default()
+            })
             .insert(DeathBehavior::Despawn);
 
+        let assets = if let Some(assets) = assets {
+            assets
+        } else {
+            return entity_id;
+        };
+
         let (body, proteins, color) = match self {
             Cell::Leukocyte(cell) => {
-                (cell.body, &cell.proteins[..], Leukocyte::color(0))
+                (cell.body, &cell.proteins[..], cell.kind.color(0))
             }
             Cell::Pathogen(cell) => {
                 (cell.body, [].as_slice(), Pathogen::color(0))
@@ -108,9 +135,9 @@ impl<'a> Cell<'a> {
         entity.with_children(|entity| {
             entity
                 .spawn()
-                .insert(Transform::default())
+                .insert(Transform::from_scale(Vec3::splat(scale)))
                 .insert(GlobalTransform::default())
-                .insert(CellBody)
+                .insert(CellBody { scale })
                 .with_children(|entity| {
                     let texture = assets.load(body.asset_path());
 
@@ -175,10 +202,19 @@ impl<'a> Cell<'a> {
             Selector::spawn(
                 assets,
                 entity,
-                2.4 * Self::SIZE * PHYSICS_SCALE,
+                2.4 * Self::SIZE * scale * PHYSICS_SCALE,
                 Color::rgba_u8(0, 220, 0, 50),
             );
         });
+
+        entity_id
+    }
+
+    fn scale(&self) -> f32 {
+        match self {
+            Cell::Leukocyte(cell) => cell.props.radius,
+            Cell::Pathogen(cell) => cell.kind.size(),
+        }
     }
 }
 
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::{AntigenBinder, Body, Cell, Protein};
+use crate::systems::units::REGEN_RATE;
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct Leukocyte {
     pub body: Body,
     pub binder: AntigenBinder,
@@ -12,28 +14,120 @@ pub struct Leukocyte {
 }
 
 impl Leukocyte {
+    const MEMORY_CELL_HP_FACTOR: f32 = 1.5;
+    const MEMORY_CELL_DAMAGE_FACTOR: f32 = 1.5;
+
     pub fn color(a: u8) -> Color {
         Color::rgba_u8(255, 255, 255, a)
     }
 
+    /// Turns a killer into a memory cell - a tougher killer, produced once
+    /// the player's army has learned to fight pathogens with the matching
+    /// antigen (see `ImmuneMemory`).
+    pub fn into_memory_cell(mut self) -> Self {
+        self.kind = LeukocyteKind::Memory;
+        self.props.hp *= Self::MEMORY_CELL_HP_FACTOR;
+        self.props.speed = LeukocyteKind::Memory.speed();
+        self.props.damage *= Self::MEMORY_CELL_DAMAGE_FACTOR;
+        self
+    }
+
     pub fn spawn(
         &self,
         commands: &mut Commands,
-        assets: &AssetServer,
+        assets: Option<&AssetServer>,
         pos: Vec2,
         vel: Vec2,
-    ) {
-        Cell::Leukocyte(self).spawn(commands, assets, pos, vel);
+    ) -> Entity {
+        Cell::Leukocyte(self).spawn(commands, assets, pos, vel)
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LeukocyteKind {
-    // Cager, TODO(pwy) post-MVP
     Killer,
+
+    /// Immobilises pathogens on contact instead of damaging them
+    Cager,
+
+    /// Engulfs weakened pathogens
+    Macrophage,
+
+    /// Makes nearby leukocytes deal more damage, but it's a poor fighter on
+    /// its own
+    Helper,
+
+    /// Tougher killer, produced instead of killers that match a remembered
+    /// strain
+    Memory,
+}
+
+impl LeukocyteKind {
+    /// Returns how fast leukocytes of this kind move by default, relative to
+    /// the base speed (see `LeukocyteProps::speed`).
+    pub fn speed(self) -> f32 {
+        match self {
+            Self::Killer => 1.0,
+            Self::Cager => 1.3,
+            Self::Macrophage => 0.6,
+            Self::Helper => 0.9,
+            Self::Memory => 1.1,
+        }
+    }
+
+    pub fn color(self, a: u8) -> Color {
+        match self {
+            Self::Killer => Leukocyte::color(a),
+            Self::Cager => Color::rgba_u8(140, 210, 255, a),
+            Self::Macrophage => Color::rgba_u8(255, 205, 140, a),
+            Self::Helper => Color::rgba_u8(175, 255, 175, a),
+            Self::Memory => Color::rgba_u8(255, 235, 130, a),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LeukocyteProps {
     pub hp: f32,
+
+    /// How fast the leukocyte moves, relative to the base speed
+    pub speed: f32,
+
+    /// How much damage the leukocyte deals, relative to the base damage
+    pub damage: f32,
+
+    /// Health regenerated per second
+    pub regen: f32,
+
+    /// For how long (in seconds) the leukocyte lives; `None` stands for
+    /// forever
+    pub lifespan: Option<f32>,
+
+    /// Size of the leukocyte (and so the distance at which it gets into
+    /// contact with others), relative to `Cell::SIZE`
+    pub radius: f32,
+}
+
+impl LeukocyteProps {
+    pub fn new(kind: LeukocyteKind, hp: f32) -> Self {
+        Self {
+            hp,
+            speed: kind.speed(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for LeukocyteProps {
+    fn default() -> Self {
+        Self {
+            hp: 1.0,
+            speed: 1.0,
+            damage: 1.0,
+            regen: REGEN_RATE,
+            lifespan: None,
+            radius: 1.0,
+        }
+    }
 }
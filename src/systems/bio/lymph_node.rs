@@ -1,3 +1,6 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
 use bevy::math::{vec2, vec3};
 use bevy::prelude::*;
 use bevy::sprite::MaterialMesh2dBundle;
@@ -10,9 +13,10 @@ use bevy_rapier2d::prelude::{
 };
 use itertools::Itertools;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use super::{AntigenBinder, Body, Leukocyte, Pathogen, Protein};
-use crate::compiling::CompilationWarning;
+use crate::compiling::{CompilationDiagnostic, CompilationWarning};
 use crate::level::{Level, LevelPoint};
 use crate::systems::input::{Collider, Selector};
 use crate::systems::physics::PHYSICS_SCALE;
@@ -24,32 +28,60 @@ use crate::theme;
 pub struct LymphNode {
     pub pos: LevelPoint,
     pub resource: Option<LymphNodeResource>,
-    pub target: LymphNodeTarget,
+
+    /// Lymph nodes this one sends its products into; when empty, the products
+    /// get spawned outside
+    pub targets: Vec<Entity>,
+
     pub product: Option<LymphNodeProduct>,
-    pub parent: Option<Entity>,
+    /// Lymph nodes that send their products into this one
+    pub parents: Vec<Entity>,
     pub warning: Option<CompilationWarning>,
+    /// Explanation of the warning, if there's anything more to say than its
+    /// description
+    pub diagnostic: Option<CompilationDiagnostic>,
     pub state: LymphNodeState,
+    pub production: LymphNodeProduction,
     pub production_tt: f32,
+
+    /// Where the cells produced by the node head to, once spawned
+    pub rally: Option<LymphNodeRally>,
+
+    /// Products received from the parents and not consumed yet, counted per
+    /// parent
+    pub stock: BTreeMap<Entity, usize>,
+
+    pub flow: LymphNodeFlow,
 }
 
 impl LymphNode {
     pub const SIZE: f32 = 0.25;
     pub const PRODUCTION_DURATION: f32 = 1.5;
 
+    /// How much longer (relative to `PRODUCTION_DURATION`) production takes
+    /// for each input merged on top of the first one
+    pub const PRODUCTION_DURATION_PER_INPUT: f32 = 0.5;
+
+    /// How many products (including the ones still travelling along the link)
+    /// a node accepts from each of its parents; once that's reached, the
+    /// parent stops producing until some of them get consumed
+    pub const STOCK_CAPACITY: usize = 3;
+
+    /// Spawns the lymph node; when `assets` is `None` (i.e. in headless mode),
+    /// the node gets spawned without any sprites.
     pub fn spawn(
         &self,
         commands: &mut Commands,
-        meshes: &mut Assets<Mesh>,
-        materials: &mut Assets<ColorMaterial>,
-        assets: &AssetServer,
+        assets: Option<LymphNodeAssets>,
         alignment: Alignment,
-    ) {
+    ) -> Entity {
         let pos = Level::local_to_world(self.pos);
 
         let transform =
             Transform::from_translation(pos.extend(theme::z_index::LYMPH_NODE));
 
         let mut entity = commands.spawn();
+        let entity_id = entity.id();
 
         entity
             .insert(transform)
@@ -79,6 +111,16 @@ impl LymphNode {
             .insert(Weapon::None)
             .insert(self.to_owned());
 
+        let LymphNodeAssets {
+            assets,
+            meshes,
+            materials,
+        } = if let Some(assets) = assets {
+            assets
+        } else {
+            return entity_id;
+        };
+
         // Spawn lymph node's sprite
         entity.with_children(|entity| {
             entity.spawn_bundle(SpriteBundle {
@@ -111,23 +153,105 @@ impl LymphNode {
                 Color::rgba_u8(242, 185, 56, 50),
             );
         });
+
+        entity_id
     }
 
-    pub fn is_spawner(&self) -> bool {
-        matches!(self.target, LymphNodeTarget::Outside)
-            && matches!(
-                self.product,
-                Some(
-                    LymphNodeProduct::Leukocyte(_)
-                        | LymphNodeProduct::Pathogen(_)
-                )
-            )
+    /// Returns whether the node has got something to produce and somewhere to
+    /// send it to - which doesn't mean it's actually producing, since it might
+    /// be waiting for its inputs (see `flow`).
+    pub fn is_producer(&self) -> bool {
+        let has_destination = match &self.product {
+            None => false,
+            Some(LymphNodeProduct::Resource(_)) => !self.targets.is_empty(),
+            _ => true,
+        };
+
+        has_destination
             && !self.state.is_paused
             && !self.state.is_awaiting_resources
     }
+
+    /// Returns how long it takes the node to produce a single product - the
+    /// more inputs (i.e. the node's resource and its parents' products) get
+    /// merged, the longer it takes.
+    pub fn production_duration(&self) -> f32 {
+        // Infected nodes produce pathogens out of thin air
+        if let Some(LymphNodeProduct::Pathogen(_)) = self.product {
+            return Self::PRODUCTION_DURATION;
+        }
+
+        let inputs = self.parents.len() + usize::from(self.resource.is_some());
+
+        Self::PRODUCTION_DURATION
+            * (1.0
+                + Self::PRODUCTION_DURATION_PER_INPUT
+                    * inputs.saturating_sub(1) as f32)
+    }
+
+    /// Returns products of which parents the node has to receive before it
+    /// can produce anything.
+    ///
+    /// `produces` tells whether given parent produces anything at all, as
+    /// there's no point in waiting for the ones that don't.
+    pub fn required_inputs(
+        &self,
+        produces: impl Fn(Entity) -> bool,
+    ) -> Vec<Entity> {
+        // Infected nodes produce pathogens out of thin air
+        if let Some(LymphNodeProduct::Pathogen(_)) = self.product {
+            return vec![];
+        }
+
+        self.parents
+            .iter()
+            .copied()
+            .filter(|&parent| produces(parent))
+            .collect()
+    }
+
+    /// Returns which of the node's targets should get its next product: the
+    /// highest-priority one that's got room for it and, among the equally
+    /// important ones, the one that's got the fewest of the node's products.
+    ///
+    /// `targets` yields each target's priority and the number of the node's
+    /// products it's already got (including the ones still travelling along
+    /// the link).
+    pub fn pick_consumer(
+        targets: impl IntoIterator<Item = (Entity, LymphNodePriority, usize)>,
+    ) -> Option<Entity> {
+        targets
+            .into_iter()
+            .filter(|&(_, _, stock)| stock < Self::STOCK_CAPACITY)
+            .max_by_key(|&(_, priority, stock)| (priority, Reverse(stock)))
+            .map(|(target, _, _)| target)
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Assets required to render a lymph node
+pub struct LymphNodeAssets<'a> {
+    pub assets: &'a AssetServer,
+    pub meshes: &'a mut Assets<Mesh>,
+    pub materials: &'a mut Assets<ColorMaterial>,
+}
+
+impl<'a> LymphNodeAssets<'a> {
+    /// Returns `Some` only if all of the assets are available, which is not the
+    /// case in headless mode.
+    pub fn new(
+        assets: Option<&'a AssetServer>,
+        meshes: Option<&'a mut Assets<Mesh>>,
+        materials: Option<&'a mut Assets<ColorMaterial>>,
+    ) -> Option<Self> {
+        Some(Self {
+            assets: assets?,
+            meshes: meshes?,
+            materials: materials?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LymphNodeResource {
     Antigen(AntigenBinder),
     Body(Body),
@@ -152,18 +276,106 @@ impl LymphNodeResource {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum LymphNodeTarget {
-    Outside,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LymphNodeRally {
+    /// Given point, in world coordinates
+    Point(Vec2),
+
     LymphNode(Entity),
 }
 
+impl LymphNodeRally {
+    /// Returns position of the rally point; `node_pos` returns positions of
+    /// lymph nodes, which might not exist anymore.
+    pub fn pos(
+        self,
+        node_pos: impl FnOnce(Entity) -> Option<Vec2>,
+    ) -> Option<Vec2> {
+        match self {
+            Self::Point(pos) => Some(pos),
+            Self::LymphNode(node) => node_pos(node),
+        }
+    }
+}
+
+/// What the node's production is up to at the moment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LymphNodeFlow {
+    /// Node doesn't produce anything (e.g. it's paused or misconfigured)
+    Idle,
+
+    Producing,
+
+    /// Node is waiting for products of given parent
+    Starved(Entity),
+
+    /// None of the node's targets has got room for more products
+    Blocked,
+
+    /// Node has got as many of its cells alive as its cap allows (see
+    /// `LymphNodeProduction::cap`)
+    Capped,
+
+    /// Node has produced everything it's been asked for (see
+    /// `LymphNodeProduction::remaining`)
+    Finished,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LymphNodeState {
     pub is_paused: bool,
     pub is_awaiting_resources: bool,
 }
 
+/// Player's settings of the node's production
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct LymphNodeProduction {
+    /// Maximum number of the node's cells that can be alive at once
+    pub cap: Option<usize>,
+
+    /// Number of products left to produce, after which the node stops;
+    /// `None` stands for producing forever
+    pub remaining: Option<usize>,
+
+    pub priority: LymphNodePriority,
+}
+
+/// Decides which of the nodes linked to the same parent gets the parent's
+/// products first - lower-priority nodes get only what the higher-priority
+/// ones have no room for (see `LymphNode::pick_consumer()`).
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum LymphNodePriority {
+    Low,
+    Normal,
+    High,
+}
+
+impl LymphNodePriority {
+    pub fn variants() -> impl Iterator<Item = Self> {
+        [Self::Low, Self::Normal, Self::High].into_iter()
+    }
+}
+
+impl Default for LymphNodePriority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// Lymph node that has produced given cell; used to enforce the node's cap
+/// (see `LymphNodeProduction::cap`)
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ProducedBy(pub Entity);
+
+/// Marks cells that have just been produced and haven't been sent towards
+/// their lymph node's rally point yet (see `LymphNode::rally`)
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AwaitingRally;
+
 #[derive(Clone, Debug)]
 pub enum LymphNodeProduct {
     Resource(LymphNodeResource),
@@ -250,6 +462,10 @@ pub struct LymphNodeConnection {
     pub target_pos: Vec2,
     pub wires: Vec<LymphNodeConnectionWire>,
     pub tt: f32,
+
+    /// Products travelling along the connection, as seconds since they've
+    /// been sent
+    pub packets: Vec<f32>,
 }
 
 impl LymphNodeConnection {
@@ -281,12 +497,22 @@ impl LymphNodeConnection {
             target_pos,
             wires: wires.collect(),
             tt: 0.0,
+            packets: Default::default(),
         }
     }
 
+    /// Speed products travel along connections with (in pixels per second)
+    pub const PACKET_SPEED: f32 = 200.0;
+
     pub fn spawn(self, commands: &mut Commands) {
         commands.spawn().insert(self);
     }
+
+    /// Returns how many seconds it takes for a product to get from the source
+    /// to the target.
+    pub fn travel_duration(&self) -> f32 {
+        self.source_pos.distance(self.target_pos) / Self::PACKET_SPEED
+    }
 }
 
 #[derive(Component, Debug)]
@@ -362,3 +588,57 @@ pub struct DeadLymphNodeConnection {
     pub tt: f32,
     pub started: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(id: u32) -> Entity {
+        Entity::from_raw(id)
+    }
+
+    #[test]
+    fn higher_priority_consumer_is_served_first() {
+        let targets = [
+            (entity(1), LymphNodePriority::Normal, 0),
+            (entity(2), LymphNodePriority::High, 2),
+            (entity(3), LymphNodePriority::Low, 0),
+        ];
+
+        assert_eq!(Some(entity(2)), LymphNode::pick_consumer(targets));
+    }
+
+    #[test]
+    fn full_consumers_are_skipped() {
+        let full = LymphNode::STOCK_CAPACITY;
+
+        let targets = [
+            (entity(1), LymphNodePriority::High, full),
+            (entity(2), LymphNodePriority::Low, full - 1),
+        ];
+
+        assert_eq!(Some(entity(2)), LymphNode::pick_consumer(targets));
+    }
+
+    #[test]
+    fn equally_important_consumers_take_turns() {
+        let targets = [
+            (entity(1), LymphNodePriority::Normal, 2),
+            (entity(2), LymphNodePriority::Normal, 1),
+        ];
+
+        assert_eq!(Some(entity(2)), LymphNode::pick_consumer(targets));
+    }
+
+    #[test]
+    fn nothing_is_served_when_every_consumer_is_full() {
+        let full = LymphNode::STOCK_CAPACITY;
+
+        let targets = [
+            (entity(1), LymphNodePriority::High, full),
+            (entity(2), LymphNodePriority::Low, full),
+        ];
+
+        assert_eq!(None, LymphNode::pick_consumer(targets));
+    }
+}
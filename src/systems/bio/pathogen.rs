@@ -1,27 +1,37 @@
 use bevy::prelude::*;
 use rand::prelude::SliceRandom;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use super::{Antigen, Body, Cell};
 
-#[derive(Component, Clone, Copy, Debug)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Pathogen {
     pub body: Body,
     pub antigen: Antigen,
     pub kind: PathogenKind,
+
+    /// Health the pathogen spawns with; depends on the level's difficulty
+    pub hp: f32,
 }
 
 impl Pathogen {
-    pub fn random() -> Self {
-        let mut rng = thread_rng();
+    pub const DEFAULT_HP: f32 = 1.0;
 
+    pub fn random(rng: &mut impl Rng) -> Self {
         Self {
-            body: Body::random(&mut rng),
-            antigen: Antigen::random(&mut rng),
+            body: Body::random(rng),
+            antigen: Antigen::random(rng),
             kind: PathogenKind::Virus,
+            hp: Self::DEFAULT_HP,
         }
     }
 
+    /// Returns health the pathogen spawns with, taking its kind into account.
+    pub fn max_hp(&self) -> f32 {
+        self.hp * self.kind.hp()
+    }
+
     pub fn color(a: u8) -> Color {
         let mut rng = rand::thread_rng();
 
@@ -46,15 +56,99 @@ impl Pathogen {
     pub fn spawn(
         &self,
         commands: &mut Commands,
-        assets: &AssetServer,
+        assets: Option<&AssetServer>,
         pos: Vec2,
         vel: Vec2,
-    ) {
-        Cell::Pathogen(self).spawn(commands, assets, pos, vel);
+    ) -> Entity {
+        Cell::Pathogen(self).spawn(commands, assets, pos, vel)
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PathogenKind {
     Virus,
+
+    /// Divides when left alone for a while (see `Division`)
+    Bacterium,
+
+    /// Large and slow, but takes a lot of hits
+    Parasite,
+
+    /// Fast, but fragile
+    Spore,
+}
+
+impl PathogenKind {
+    pub fn variants() -> impl Iterator<Item = Self> {
+        [Self::Virus, Self::Bacterium, Self::Parasite, Self::Spore].into_iter()
+    }
+
+    /// Returns health of pathogens of this kind, relative to the level's
+    /// `PathogenHp`.
+    pub fn hp(self) -> f32 {
+        match self {
+            Self::Virus => 1.0,
+            Self::Bacterium => 1.0,
+            Self::Parasite => 3.0,
+            Self::Spore => 0.4,
+        }
+    }
+
+    /// Returns how fast pathogens of this kind move, relative to the base
+    /// speed.
+    pub fn speed(self) -> f32 {
+        match self {
+            Self::Virus => 1.0,
+            Self::Bacterium => 0.8,
+            Self::Parasite => 0.5,
+            Self::Spore => 1.7,
+        }
+    }
+
+    /// Returns how large pathogens of this kind are, relative to
+    /// `Cell::SIZE`.
+    pub fn size(self) -> f32 {
+        match self {
+            Self::Virus => 1.0,
+            Self::Bacterium => 1.1,
+            Self::Parasite => 1.8,
+            Self::Spore => 0.7,
+        }
+    }
+
+    /// Returns how much damage pathogens of this kind deal, relative to the
+    /// base damage.
+    pub fn damage(self) -> f32 {
+        match self {
+            Self::Virus => 1.0,
+            Self::Bacterium => 0.8,
+            Self::Parasite => 1.5,
+            Self::Spore => 0.6,
+        }
+    }
+}
+
+impl Default for PathogenKind {
+    fn default() -> Self {
+        Self::Virus
+    }
+}
+
+/// Keeps track of how long a bacterium has been left alone; once it's been
+/// undisturbed for `Division::DURATION`, it divides in two.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Division {
+    pub tt: f32,
+}
+
+impl Division {
+    /// How long a bacterium has to be left alone before it divides (in
+    /// seconds)
+    pub const DURATION: f32 = 12.0;
+
+    /// Leukocytes closer than this (in pixels) keep bacteria from dividing
+    pub const DISTURBANCE_RADIUS: f32 = 150.0;
+
+    /// Bacteria stop dividing once there's this many of them
+    pub const MAX_BACTERIA: usize = 40;
 }
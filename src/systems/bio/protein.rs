@@ -1,14 +1,25 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Protein {
     Dumbbell,
     Star,
+    Arrow,
+    Spike,
+    Ring,
 }
 
 impl Protein {
     pub fn variants() -> impl Iterator<Item = Self> {
-        [Self::Dumbbell, Self::Star].into_iter()
+        [
+            Self::Dumbbell,
+            Self::Star,
+            Self::Arrow,
+            Self::Spike,
+            Self::Ring,
+        ]
+        .into_iter()
     }
 
     pub fn color() -> Color {
@@ -19,6 +30,9 @@ impl Protein {
         match self {
             Self::Dumbbell => "protein.dumbbell.png",
             Self::Star => "protein.star.png",
+            Self::Arrow => "protein.arrow.png",
+            Self::Spike => "protein.spike.png",
+            Self::Ring => "protein.ring.png",
         }
     }
 }
@@ -17,7 +17,9 @@ pub struct Wall {
 impl Wall {
     pub const SIZE: f32 = 0.35;
 
-    pub fn spawn(self, commands: &mut Commands, assets: &AssetServer) {
+    /// Spawns the wall; when `assets` is `None` (i.e. in headless mode), the
+    /// wall gets spawned without its sprite.
+    pub fn spawn(self, commands: &mut Commands, assets: Option<&AssetServer>) {
         let pos = Level::local_to_world(self.pos);
 
         let transform =
@@ -53,6 +55,12 @@ impl Wall {
             .insert(self)
             .insert(WallFadeIn::default());
 
+        let assets = if let Some(assets) = assets {
+            assets
+        } else {
+            return;
+        };
+
         // Spawn wall's sprite
         entity.with_children(|entity| {
             entity.spawn_bundle(SpriteBundle {
@@ -6,6 +6,7 @@ use itertools::Itertools;
 
 use super::bio::{LymphNode, Pathogen};
 use super::units::{Alignment, Unit};
+use crate::game::GameTime;
 use crate::pathfinding::NavigateUnit;
 
 // Max size of a combat group
@@ -32,6 +33,10 @@ pub struct EnemyAiEnabled(pub bool);
 #[derive(Default)]
 pub struct State {
     pub combat_groups: Vec<CombatGroup>,
+
+    /// When set, combat groups attack this position (in pixels) instead of
+    /// the closest player-owned lymph node
+    pub target: Option<Vec2>,
 }
 
 impl State {
@@ -108,7 +113,7 @@ impl CombatGroup {
 }
 
 fn attack_lymph_nodes(
-    time: Res<Time>,
+    time: Res<GameTime>,
     enabled: Res<EnemyAiEnabled>,
     state: Res<State>,
     lymph_nodes: Query<(&Transform, &Alignment), With<LymphNode>>,
@@ -126,7 +131,7 @@ fn attack_lymph_nodes(
 
     for combat_group in state.combat_groups.iter() {
         let combat_group_age =
-            time.seconds_since_startup() as f32 - combat_group.creation_time;
+            time.elapsed_seconds() - combat_group.creation_time;
 
         if combat_group_age < MAX_WAIT_TIME_BEFORE_ATTACKING
             && combat_group.len() < COMBAT_GROUP_SIZE
@@ -138,14 +143,17 @@ fn attack_lymph_nodes(
                 });
             }
         } else {
-            let closest_lymph_node = player_owned_lymph_nodes
-                .iter()
-                .map(|pos| (pos, pos.distance(combat_group.center)))
-                .min_by(|(_, lhs), (_, rhs)| {
-                    lhs.partial_cmp(rhs).unwrap_or(Ordering::Greater)
-                });
+            let target = state.target.or_else(|| {
+                player_owned_lymph_nodes
+                    .iter()
+                    .map(|pos| (pos, pos.distance(combat_group.center)))
+                    .min_by(|(_, lhs), (_, rhs)| {
+                        lhs.partial_cmp(rhs).unwrap_or(Ordering::Greater)
+                    })
+                    .map(|(&pos, _)| pos)
+            });
 
-            if let Some((&target, _)) = closest_lymph_node {
+            if let Some(target) = target {
                 for &entity in combat_group.units.iter() {
                     navigate_tx.send(NavigateUnit { entity, target });
                 }
@@ -172,14 +180,14 @@ fn track_combat_group_center(
 }
 
 fn track_unit_alignment(
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut state: ResMut<State>,
     query: Query<(Entity, &Unit, &Transform, &Alignment), Changed<Alignment>>,
 ) {
     for (entity, _, transform, alignment) in query.iter() {
         if alignment.is_enemy() {
             state.add_unit(
-                time.seconds_since_startup() as f32,
+                time.elapsed_seconds(),
                 entity,
                 transform.translation.truncate(),
             );
@@ -190,13 +198,13 @@ fn track_unit_alignment(
 }
 
 fn detect_new_units(
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut state: ResMut<State>,
     query: Query<(Entity, &Unit, &Transform, &Pathogen), Added<Pathogen>>,
 ) {
     for (entity, _, transform, _) in query.iter() {
         state.add_unit(
-            time.seconds_since_startup() as f32,
+            time.elapsed_seconds(),
             entity,
             transform.translation.truncate(),
         );
@@ -0,0 +1,231 @@
+//! Antigenic drift - pathogens produced by infected lymph nodes gradually
+//! adapt to the player's army.
+//!
+//! We keep track of which antigen binders the player's leukocytes carry and
+//! which strains (i.e. body + antigen combinations) keep dying; at the start of
+//! each wave, infected lymph nodes may then shift their pathogens one step
+//! towards the strain that's countered the least.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::bio::{
+    Antigen, AntigenBinder, Body, Leukocyte, LymphNode, LymphNodeProduct,
+    Pathogen,
+};
+use crate::compiling::RecompileEvent;
+use crate::level::Level;
+use crate::rng::GameRng;
+
+/// Chance of an infected lymph node shifting its pathogen at the start of a
+/// wave
+const DRIFT_CHANCE: f64 = 0.5;
+
+/// How much of the previous waves' deaths gets remembered when a new wave
+/// starts
+const DEATHS_DECAY: f32 = 0.5;
+
+pub fn initialize(app: &mut App) {
+    app.insert_resource(Evolution::default())
+        .add_system(track_binders)
+        .add_system(track_deaths)
+        .add_system(drift);
+}
+
+/// State of the drift; it's saved along with the game (see `game::save`),
+/// except for what gets tracked anew each frame.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Evolution {
+    /// Number of the player's leukocytes carrying given binder
+    #[serde(skip)]
+    binders: BTreeMap<Antigen, usize>,
+
+    /// Number of pathogens of given strain that've died recently
+    deaths: BTreeMap<Strain, f32>,
+
+    /// Pathogens currently alive, so that we know which strain has died once
+    /// a pathogen gets despawned; pathogens despawned while loading a game
+    /// aren't here, so they don't count as deaths
+    #[serde(skip)]
+    alive: BTreeMap<Entity, Strain>,
+
+    /// Strains that have emerged at the start of the current wave
+    emerged: BTreeSet<Strain>,
+
+    wave_idx: usize,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct Strain {
+    pub body: Body,
+    pub antigen: Antigen,
+}
+
+impl Evolution {
+    /// Returns whether given strain has emerged through drift at the start of
+    /// the current wave.
+    pub fn has_emerged(&self, strain: Strain) -> bool {
+        self.emerged.contains(&strain)
+    }
+
+    /// Returns how well the player's army copes with given strain - the lower,
+    /// the better for the pathogens.
+    fn score(&self, strain: Strain) -> f32 {
+        let binders = self.binders.values().sum::<usize>() as f32;
+        let deaths = self.deaths.values().sum::<f32>();
+
+        let binders = if binders > 0.0 {
+            self.binders
+                .get(&strain.antigen)
+                .copied()
+                .unwrap_or_default() as f32
+                / binders
+        } else {
+            0.0
+        };
+
+        let deaths = if deaths > 0.0 {
+            self.deaths.get(&strain).copied().unwrap_or_default() / deaths
+        } else {
+            0.0
+        };
+
+        binders + deaths
+    }
+
+    /// Returns the strain the player's army is least prepared for; ties are
+    /// broken randomly.
+    fn counter(&self, rng: &mut impl Rng) -> Strain {
+        let strains = Body::variants()
+            .cartesian_product(Antigen::variants())
+            .map(|(body, antigen)| Strain { body, antigen })
+            .collect_vec();
+
+        let best = strains
+            .iter()
+            .map(|&strain| self.score(strain))
+            .fold(f32::INFINITY, f32::min);
+
+        let candidates = strains
+            .into_iter()
+            .filter(|&strain| self.score(strain) <= best)
+            .collect_vec();
+
+        *candidates.choose(rng).unwrap()
+    }
+}
+
+impl Strain {
+    pub fn of(pathogen: &Pathogen) -> Self {
+        Self {
+            body: pathogen.body,
+            antigen: pathogen.antigen,
+        }
+    }
+
+    /// Moves the strain one step towards `target` - antigen first, since
+    /// that's what the player's binders care about.
+    fn drift(self, target: Self) -> Self {
+        if self.antigen != target.antigen {
+            Self {
+                antigen: target.antigen,
+                ..self
+            }
+        } else {
+            Self {
+                body: target.body,
+                ..self
+            }
+        }
+    }
+}
+
+fn track_binders(
+    mut evolution: ResMut<Evolution>,
+    leukocytes: Query<&Leukocyte>,
+) {
+    evolution.binders = leukocytes
+        .iter()
+        .map(|cell| {
+            let AntigenBinder(antigen) = cell.binder;
+            antigen
+        })
+        .counts()
+        .into_iter()
+        .collect();
+}
+
+fn track_deaths(
+    mut evolution: ResMut<Evolution>,
+    pathogens: Query<(Entity, &Pathogen), Added<Pathogen>>,
+    removed_pathogens: RemovedComponents<Pathogen>,
+) {
+    for (entity, pathogen) in pathogens.iter() {
+        evolution.alive.insert(entity, Strain::of(pathogen));
+    }
+
+    for entity in removed_pathogens.iter() {
+        if let Some(strain) = evolution.alive.remove(&entity) {
+            *evolution.deaths.entry(strain).or_default() += 1.0;
+        }
+    }
+}
+
+fn drift(
+    mut evolution: ResMut<Evolution>,
+    mut rng: ResMut<GameRng>,
+    level: Res<Level>,
+    mut lymph_nodes: Query<&mut LymphNode>,
+    mut recompile_event_tx: EventWriter<RecompileEvent>,
+) {
+    if level.wave_idx == evolution.wave_idx {
+        return;
+    }
+
+    evolution.wave_idx = level.wave_idx;
+    evolution.emerged.clear();
+
+    let counter = evolution.counter(&mut *rng);
+    let mut drifted = false;
+
+    for mut node in lymph_nodes.iter_mut() {
+        let pathogen =
+            if let Some(LymphNodeProduct::Pathogen(pathogen)) = &node.product {
+                *pathogen
+            } else {
+                continue;
+            };
+
+        let strain = Strain::of(&pathogen);
+
+        if strain == counter || !rng.gen_bool(DRIFT_CHANCE) {
+            continue;
+        }
+
+        let strain = strain.drift(counter);
+
+        node.product = Some(LymphNodeProduct::Pathogen(Pathogen {
+            body: strain.body,
+            antigen: strain.antigen,
+            ..pathogen
+        }));
+
+        evolution.emerged.insert(strain);
+        drifted = true;
+    }
+
+    for deaths in evolution.deaths.values_mut() {
+        *deaths *= DEATHS_DECAY;
+    }
+
+    if drifted {
+        recompile_event_tx.send(RecompileEvent);
+    }
+}
@@ -0,0 +1,136 @@
+//! Adaptive immunity - the more pathogens of a strain get killed by leukocytes
+//! with a matching antigen binder, the better the player's army gets at
+//! fighting that strain, for the rest of the run.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::bio::{Antigen, AntigenBinder};
+use super::evolution::Strain;
+
+/// How much faster lymph nodes produce leukocytes that match a remembered
+/// strain
+const PRODUCTION_SPEED_FACTOR: f32 = 1.25;
+
+/// How much more damage matching leukocytes deal against a remembered strain
+const DAMAGE_FACTOR: f32 = 1.25;
+
+pub fn initialize(app: &mut App) {
+    app.insert_resource(ImmuneMemory::default());
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ImmuneMemory {
+    /// Number of pathogens of given strain killed by leukocytes with a
+    /// matching binder
+    kills: BTreeMap<Strain, usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryBonus {
+    /// Leukocytes with a binder matching the strain get produced faster
+    FasterProduction,
+
+    /// Leukocytes with a binder matching the strain deal more damage to it
+    HigherDamage,
+
+    /// Killers with a binder matching the strain get compiled into memory
+    /// cells (see `Leukocyte::into_memory_cell()`)
+    MemoryCells,
+}
+
+impl ImmuneMemory {
+    /// Records a pathogen of given strain killed by a matching leukocyte;
+    /// returns the bonus this kill has unlocked, if any.
+    pub fn record_kill(&mut self, strain: Strain) -> Option<MemoryBonus> {
+        let kills = self.kills.entry(strain).or_default();
+
+        *kills += 1;
+
+        MemoryBonus::variants().find(|bonus| bonus.threshold() == *kills)
+    }
+
+    /// Returns remembered strains, along with the number of their kills.
+    pub fn strains(&self) -> impl Iterator<Item = (Strain, usize)> + '_ {
+        self.kills.iter().map(|(&strain, &kills)| (strain, kills))
+    }
+
+    pub fn has_bonus(&self, strain: Strain, bonus: MemoryBonus) -> bool {
+        self.kills.get(&strain).copied().unwrap_or_default()
+            >= bonus.threshold()
+    }
+
+    /// Returns how fast leukocytes with given binder get produced, relative
+    /// to the base production duration.
+    pub fn production_speed(
+        &self,
+        AntigenBinder(antigen): AntigenBinder,
+    ) -> f32 {
+        if self.has_antigen_bonus(antigen, MemoryBonus::FasterProduction) {
+            PRODUCTION_SPEED_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns how much more damage a leukocyte with given binder deals to
+    /// pathogens of given strain.
+    pub fn damage_factor(
+        &self,
+        AntigenBinder(antigen): AntigenBinder,
+        strain: Strain,
+    ) -> f32 {
+        if antigen == strain.antigen
+            && self.has_bonus(strain, MemoryBonus::HigherDamage)
+        {
+            DAMAGE_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns antigens for which killers get compiled into memory cells.
+    pub fn memory_cells(&self) -> BTreeSet<Antigen> {
+        self.kills
+            .keys()
+            .filter(|&&strain| self.has_bonus(strain, MemoryBonus::MemoryCells))
+            .map(|strain| strain.antigen)
+            .collect()
+    }
+
+    fn has_antigen_bonus(&self, antigen: Antigen, bonus: MemoryBonus) -> bool {
+        self.kills.keys().any(|&strain| {
+            strain.antigen == antigen && self.has_bonus(strain, bonus)
+        })
+    }
+}
+
+impl MemoryBonus {
+    pub fn variants() -> impl Iterator<Item = Self> {
+        [
+            Self::FasterProduction,
+            Self::HigherDamage,
+            Self::MemoryCells,
+        ]
+        .into_iter()
+    }
+
+    /// Returns number of kills required to unlock the bonus.
+    pub fn threshold(self) -> usize {
+        match self {
+            Self::FasterProduction => 10,
+            Self::HigherDamage => 25,
+            Self::MemoryCells => 50,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::FasterProduction => "matching leukocytes get produced faster",
+            Self::HigherDamage => "matching leukocytes deal more damage",
+            Self::MemoryCells => "matching killers become memory cells",
+        }
+    }
+}
@@ -36,6 +36,10 @@ pub struct InputState {
     pub hovered_entity: Option<Entity>,
     pub highlight_zoom: f32,
     pub highlight_zoom_dir: f32,
+
+    /// Whether the lymph node editor is opened - in that case right-clicks
+    /// place the node's rally point instead of moving the selected units
+    pub is_editing_lymph_node: bool,
 }
 
 impl Default for InputState {
@@ -46,6 +50,7 @@ impl Default for InputState {
             hovered_entity: Default::default(),
             highlight_zoom: 1.0,
             highlight_zoom_dir: 1.0,
+            is_editing_lymph_node: false,
         }
     }
 }
@@ -79,9 +84,10 @@ fn movement_command(
     mouse_pos: Res<MousePos>,
     selected_units: Res<SelectedUnits>,
     mouse: Res<Input<MouseButton>>,
+    input: Res<InputState>,
     mut navigate_tx: EventWriter<NavigateUnit>,
 ) {
-    if egui.ctx_mut().is_pointer_over_area() {
+    if egui.ctx_mut().is_pointer_over_area() || input.is_editing_lymph_node {
         return;
     }
 
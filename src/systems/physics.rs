@@ -1,17 +1,57 @@
 use bevy::prelude::*;
+use bevy_rapier2d::physics::SimulationToRenderTime;
 use bevy_rapier2d::prelude::*;
 
+use crate::game::{GameTime, TickGameTime};
+
 pub const PHYSICS_SCALE: f32 = 100.0;
 
+/// Length of a single physics step (in in-game seconds); it doesn't depend on
+/// the game's speed, since longer steps would let fast cells pass through walls
+const STEP_DURATION: f32 = 1.0 / 60.0;
+
 pub fn initialize(app: &mut App) {
+    initialize_simulation(app);
+
+    app.add_plugin(RapierRenderPlugin);
+}
+
+/// Registers physics without its debug renderer - used on its own in headless
+/// mode.
+pub fn initialize_simulation(app: &mut App) {
     app.add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(RapierRenderPlugin)
-        .add_startup_system(setup);
+        .add_startup_system(setup)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            sync_with_game_time.after(TickGameTime),
+        );
 }
 
-pub fn setup(mut rapier_config: ResMut<RapierConfiguration>) {
+pub fn setup(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut integration_params: ResMut<IntegrationParameters>,
+) {
     rapier_config.gravity = [0.0, 0.0].into();
     rapier_config.scale = PHYSICS_SCALE;
+
+    // In this mode rapier runs as many fixed-length steps per frame as fit in
+    // the time that has passed (see `sync_with_game_time()`)
+    rapier_config.timestep_mode = TimestepMode::InterpolatedTimestep;
+    integration_params.dt = STEP_DURATION;
+}
+
+/// Makes physics follow the game's clock, so that pausing or speeding up the
+/// game affects physics in the same way it affects everything else - e.g. at
+/// 4x physics runs four steps per frame, and none while the game is paused.
+fn sync_with_game_time(
+    real_time: Res<Time>,
+    game_time: Res<GameTime>,
+    mut sim_time: ResMut<SimulationToRenderTime>,
+) {
+    // Rapier accumulates the real time that has passed on its own (and then
+    // steps once per each `STEP_DURATION` accumulated), so we have to swap it
+    // for the game's time
+    sim_time.diff += game_time.delta_seconds() - real_time.delta_seconds();
 }
 
 pub fn pixel_to_world(vec: Vec2) -> Vector<Real> {
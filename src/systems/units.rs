@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 const MAX_SPEED: f32 = 5.0;
 const FORCE_FACTOR: f32 = 1.0;
@@ -10,19 +11,34 @@ const MOVEMENT_SQUEEZE_FACTOR: f32 = 0.6;
 const LYMPH_NODE_MAX_HEALTH: f32 = 10.0;
 const MAX_HEALTH: f32 = 1.0;
 const BASE_DAMAGE: f32 = 0.25; // By default a cell can take 4 hits
-const REGEN_RATE: f32 = 0.1; // 0.1 point per second
+pub const REGEN_RATE: f32 = 0.1; // 0.1 point per second
 
 pub mod animate;
 pub mod combat;
 pub mod health_regen;
+pub mod lifespan;
 pub mod movement;
 
-#[derive(Debug, Component)]
+#[derive(Clone, Debug, Component, Serialize, Deserialize)]
 pub struct Unit {
     // TODO(dzejkop): Should be enum, target can be unit, etc.
     pub target: Option<Vec2>,
     pub path: Vec<Vec2>,
     pub step: usize,
+
+    /// For how long (in seconds) the unit remains immobilised by a cager
+    #[serde(default)]
+    pub caged_for: f32,
+
+    /// Whether there's a helper nearby (see `combat::helpers()`); it's
+    /// recomputed each frame, so it doesn't get saved
+    #[serde(skip)]
+    pub is_helped: bool,
+
+    /// For how long (in seconds) the unit remains alive; `None` stands for
+    /// forever
+    #[serde(default)]
+    pub lifespan: Option<f32>,
 }
 
 #[derive(Component)]
@@ -64,7 +80,18 @@ pub enum DeathBehavior {
     SwitchSides,
 }
 
-#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Component,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
 pub enum Alignment {
     Unaligned,
     Player,
@@ -95,6 +122,9 @@ impl Default for Unit {
             target: Default::default(),
             path: Default::default(),
             step: Default::default(),
+            caged_for: Default::default(),
+            is_helped: Default::default(),
+            lifespan: Default::default(),
         }
     }
 }
@@ -117,8 +147,17 @@ impl Unit {
 }
 
 pub fn initialize(app: &mut App) {
+    initialize_simulation(app);
+
+    app.add_system(animate::system);
+}
+
+/// Registers units' behavior without their animations - used on its own in
+/// headless mode.
+pub fn initialize_simulation(app: &mut App) {
     app.add_system(movement::system)
-        .add_system(animate::system)
         .add_system(combat::system)
-        .add_system(health_regen::system);
+        .add_system(combat::helpers)
+        .add_system(health_regen::system)
+        .add_system(lifespan::system);
 }
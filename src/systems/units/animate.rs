@@ -23,10 +23,10 @@ pub fn system(
         };
 
         for child in children.iter() {
-            if let Ok((_, mut transform)) = child_sprites.get_mut(*child) {
-                let health_ratio = health.health / health.max_health;
+            if let Ok((body, mut transform)) = child_sprites.get_mut(*child) {
+                let scale = body.scale * health.health / health.max_health;
                 if velocity.linvel.magnitude() < 0.1 {
-                    transform.scale = Vec3::ONE * health_ratio;
+                    transform.scale = Vec3::ONE * scale;
                 } else {
                     let stretch_x = remap(
                         velocity.linvel.magnitude(),
@@ -45,7 +45,7 @@ pub fn system(
 
                     transform.rotation = Quat::from_rotation_z(angle);
                     transform.scale =
-                        Vec3::new(stretch_x, stretch_y, 1.0) * health_ratio;
+                        Vec3::new(stretch_x, stretch_y, 1.0) * scale;
                 }
                 break;
             }
@@ -1,11 +1,33 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use super::{Alignment, DeathBehavior, Health, BASE_DAMAGE};
-use crate::systems::bio::{Antigen, AntigenBinder};
+use super::{Alignment, DeathBehavior, Health, Unit, BASE_DAMAGE};
+use crate::compiling::RecompileEvent;
+use crate::systems::bio::{
+    Antigen, AntigenBinder, Leukocyte, LeukocyteKind, Pathogen,
+};
+use crate::systems::evolution::Strain;
+use crate::systems::immunity::{ImmuneMemory, MemoryBonus};
 
 const MATCHING_WEAPON_BONUS: f32 = 0.3;
 
+/// For how long a pathogen remains immobilised after touching a cager (in
+/// seconds)
+const CAGE_DURATION: f32 = 4.0;
+
+/// Pathogens with health at or below this fraction get engulfed by
+/// macrophages
+const ENGULF_THRESHOLD: f32 = 0.5;
+
+/// Helpers are poor fighters on their own
+const HELPER_DAMAGE_FACTOR: f32 = 0.5;
+
+/// Leukocytes with a helper nearby deal more damage
+const HELPED_DAMAGE_FACTOR: f32 = 1.5;
+
+/// Distance (in pixels) within which helpers affect other leukocytes
+const HELPER_RADIUS: f32 = 120.0;
+
 #[derive(Clone, Copy, Component)]
 pub enum Weapon {
     Antigen(Antigen),
@@ -13,10 +35,51 @@ pub enum Weapon {
     None,
 }
 
+type Units<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Alignment,
+        &'static Weapon,
+        &'static DeathBehavior,
+        &'static mut Health,
+        Option<&'static Leukocyte>,
+        Option<&'static Pathogen>,
+        Option<&'static mut Unit>,
+    ),
+>;
+
+/// Unit taking part in a fight
+struct Fighter {
+    weapon: Weapon,
+
+    /// Damage the unit deals with its weapon, before any bonuses
+    damage: f32,
+
+    alignment: Alignment,
+    kind: Option<LeukocyteKind>,
+
+    /// Strain of the pathogen, if the unit is one
+    strain: Option<Strain>,
+
+    is_helped: bool,
+    health: f32,
+    max_health: f32,
+}
+
+/// What happens to a unit touched by an enemy
+enum Attack {
+    Damage(f32),
+    Cage,
+    Engulf,
+}
+
 pub fn system(
     mut commands: Commands,
     mut contact_events: EventReader<ContactEvent>,
-    mut units: Query<(&mut Alignment, &Weapon, &DeathBehavior, &mut Health)>,
+    mut memory: ResMut<ImmuneMemory>,
+    mut recompile_event_tx: EventWriter<RecompileEvent>,
+    mut units: Units,
 ) {
     for contact_event in contact_events.iter() {
         match contact_event {
@@ -24,32 +87,46 @@ pub fn system(
                 let left = left.entity();
                 let right = right.entity();
 
-                match (
-                    weapon_and_alignment_of(left, &units),
-                    weapon_and_alignment_of(right, &units),
-                ) {
-                    (
-                        Some((left_weapon, left_alignment)),
-                        Some((right_weapon, right_alignment)),
-                    ) => {
-                        if left_alignment != right_alignment {
-                            let damage_against_left =
-                                calculate_damage(right_weapon, left_weapon);
-                            let damage_against_right =
-                                calculate_damage(left_weapon, right_weapon);
-
-                            deal_damage(
+                match (fighter(left, &units), fighter(right, &units)) {
+                    (Some(left_fighter), Some(right_fighter)) => {
+                        if left_fighter.alignment != right_fighter.alignment {
+                            let attack_against_left =
+                                attack(&right_fighter, &left_fighter, &memory);
+                            let attack_against_right =
+                                attack(&left_fighter, &right_fighter, &memory);
+
+                            let left_died = suffer(
                                 left,
-                                damage_against_left,
+                                attack_against_left,
                                 &mut units,
                                 &mut commands,
                             );
-                            deal_damage(
+                            let right_died = suffer(
                                 right,
-                                damage_against_right,
+                                attack_against_right,
                                 &mut units,
                                 &mut commands,
                             );
+
+                            let kills = [
+                                (left_died, &right_fighter, &left_fighter),
+                                (right_died, &left_fighter, &right_fighter),
+                            ];
+
+                            for (died, killer, victim) in kills {
+                                if !died {
+                                    continue;
+                                }
+
+                                let bonus =
+                                    remember(killer, victim, &mut memory);
+
+                                // Memory cells are produced by the compiler,
+                                // so it has to know about them
+                                if bonus == Some(MemoryBonus::MemoryCells) {
+                                    recompile_event_tx.send(RecompileEvent);
+                                }
+                            }
                         }
                     }
                     _ => {}
@@ -60,61 +137,200 @@ pub fn system(
     }
 }
 
-fn weapon_and_alignment_of(
-    entity: Entity,
-    units: &Query<(&mut Alignment, &Weapon, &DeathBehavior, &mut Health)>,
-) -> Option<(Weapon, Alignment)> {
+/// Marks leukocytes that have got a helper nearby.
+pub fn helpers(mut cells: Query<(Entity, &Transform, &Leukocyte, &mut Unit)>) {
+    let helpers: Vec<_> = cells
+        .iter()
+        .filter(|(_, _, cell, _)| cell.kind == LeukocyteKind::Helper)
+        .map(|(entity, transform, _, _)| {
+            (entity, transform.translation.truncate())
+        })
+        .collect();
+
+    for (entity, transform, _, mut unit) in cells.iter_mut() {
+        let pos = transform.translation.truncate();
+
+        let is_helped = helpers.iter().any(|&(helper, helper_pos)| {
+            helper != entity && helper_pos.distance(pos) <= HELPER_RADIUS
+        });
+
+        if unit.is_helped != is_helped {
+            unit.is_helped = is_helped;
+        }
+    }
+}
+
+fn fighter(entity: Entity, units: &Units) -> Option<Fighter> {
     units
         .get(entity)
-        .map(|(alignment, weapon, _, _)| (*weapon, *alignment))
+        .map(
+            |(alignment, weapon, _, health, cell, pathogen, unit)| Fighter {
+                weapon: *weapon,
+                damage: damage(cell, pathogen),
+                alignment: *alignment,
+                kind: cell.map(|cell| cell.kind),
+                strain: pathogen.map(Strain::of),
+                is_helped: unit.map_or(false, |unit| unit.is_helped),
+                health: health.health,
+                max_health: health.max_health,
+            },
+        )
         .ok()
 }
 
+fn attack(
+    attacker: &Fighter,
+    victim: &Fighter,
+    memory: &ImmuneMemory,
+) -> Attack {
+    let is_pathogen = matches!(victim.weapon, Weapon::Antigen(_));
+
+    match attacker.kind {
+        Some(LeukocyteKind::Cager) if is_pathogen => {
+            return Attack::Cage;
+        }
+
+        Some(LeukocyteKind::Macrophage)
+            if is_pathogen
+                && victim.health <= victim.max_health * ENGULF_THRESHOLD =>
+        {
+            return Attack::Engulf;
+        }
+
+        _ => (),
+    }
+
+    let mut damage =
+        calculate_damage(attacker.damage, attacker.weapon, victim.weapon);
+
+    if attacker.kind == Some(LeukocyteKind::Helper) {
+        damage *= HELPER_DAMAGE_FACTOR;
+    }
+
+    if attacker.is_helped {
+        damage *= HELPED_DAMAGE_FACTOR;
+    }
+
+    if let (Weapon::AntigenBinder(binder), Some(strain)) =
+        (attacker.weapon, victim.strain)
+    {
+        damage *= memory.damage_factor(binder, strain);
+    }
+
+    Attack::Damage(damage)
+}
+
+/// Applies the attack to given unit, returning whether the unit has died.
+fn suffer(
+    entity: Entity,
+    attack: Attack,
+    units: &mut Units,
+    commands: &mut Commands,
+) -> bool {
+    match attack {
+        Attack::Damage(damage) => deal_damage(entity, damage, units, commands),
+
+        Attack::Cage => {
+            if let Ok((_, _, _, _, _, _, Some(mut unit))) =
+                units.get_mut(entity)
+            {
+                unit.caged_for = CAGE_DURATION;
+            }
+
+            false
+        }
+
+        Attack::Engulf => deal_damage(entity, f32::INFINITY, units, commands),
+    }
+}
+
+/// Deals damage to given unit, returning whether the unit has died (units
+/// that have already died earlier during the same frame don't count).
 fn deal_damage(
     entity: Entity,
     damage: f32,
-    units: &mut Query<(&mut Alignment, &Weapon, &DeathBehavior, &mut Health)>,
+    units: &mut Units,
     commands: &mut Commands,
-) {
-    if let Ok((mut alignment, _, death_behavior, mut health)) =
-        units.get_mut(entity)
-    {
-        health.health -= damage;
+) -> bool {
+    let (mut alignment, _, death_behavior, mut health, _, _, _) =
+        if let Ok(unit) = units.get_mut(entity) {
+            unit
+        } else {
+            return false;
+        };
 
-        if health.health <= 0.0 {
-            match death_behavior {
-                DeathBehavior::Despawn => {
-                    commands.entity(entity).despawn_recursive()
-                }
-                DeathBehavior::SwitchSides => {
-                    alignment.flip();
-                    health.reset();
-                }
-            }
+    if health.health <= 0.0 {
+        return false;
+    }
+
+    health.health -= damage;
+
+    if health.health > 0.0 {
+        return false;
+    }
+
+    match death_behavior {
+        DeathBehavior::Despawn => commands.entity(entity).despawn_recursive(),
+        DeathBehavior::SwitchSides => {
+            alignment.flip();
+            health.reset();
         }
     }
+
+    true
+}
+
+/// Remembers the victim's strain, provided it's a pathogen killed by a
+/// leukocyte with a matching binder; returns the bonus this has unlocked, if
+/// any.
+fn remember(
+    killer: &Fighter,
+    victim: &Fighter,
+    memory: &mut ImmuneMemory,
+) -> Option<MemoryBonus> {
+    match (killer.weapon, victim.strain) {
+        (Weapon::AntigenBinder(AntigenBinder(binder)), Some(strain))
+            if binder == strain.antigen =>
+        {
+            memory.record_kill(strain)
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns damage dealt by given unit - leukocytes and pathogens deal damage
+/// according to their stats, while lymph nodes deal the base damage.
+fn damage(cell: Option<&Leukocyte>, pathogen: Option<&Pathogen>) -> f32 {
+    if let Some(cell) = cell {
+        BASE_DAMAGE * cell.props.damage
+    } else if let Some(pathogen) = pathogen {
+        BASE_DAMAGE * pathogen.kind.damage()
+    } else {
+        BASE_DAMAGE
+    }
 }
 
-fn calculate_damage(left: Weapon, right: Weapon) -> f32 {
+fn calculate_damage(damage: f32, left: Weapon, right: Weapon) -> f32 {
     match (left, right) {
         (
             Weapon::Antigen(left),
             Weapon::AntigenBinder(AntigenBinder(right)),
         ) if left == right => {
             // Matching pathogen's antigen deals reduced damage against correct binder
-            BASE_DAMAGE * (1.0 - MATCHING_WEAPON_BONUS)
+            damage * (1.0 - MATCHING_WEAPON_BONUS)
         }
         (
             Weapon::AntigenBinder(AntigenBinder(left)),
             Weapon::Antigen(right),
         ) if left == right => {
             // Matching leukocyte's antigen binder deals increased damage against correct antigen
-            BASE_DAMAGE * (1.0 + MATCHING_WEAPON_BONUS)
+            damage * (1.0 + MATCHING_WEAPON_BONUS)
         }
         (Weapon::None, _) => {
             // No weapon (lymph node) deals reduced damage
-            BASE_DAMAGE * (1.0 - MATCHING_WEAPON_BONUS)
+            damage * (1.0 - MATCHING_WEAPON_BONUS)
         }
-        _ => BASE_DAMAGE,
+        _ => damage,
     }
 }
@@ -1,8 +1,9 @@
 use bevy::prelude::*;
 
 use super::Health;
+use crate::game::GameTime;
 
-pub fn system(time: Res<Time>, mut units: Query<&mut Health>) {
+pub fn system(time: Res<GameTime>, mut units: Query<&mut Health>) {
     for mut health in units.iter_mut() {
         health.health = (health.health
             + time.delta_seconds() * health.regen_rate)
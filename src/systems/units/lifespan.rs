@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+
+use super::Unit;
+use crate::game::GameTime;
+
+pub fn system(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    mut units: Query<(Entity, &mut Unit)>,
+) {
+    for (entity, mut unit) in units.iter_mut() {
+        if let Some(lifespan) = &mut unit.lifespan {
+            *lifespan -= time.delta_seconds();
+
+            if *lifespan <= 0.0 {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -2,21 +2,45 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use super::{Unit, FORCE_FACTOR, MAX_SPEED, STOPPING_FORCE_FACTOR};
+use crate::game::GameTime;
+use crate::systems::bio::{Leukocyte, Pathogen};
 use crate::systems::physics::pixel_to_world;
 
 pub fn system(
+    time: Res<GameTime>,
     mut units: Query<(
         &RigidBodyVelocityComponent,
         &mut RigidBodyForcesComponent,
         &mut Unit,
         &Transform,
+        Option<&Leukocyte>,
+        Option<&Pathogen>,
     )>,
 ) {
-    for (velocity, mut forces, mut unit, transform) in units.iter_mut() {
+    for (velocity, mut forces, mut unit, transform, leukocyte, pathogen) in
+        units.iter_mut()
+    {
+        if unit.caged_for > 0.0 {
+            unit.caged_for -= time.delta_seconds();
+            maintain_position(velocity, &mut forces);
+            continue;
+        }
+
         if let Some(target) = unit.target {
+            let speed = if let Some(cell) = leukocyte {
+                cell.props.speed
+            } else if let Some(cell) = pathogen {
+                cell.kind.speed()
+            } else {
+                1.0
+            };
+
+            let max_speed = MAX_SPEED * speed;
+
             move_towards_target(
                 transform,
                 target,
+                max_speed,
                 &mut unit,
                 velocity,
                 &mut forces,
@@ -30,6 +54,7 @@ pub fn system(
 fn move_towards_target(
     transform: &Transform,
     target: Vec2,
+    max_speed: f32,
     unit: &mut Unit,
     velocity: &RigidBodyVelocityComponent,
     forces: &mut RigidBodyForcesComponent,
@@ -43,9 +68,9 @@ fn move_towards_target(
     let force_direction = pixel_to_world(force_direction);
 
     let desired_linvel: Vector<Real> = if force_direction.magnitude() < 1.0 {
-        force_direction * MAX_SPEED
+        force_direction * max_speed
     } else {
-        force_direction.normalize() * MAX_SPEED
+        force_direction.normalize() * max_speed
     };
 
     let current_linvel = velocity.linvel;
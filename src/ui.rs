@@ -1,3 +1,7 @@
+mod game_speed;
+mod immune_memory;
+mod level_editor;
+mod level_select;
 mod lymph_node_editor;
 mod poll;
 mod radio_image_button;
@@ -12,9 +16,12 @@ pub(self) use self::lymph_node_editor::*;
 pub(self) use self::poll::*;
 pub(self) use self::radio_image_button::*;
 pub(self) use self::textures::*;
-use crate::compiling::RecompileEvent;
+use crate::blueprints::Blueprints;
+use crate::compiling::{Recipes, RecompileEvent};
 use crate::systems::bio::*;
-use crate::systems::input::{MousePos, Selector};
+use crate::systems::immunity::ImmuneMemory;
+use crate::systems::input::{InputState, MousePos, Selector};
+use crate::systems::units::Alignment;
 
 pub struct UiPlugin;
 
@@ -26,6 +33,10 @@ impl Plugin for UiPlugin {
             .add_event::<UiEvent>()
             .add_system(process_events)
             .add_system(process_lymph_node_editor)
+            .add_system(game_speed::system)
+            .add_system(immune_memory::system)
+            .add_system(level_select::system)
+            .add_system(level_editor::system)
             .add_system(tutorial::system);
     }
 }
@@ -37,6 +48,7 @@ pub enum UiEvent {
 #[derive(Default)]
 struct UiState {
     lymph_node_editor: Option<UiLymphNodeEditor>,
+    level_editor: Option<level_editor::UiLevelEditor>,
 }
 
 fn process_events(
@@ -45,6 +57,9 @@ fn process_events(
     mut textures: ResMut<UiTextures>,
     mut events: EventReader<UiEvent>,
     keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mouse_pos: Res<MousePos>,
+    mut input: ResMut<InputState>,
     mut state: ResMut<UiState>,
     mut selectors: Query<&mut Selector>,
     lymph_nodes: Query<&Children, With<LymphNode>>,
@@ -77,15 +92,33 @@ fn process_events(
             editor.on_escape_pressed();
         }
     }
+
+    if mouse.just_pressed(MouseButton::Right)
+        && !egui.ctx_mut().is_pointer_over_area()
+    {
+        if let Some(editor) = &mut state.lymph_node_editor {
+            let node = input
+                .hovered_entity
+                .filter(|&entity| lymph_nodes.get(entity).is_ok());
+
+            editor.on_right_clicked(mouse_pos.0, node);
+        }
+    }
+
+    input.is_editing_lymph_node = state.lymph_node_editor.is_some();
 }
 
 fn process_lymph_node_editor(
     lines: ResMut<DebugLines>,
     egui: ResMut<EguiContext>,
     textures: Res<UiTextures>,
+    recipes: Res<Recipes>,
+    memory: Res<ImmuneMemory>,
+    mut blueprints: ResMut<Blueprints>,
     mouse_pos: Res<MousePos>,
     mut state: ResMut<UiState>,
     mut lymph_nodes: Query<(&mut LymphNode, &Transform, &Children, Entity)>,
+    alignments: Query<&Alignment, With<LymphNode>>,
     recompile_event_tx: EventWriter<RecompileEvent>,
     mut selectors: Query<&mut Selector>,
     mut events: EventWriter<UiEvent>,
@@ -95,8 +128,12 @@ fn process_lymph_node_editor(
             lines,
             egui,
             &textures,
+            &recipes,
+            &memory,
+            &mut blueprints,
             mouse_pos.0,
             &mut lymph_nodes,
+            &alignments,
             recompile_event_tx,
         ) {
             Poll::Pending => {
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Align2};
+use bevy_egui::EguiContext;
+
+use crate::game::{GameSpeed, GameState, GameTime};
+
+pub fn system(
+    keys: Res<Input<KeyCode>>,
+    state: Res<GameState>,
+    mut egui: ResMut<EguiContext>,
+    mut time: ResMut<GameTime>,
+    mut unpaused_speed: Local<GameSpeed>,
+) {
+    if state.tutorial {
+        return;
+    }
+
+    if !time.is_paused() {
+        *unpaused_speed = time.speed();
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.set_speed(*unpaused_speed);
+        } else {
+            time.set_speed(GameSpeed::Paused);
+        }
+    }
+
+    egui::Window::new("Speed")
+        .anchor(Align2::RIGHT_TOP, (-10.0, 10.0))
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(false)
+        .show(egui.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for speed in GameSpeed::variants() {
+                    if ui
+                        .selectable_label(time.speed() == speed, speed.label())
+                        .clicked()
+                    {
+                        time.set_speed(speed);
+                    }
+                }
+            });
+        });
+}
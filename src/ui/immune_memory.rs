@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Align2};
+use bevy_egui::EguiContext;
+
+use crate::game::GameState;
+use crate::systems::immunity::{ImmuneMemory, MemoryBonus};
+
+pub fn system(
+    state: Res<GameState>,
+    memory: Res<ImmuneMemory>,
+    mut egui: ResMut<EguiContext>,
+) {
+    if state.tutorial {
+        return;
+    }
+
+    egui::Window::new("Immune memory")
+        .anchor(Align2::RIGHT_TOP, (-10.0, 50.0))
+        .resizable(false)
+        .title_bar(false)
+        .show(egui.ctx_mut(), |ui| {
+            // Windows can't start collapsed, so we use a collapsing header
+            // instead of the title bar
+            egui::CollapsingHeader::new("Immune memory")
+                .default_open(false)
+                .show(ui, |ui| {
+                    let mut strains = memory.strains().peekable();
+
+                    if strains.peek().is_none() {
+                        ui.label("No pathogens remembered yet.");
+                        return;
+                    }
+
+                    for (strain, kills) in strains {
+                        ui.strong(format!(
+                            "{:?} {:?} - {} killed",
+                            strain.body, strain.antigen, kills
+                        ));
+
+                        for bonus in MemoryBonus::variants() {
+                            if memory.has_bonus(strain, bonus) {
+                                ui.label(format!("- {}", bonus.description()));
+                            } else {
+                                ui.add(
+                                    egui::Label::new(format!(
+                                        "- {} (at {} kills)",
+                                        bonus.description(),
+                                        bonus.threshold()
+                                    ))
+                                    .weak(),
+                                );
+
+                                break;
+                            }
+                        }
+                    }
+                });
+        });
+}
@@ -0,0 +1,473 @@
+//! Editor for the hand-authored levels - it edits just the level's layout
+//! (see `LevelLayout`), which then gets saved into a `levels/*.toml` file.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Align2};
+use bevy_egui::EguiContext;
+use bevy_prototype_debug_lines::DebugLines;
+
+use super::UiState;
+use crate::game::StartGameEvent;
+use crate::level::{
+    Level, LevelChamber, LevelCorridor, LevelLayout, LevelLayoutNode,
+    LevelPoint,
+};
+use crate::rng::GameRng;
+use crate::systems::bio::LymphNodeResource;
+use crate::systems::input::MousePos;
+use crate::systems::units::Alignment;
+use crate::theme;
+use crate::utils::DebugLinesExt;
+
+const DEFAULT_PATH: &str = "levels/custom.toml";
+const MIN_CHAMBER_RADIUS: i32 = 2;
+const MAX_CHAMBER_RADIUS: i32 = 40;
+
+pub struct UiLevelEditor {
+    layout: LevelLayout,
+    path: String,
+    tool: UiLevelEditorTool,
+    alignment: Alignment,
+    resource: Option<LymphNodeResource>,
+
+    /// Field the mouse has been pressed at, when placing a chamber or a
+    /// corridor
+    drag_start: Option<LevelPoint>,
+
+    status: Option<Result<String, String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UiLevelEditorTool {
+    Chamber,
+    Corridor,
+    Wall,
+    LymphNode,
+}
+
+impl UiLevelEditorTool {
+    fn variants() -> impl Iterator<Item = Self> {
+        [Self::Chamber, Self::Corridor, Self::Wall, Self::LymphNode].into_iter()
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Chamber => "Chamber",
+            Self::Corridor => "Corridor",
+            Self::Wall => "Wall",
+            Self::LymphNode => "Lymph node",
+        }
+    }
+}
+
+impl UiLevelEditor {
+    pub fn new() -> Self {
+        Self {
+            layout: Default::default(),
+            path: DEFAULT_PATH.into(),
+            tool: UiLevelEditorTool::Chamber,
+            alignment: Alignment::Player,
+            resource: None,
+            drag_start: None,
+            status: None,
+        }
+    }
+
+    fn on_mouse(&mut self, mouse: &Input<MouseButton>, pos: LevelPoint) {
+        match self.tool {
+            UiLevelEditorTool::Chamber | UiLevelEditorTool::Corridor => {
+                if mouse.just_pressed(MouseButton::Left) {
+                    self.drag_start = Some(pos);
+                }
+
+                if !mouse.just_released(MouseButton::Left) {
+                    return;
+                }
+
+                if let Some(start) = self.drag_start.take() {
+                    if self.tool == UiLevelEditorTool::Chamber {
+                        self.layout.chambers.push(chamber(start, pos));
+                    } else if start != pos {
+                        self.layout.corridors.push(corridor(start, pos));
+                    }
+                }
+            }
+
+            UiLevelEditorTool::Wall => {
+                if mouse.just_pressed(MouseButton::Left) {
+                    self.toggle_wall(pos);
+                }
+            }
+
+            UiLevelEditorTool::LymphNode => {
+                if !mouse.just_pressed(MouseButton::Left) {
+                    return;
+                }
+
+                if let Some(idx) = self.layout.lymph_node_at(pos) {
+                    self.layout.lymph_nodes.remove(idx);
+                } else {
+                    self.layout.lymph_nodes.push(LevelLayoutNode {
+                        pos,
+                        alignment: self.alignment,
+                        resource: self.resource,
+                    });
+                }
+            }
+        }
+    }
+
+    fn toggle_wall(&mut self, pos: LevelPoint) {
+        let layout = &mut self.layout;
+
+        if let Some(idx) = layout.walls.iter().position(|&wall| wall == pos) {
+            layout.walls.remove(idx);
+        } else if let Some(idx) =
+            layout.holes.iter().position(|&hole| hole == pos)
+        {
+            layout.holes.remove(idx);
+        } else if layout.wall_cells().contains(&(pos.x, pos.y)) {
+            layout.holes.push(pos);
+        } else {
+            layout.walls.push(pos);
+        }
+    }
+
+    fn draw(
+        &self,
+        lines: &mut DebugLines,
+        mouse: &Input<MouseButton>,
+        cursor: Option<LevelPoint>,
+    ) {
+        let half = Vec2::ONE * Level::FIELD_SIZE / 2.0;
+
+        let mut field = |pos: LevelPoint, color: Color| {
+            let pos = Level::local_to_world(pos);
+
+            lines.square(pos - half, pos + half).color(color).draw();
+        };
+
+        for (x, y) in self.layout.wall_cells() {
+            field(LevelPoint::new(x, y), Color::GRAY);
+        }
+
+        for node in &self.layout.lymph_nodes {
+            let color = match node.alignment {
+                Alignment::Unaligned => Color::WHITE,
+                Alignment::Player => Color::GREEN,
+                Alignment::Enemy => Color::RED,
+            };
+
+            field(node.pos, color);
+        }
+
+        if let Some(cursor) = cursor {
+            field(cursor, Color::YELLOW);
+
+            if let (Some(start), true) =
+                (self.drag_start, mouse.pressed(MouseButton::Left))
+            {
+                match self.tool {
+                    UiLevelEditorTool::Chamber => {
+                        draw_chamber(lines, &chamber(start, cursor));
+                    }
+
+                    UiLevelEditorTool::Corridor => {
+                        draw_corridor(lines, &corridor(start, cursor));
+                    }
+
+                    _ => (),
+                }
+            }
+        }
+
+        for chamber in &self.layout.chambers {
+            draw_chamber(lines, chamber);
+        }
+
+        for corridor in &self.layout.corridors {
+            draw_corridor(lines, corridor);
+        }
+    }
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        rng: &mut GameRng,
+        start_tx: &mut EventWriter<StartGameEvent>,
+    ) -> bool {
+        ui.horizontal(|ui| {
+            for tool in UiLevelEditorTool::variants() {
+                if ui
+                    .selectable_label(self.tool == tool, tool.label())
+                    .clicked()
+                {
+                    self.tool = tool;
+                    self.drag_start = None;
+                }
+            }
+        });
+
+        if self.tool == UiLevelEditorTool::LymphNode {
+            ui.horizontal(|ui| {
+                for alignment in
+                    [Alignment::Player, Alignment::Enemy, Alignment::Unaligned]
+                {
+                    ui.radio_value(
+                        &mut self.alignment,
+                        alignment,
+                        format!("{:?}", alignment),
+                    );
+                }
+            });
+
+            egui::ComboBox::from_label("Resource")
+                .selected_text(resource_label(self.resource))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.resource,
+                        None,
+                        resource_label(None),
+                    );
+
+                    for resource in LymphNodeResource::variants() {
+                        ui.selectable_value(
+                            &mut self.resource,
+                            Some(resource),
+                            resource_label(Some(resource)),
+                        );
+                    }
+                });
+        }
+
+        ui.separator();
+
+        egui::CollapsingHeader::new("Chambers").show(ui, |ui| {
+            let mut removed = None;
+
+            for (idx, chamber) in self.layout.chambers.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut chamber.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut chamber.y).prefix("y: "));
+
+                    ui.add(
+                        egui::DragValue::new(&mut chamber.r)
+                            .prefix("r: ")
+                            .clamp_range(
+                                MIN_CHAMBER_RADIUS..=MAX_CHAMBER_RADIUS,
+                            ),
+                    );
+
+                    if ui.button("Remove").clicked() {
+                        removed = Some(idx);
+                    }
+                });
+            }
+
+            if let Some(idx) = removed {
+                self.layout.chambers.remove(idx);
+            }
+        });
+
+        egui::CollapsingHeader::new("Corridors").show(ui, |ui| {
+            let mut removed = None;
+
+            for (idx, corridor) in self.layout.corridors.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{}x{} -> {}x{}",
+                        corridor.x1, corridor.y1, corridor.x2, corridor.y2
+                    ));
+
+                    if ui.button("Remove").clicked() {
+                        removed = Some(idx);
+                    }
+                });
+            }
+
+            if let Some(idx) = removed {
+                self.layout.corridors.remove(idx);
+            }
+        });
+
+        ui.label(format!(
+            "Walls added: {}, removed: {}",
+            self.layout.walls.len(),
+            self.layout.holes.len()
+        ));
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Path:");
+            ui.text_edit_singleline(&mut self.path);
+        });
+
+        let mut is_closed = false;
+
+        ui.horizontal(|ui| {
+            if ui.button("Open").clicked() {
+                self.status = Some(match LevelLayout::load(&self.path) {
+                    Ok(layout) => {
+                        self.layout = layout;
+                        Ok(format!("Opened `{}`", self.path))
+                    }
+
+                    Err(err) => Err(format!("{:#}", err)),
+                });
+            }
+
+            if ui.button("Save").clicked() {
+                self.status = Some(match self.layout.save(&self.path) {
+                    Ok(()) => Ok(format!("Saved `{}`", self.path)),
+                    Err(err) => Err(format!("{:#}", err)),
+                });
+            }
+
+            if ui.button("Play").clicked() {
+                let level: anyhow::Result<_> = try {
+                    self.layout.save(&self.path)?;
+                    Level::load(&self.path, rng)?
+                };
+
+                match level {
+                    Ok(level) => {
+                        start_tx.send(StartGameEvent { level });
+                        is_closed = true;
+                    }
+
+                    Err(err) => {
+                        self.status = Some(Err(format!("{:#}", err)));
+                    }
+                }
+            }
+
+            if ui.button("Exit").clicked() {
+                is_closed = true;
+            }
+        });
+
+        match &self.status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+
+            Some(Err(status)) => {
+                ui.colored_label(theme::ui::text_danger_egui(), status);
+            }
+
+            None => (),
+        }
+
+        is_closed
+    }
+}
+
+pub fn system(
+    mut lines: ResMut<DebugLines>,
+    mut egui: ResMut<EguiContext>,
+    mouse: Res<Input<MouseButton>>,
+    mouse_pos: Res<MousePos>,
+    mut rng: ResMut<GameRng>,
+    mut state: ResMut<UiState>,
+    mut start_tx: EventWriter<StartGameEvent>,
+) {
+    let editor = if let Some(editor) = &mut state.level_editor {
+        editor
+    } else {
+        return;
+    };
+
+    let mut is_closed = false;
+
+    egui::Window::new("Level editor")
+        .anchor(Align2::LEFT_TOP, (10.0, 10.0))
+        .resizable(false)
+        .collapsible(false)
+        .show(egui.ctx_mut(), |ui| {
+            is_closed = editor.ui(ui, &mut rng, &mut start_tx);
+        });
+
+    let cursor = if egui.ctx_mut().is_pointer_over_area() {
+        None
+    } else {
+        Some(Level::world_to_nearest_local(mouse_pos.0))
+    };
+
+    if let Some(cursor) = cursor {
+        editor.on_mouse(&mouse, cursor);
+    }
+
+    editor.draw(&mut lines, &mouse, cursor);
+
+    if is_closed {
+        state.level_editor = None;
+    }
+}
+
+/// Returns chamber centered at `center` that reaches `edge`
+fn chamber(center: LevelPoint, edge: LevelPoint) -> LevelChamber {
+    let r = ((edge - center).cast::<f32>().norm().round() as i32)
+        .clamp(MIN_CHAMBER_RADIUS, MAX_CHAMBER_RADIUS);
+
+    LevelChamber {
+        x: center.x,
+        y: center.y,
+        r,
+    }
+}
+
+fn corridor(from: LevelPoint, to: LevelPoint) -> LevelCorridor {
+    LevelCorridor {
+        x1: from.x,
+        y1: from.y,
+        x2: to.x,
+        y2: to.y,
+    }
+}
+
+fn draw_chamber(lines: &mut DebugLines, chamber: &LevelChamber) {
+    const SEGMENTS: usize = 64;
+
+    let center = Level::local_to_world(LevelPoint::new(chamber.x, chamber.y));
+    let r = chamber.r as f32 * Level::FIELD_SIZE;
+
+    let point = |idx: usize| {
+        let angle = TAU * (idx as f32) / (SEGMENTS as f32);
+
+        (center + Vec2::new(angle.cos(), angle.sin()) * r).extend(0.0)
+    };
+
+    for idx in 0..SEGMENTS {
+        lines.line_colored(point(idx), point(idx + 1), 0.0, Color::CYAN);
+    }
+}
+
+fn draw_corridor(lines: &mut DebugLines, corridor: &LevelCorridor) {
+    // Corridors bend at (x1, y2) - see `LevelCorridor::walls()`
+    let points = [
+        LevelPoint::new(corridor.x1, corridor.y1),
+        LevelPoint::new(corridor.x1, corridor.y2),
+        LevelPoint::new(corridor.x2, corridor.y2),
+    ]
+    .map(|pos| Level::local_to_world(pos).extend(0.0));
+
+    for [a, b] in points.array_windows() {
+        lines.line_colored(*a, *b, 0.0, Color::CYAN);
+    }
+}
+
+fn resource_label(resource: Option<LymphNodeResource>) -> String {
+    match resource {
+        Some(LymphNodeResource::Antigen(binder)) => {
+            format!("Antigen binder: {:?}", binder.0)
+        }
+        Some(LymphNodeResource::Body(body)) => format!("Body: {:?}", body),
+        Some(LymphNodeResource::Protein(protein)) => {
+            format!("Protein: {:?}", protein)
+        }
+        None => "Nothing".into(),
+    }
+}
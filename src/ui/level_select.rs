@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Align2};
+use bevy_egui::EguiContext;
+
+use super::level_editor::UiLevelEditor;
+use super::UiState;
+use crate::campaign::Campaign;
+use crate::game::{GameOutcome, GameState, StartGameEvent};
+use crate::level::{Difficulty, Level, LevelGeneratorKind};
+use crate::rng::GameRng;
+use crate::theme;
+
+#[derive(Default)]
+pub struct UiLevelSelect {
+    is_open: bool,
+    error: Option<String>,
+    generator: LevelGeneratorKind,
+    difficulty: Difficulty,
+
+    /// Difficulty profiles, loaded when the window gets opened for the first
+    /// time
+    difficulties: Option<Vec<Difficulty>>,
+}
+
+pub fn system(
+    state: Res<GameState>,
+    mut egui: ResMut<EguiContext>,
+    mut rng: ResMut<GameRng>,
+    mut campaign: ResMut<Campaign>,
+    mut ui_state: ResMut<UiState>,
+    mut start_tx: EventWriter<StartGameEvent>,
+    mut select: Local<UiLevelSelect>,
+) {
+    if state.tutorial || ui_state.level_editor.is_some() {
+        return;
+    }
+
+    if state.is_changed() && state.is_over() {
+        select.is_open = true;
+    }
+
+    egui::Window::new("Levels button")
+        .anchor(Align2::LEFT_TOP, (10.0, 10.0))
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(false)
+        .show(egui.ctx_mut(), |ui| {
+            if ui.selectable_label(select.is_open, "Levels").clicked() {
+                select.is_open = !select.is_open;
+            }
+        });
+
+    if !select.is_open {
+        return;
+    }
+
+    if select.difficulties.is_none() {
+        let difficulties = Difficulty::load_all().unwrap_or_else(|err| {
+            error!("{:?}", err);
+            select.error = Some(format!("{:#}", err));
+
+            vec![Difficulty::default()]
+        });
+
+        if let Some(difficulty) = difficulties
+            .iter()
+            .find(|difficulty| difficulty.name == select.difficulty.name)
+        {
+            select.difficulty = difficulty.clone();
+        }
+
+        select.difficulties = Some(difficulties);
+    }
+
+    let select = &mut *select;
+
+    // Level that's been picked, as an index into the campaign (`None` stands
+    // for the endless mode)
+    let mut picked = None;
+    let mut is_editor_opened = false;
+
+    egui::Window::new("Levels")
+        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+        .resizable(false)
+        .collapsible(false)
+        .show(egui.ctx_mut(), |ui| {
+            match state.outcome {
+                Some(GameOutcome::Victory) => {
+                    ui.heading("Victory!");
+                }
+                Some(GameOutcome::Defeat) => {
+                    ui.heading("Game over");
+                }
+                None => (),
+            }
+
+            if state.outcome == Some(GameOutcome::Victory) {
+                if let Some(next) = campaign.next() {
+                    if ui.button("Next level").clicked() {
+                        picked = Some(Some(next));
+                    }
+                }
+            }
+
+            ui.separator();
+
+            egui::Grid::new("levels").show(ui, |ui| {
+                for (idx, level) in campaign.levels.iter().enumerate() {
+                    ui.label(format!("{}. {}", idx + 1, level.name));
+
+                    if campaign.is_completed(idx) {
+                        ui.label("completed");
+                    } else if campaign.is_unlocked(idx) {
+                        ui.label("");
+                    } else {
+                        ui.label("locked");
+                    }
+
+                    if ui
+                        .add_enabled(
+                            campaign.is_unlocked(idx),
+                            egui::Button::new("Play"),
+                        )
+                        .clicked()
+                    {
+                        picked = Some(Some(idx));
+                    }
+
+                    ui.end_row();
+                }
+
+                ui.label("Endless");
+
+                egui::ComboBox::from_id_source("generator")
+                    .selected_text(select.generator.label())
+                    .show_ui(ui, |ui| {
+                        for generator in LevelGeneratorKind::variants() {
+                            ui.selectable_value(
+                                &mut select.generator,
+                                generator,
+                                generator.label(),
+                            );
+                        }
+                    });
+
+                egui::ComboBox::from_id_source("difficulty")
+                    .selected_text(&select.difficulty.name)
+                    .show_ui(ui, |ui| {
+                        for difficulty in select.difficulties.iter().flatten() {
+                            ui.selectable_value(
+                                &mut select.difficulty,
+                                difficulty.clone(),
+                                &difficulty.name,
+                            );
+                        }
+                    });
+
+                if ui.button("Play").clicked() {
+                    picked = Some(None);
+                }
+
+                ui.end_row();
+            });
+
+            if let Some(error) = &select.error {
+                ui.separator();
+                ui.colored_label(theme::ui::text_danger_egui(), error);
+            }
+
+            ui.separator();
+
+            if ui.button("Level editor").clicked() {
+                is_editor_opened = true;
+            }
+        });
+
+    if is_editor_opened {
+        campaign.current = None;
+        select.is_open = false;
+        ui_state.level_editor = Some(UiLevelEditor::new());
+        start_tx.send(StartGameEvent {
+            level: Level::empty(),
+        });
+
+        return;
+    }
+
+    let picked = if let Some(picked) = picked {
+        picked
+    } else {
+        return;
+    };
+
+    let level = if let Some(idx) = picked {
+        Level::load(&campaign.levels[idx].path, &mut *rng)
+    } else {
+        Ok(Level::start(
+            select.generator,
+            select.difficulty.clone(),
+            &mut *rng,
+        ))
+    };
+
+    match level {
+        Ok(level) => {
+            campaign.current = picked;
+            select.is_open = false;
+            select.error = None;
+            start_tx.send(StartGameEvent { level });
+        }
+
+        Err(err) => {
+            error!("{:?}", err);
+            select.error = Some(format!("{:#}", err));
+        }
+    }
+}
@@ -1,25 +1,61 @@
+mod lymph_node_blueprints;
 mod lymph_node_picker;
+mod lymph_node_preview;
+mod lymph_node_production;
 mod lymph_node_resource_radio;
 mod lymph_node_resource_radios;
 mod lymph_node_target_radios;
 
+use std::collections::BTreeMap;
+use std::mem;
+
 use bevy::prelude::*;
 use bevy_egui::egui::{self, Align2};
 use bevy_egui::EguiContext;
 
+use self::lymph_node_blueprints::*;
 use self::lymph_node_picker::*;
+use self::lymph_node_preview::*;
+use self::lymph_node_production::*;
 use self::lymph_node_resource_radio::*;
 use self::lymph_node_resource_radios::*;
 use self::lymph_node_target_radios::UiLymphNodeTargetRadios;
 use super::*;
-use crate::compiling::RecompileEvent;
+use crate::blueprints::{Blueprint, Blueprints};
+use crate::compiling::{
+    describe_product, CompilationDiagnostic, Compiler, Recipes, RecompileEvent,
+};
+use crate::level::{Level, LevelPoint};
 use crate::systems::bio::*;
+use crate::systems::immunity::ImmuneMemory;
+use crate::systems::units::Alignment;
 use crate::theme;
 
 pub struct UiLymphNodeEditor {
     alive: bool,
     lymph_node: Entity,
     lymph_node_picker: Option<UiLymphNodePicker>,
+
+    /// Whether the picker picks the node's rally point (as opposed to one of
+    /// its targets)
+    is_picking_rally: bool,
+
+    /// Rally point set by right-clicking, applied during the next
+    /// `process()`; `Some(None)` clears the rally point
+    pending_rally: Option<Option<LymphNodeRally>>,
+
+    /// Changes to the node's resource and targets; they don't affect the game
+    /// until the player applies them
+    draft: Option<UiLymphNodeDraft>,
+
+    /// Compiler used to preview the draft
+    preview_compiler: Compiler,
+
+    /// Name under which the copied blueprint gets saved
+    blueprint_name: String,
+
+    /// Reason why the most recent blueprint action has failed
+    blueprint_error: Option<String>,
 }
 
 impl UiLymphNodeEditor {
@@ -45,6 +81,12 @@ impl UiLymphNodeEditor {
             alive: true,
             lymph_node,
             lymph_node_picker: None,
+            is_picking_rally: false,
+            pending_rally: None,
+            draft: None,
+            preview_compiler: Default::default(),
+            blueprint_name: Default::default(),
+            blueprint_error: None,
         }
     }
 
@@ -54,9 +96,12 @@ impl UiLymphNodeEditor {
 
     pub fn process(
         &mut self,
-        lines: ResMut<DebugLines>,
+        mut lines: ResMut<DebugLines>,
         mut egui: ResMut<EguiContext>,
         textures: &UiTextures,
+        recipes: &Recipes,
+        memory: &ImmuneMemory,
+        blueprints: &mut Blueprints,
         mouse_pos: Vec2,
         lymph_nodes: &mut Query<(
             &mut LymphNode,
@@ -64,6 +109,7 @@ impl UiLymphNodeEditor {
             &Children,
             Entity,
         )>,
+        alignments: &Query<&Alignment, With<LymphNode>>,
         mut recompile_event_tx: EventWriter<RecompileEvent>,
     ) -> Poll<Option<Entity>> {
         if !self.alive {
@@ -73,14 +119,78 @@ impl UiLymphNodeEditor {
         let mut changed = false;
 
         if let Some(picker) = &mut self.lymph_node_picker {
-            return picker
-                .process(lines, mouse_pos, lymph_nodes, self.lymph_node)
-                .map(|node| {
-                    recompile_event_tx.send(RecompileEvent);
-                    node
-                });
+            let target = match picker.process(
+                &mut lines,
+                mouse_pos,
+                lymph_nodes,
+                self.lymph_node,
+            ) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(target) => target,
+            };
+
+            self.lymph_node_picker = None;
+
+            if mem::take(&mut self.is_picking_rally) {
+                if let Some(target) = target {
+                    self.pending_rally =
+                        Some(Some(LymphNodeRally::LymphNode(target)));
+                }
+            } else if let (Some(target), Ok((node, _, _, _))) =
+                (target, lymph_nodes.get(self.lymph_node))
+            {
+                let mut draft = self
+                    .draft
+                    .take()
+                    .unwrap_or_else(|| UiLymphNodeDraft::new(node));
+
+                if !draft.targets.contains(&target) {
+                    draft.targets.push(target);
+                }
+
+                self.draft = Some(draft);
+            }
+        }
+
+        if let Some(rally) = self.pending_rally.take() {
+            if let Ok((mut node, _, _, _)) =
+                lymph_nodes.get_mut(self.lymph_node)
+            {
+                node.rally = rally;
+            }
+        }
+
+        // Gathered up-front, since the editor's node (below) borrows the
+        // query mutably
+        let inputs = self.inputs(lymph_nodes);
+        let rally = self.rally(lymph_nodes);
+
+        let positions: BTreeMap<_, _> = lymph_nodes
+            .iter()
+            .map(|(node, _, _, entity)| (entity, node.pos))
+            .collect();
+
+        if let Some((from, to, _)) = rally {
+            lines.line(from.extend(5.0), to.extend(5.0), 0.0);
         }
 
+        let preview = if let Some(draft) = &self.draft {
+            UiLymphNodePreview::compile(
+                &mut self.preview_compiler,
+                recipes,
+                memory,
+                lymph_nodes,
+                self.lymph_node,
+                draft,
+            )
+        } else {
+            None
+        };
+
+        let mut unlinked = None;
+        let mut selected = None;
+        let mut blueprint_action = None;
+
         let (mut lymph_node, _, _, _) =
             if let Ok(val) = lymph_nodes.get_mut(self.lymph_node) {
                 val
@@ -88,6 +198,13 @@ impl UiLymphNodeEditor {
                 return Poll::Ready(None);
             };
 
+        let mut draft = self
+            .draft
+            .take()
+            .unwrap_or_else(|| UiLymphNodeDraft::new(&lymph_node));
+
+        let mut applied = false;
+        let mut cancelled = false;
         let mut keep_opened = true;
 
         egui::Window::new("Lymph Node")
@@ -102,14 +219,11 @@ impl UiLymphNodeEditor {
                         ui.vertical(|ui| {
                             ui.horizontal(|ui| {
                                 ui.vertical(|ui| {
-                                    let response =
-                                        ui.add(UiLymphNodeResourceRadios::new(
-                                            textures,
-                                            "Resource:",
-                                            &mut lymph_node.resource,
-                                        ));
-
-                                    changed |= response.changed();
+                                    ui.add(UiLymphNodeResourceRadios::new(
+                                        textures,
+                                        "Resource:",
+                                        &mut draft.resource,
+                                    ));
                                 });
 
                                 ui.add_space(6.0);
@@ -120,23 +234,55 @@ impl UiLymphNodeEditor {
                         ui.vertical(|ui| {
                             let mut requests_node_picker = false;
 
-                            let response =
-                                ui.add(UiLymphNodeTargetRadios::new(
-                                    &mut lymph_node.target,
-                                    &mut requests_node_picker,
-                                ));
-
-                            changed |= response.changed();
+                            ui.add(UiLymphNodeTargetRadios::new(
+                                &mut draft.targets,
+                                &positions,
+                                &mut requests_node_picker,
+                            ));
 
                             if requests_node_picker {
                                 self.lymph_node_picker =
                                     Some(UiLymphNodePicker::new());
+                                self.is_picking_rally = false;
                             }
                         });
                     });
 
                 ui.shrink_width_to_current();
 
+                ui.vertical(|ui| {
+                    ui.separator();
+                    ui.add_space(3.0);
+                    ui.label("Inputs (in the order they get merged):");
+
+                    if inputs.is_empty() {
+                        ui.label("No lymph node sends its products here.");
+                    }
+
+                    for (entity, pos, product) in &inputs {
+                        let stock = lymph_node
+                            .stock
+                            .get(entity)
+                            .copied()
+                            .unwrap_or_default();
+
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "[{}, {}]: {} ({}/{} buffered)",
+                                pos.x,
+                                pos.y,
+                                describe_product(product.as_ref()),
+                                stock,
+                                LymphNode::STOCK_CAPACITY,
+                            ));
+
+                            if ui.small_button("Unlink").clicked() {
+                                unlinked = Some(*entity);
+                            }
+                        });
+                    }
+                });
+
                 ui.vertical(|ui| {
                     ui.separator();
                     ui.add_space(3.0);
@@ -145,8 +291,113 @@ impl UiLymphNodeEditor {
                         ui.checkbox(&mut lymph_node.state.is_paused, "Paused");
 
                     changed |= response.changed();
+
+                    // Production settings don't affect the compilation, so
+                    // they are applied right away
+                    let mut production = lymph_node.production;
+
+                    show_production(ui, &mut production);
+
+                    if production != lymph_node.production {
+                        lymph_node.production = production;
+                    }
+
+                    ui.horizontal(|ui| {
+                        let label = rally
+                            .map(|(_, _, label)| label)
+                            .unwrap_or_else(|| "none".into());
+
+                        ui.label(format!("Rally point: {}", label));
+
+                        if ui.small_button("Pick node").clicked() {
+                            self.lymph_node_picker =
+                                Some(UiLymphNodePicker::new());
+                            self.is_picking_rally = true;
+                        }
+
+                        if lymph_node.rally.is_some()
+                            && ui.small_button("Clear").clicked()
+                        {
+                            lymph_node.rally = None;
+                        }
+                    });
+
+                    ui.label("(right-click on the map to place it)");
+
+                    match lymph_node.flow {
+                        LymphNodeFlow::Idle => (),
+
+                        LymphNodeFlow::Producing => {
+                            ui.label(format!(
+                                "Producing ({:.0}%)",
+                                100.0 * lymph_node.production_tt
+                                    / lymph_node.production_duration()
+                            ));
+                        }
+
+                        LymphNodeFlow::Capped => {
+                            ui.label(
+                                "Waiting - too many of the node's cells are \
+                                 alive",
+                            );
+                        }
+
+                        LymphNodeFlow::Finished => {
+                            ui.label("Finished - nothing left to produce");
+                        }
+
+                        LymphNodeFlow::Starved(parent) => {
+                            let pos = inputs
+                                .iter()
+                                .find(|(entity, _, _)| *entity == parent)
+                                .map(|(_, pos, _)| {
+                                    format!("[{}, {}]", pos.x, pos.y)
+                                })
+                                .unwrap_or_else(|| "its parent".into());
+
+                            ui.colored_label(
+                                theme::ui::text_danger_egui(),
+                                format!("Waiting for products of {}", pos),
+                            );
+                        }
+
+                        LymphNodeFlow::Blocked => {
+                            ui.colored_label(
+                                theme::ui::text_danger_egui(),
+                                "Blocked - none of the linked nodes has got \
+                                 room for more products",
+                            );
+                        }
+                    }
                 });
 
+                ui.vertical(|ui| {
+                    ui.separator();
+                    ui.add_space(3.0);
+
+                    show_blueprints(
+                        ui,
+                        blueprints,
+                        &mut self.blueprint_name,
+                        self.blueprint_error.as_deref(),
+                        &mut blueprint_action,
+                    );
+                });
+
+                if let Some(preview) = &preview {
+                    ui.vertical(|ui| {
+                        ui.separator();
+                        ui.add_space(3.0);
+                        preview.show(ui);
+                        ui.add_space(3.0);
+
+                        ui.horizontal(|ui| {
+                            applied = ui.button("Apply").clicked();
+                            cancelled = ui.button("Cancel").clicked();
+                        });
+                    });
+                }
+
                 if let Some(warning) = lymph_node.warning {
                     ui.add_space(3.0);
                     ui.separator();
@@ -156,13 +407,52 @@ impl UiLymphNodeEditor {
                         theme::ui::text_danger_egui(),
                         warning.description(),
                     );
+
+                    if let Some(diagnostic) = &lymph_node.diagnostic {
+                        ui.add_space(3.0);
+                        show_diagnostic(ui, diagnostic, &mut selected);
+                    }
                 }
             });
 
+        if applied {
+            draft.apply(&mut lymph_node);
+            changed = true;
+        }
+
+        self.draft = if applied || cancelled || draft.is_applied_to(&lymph_node)
+        {
+            None
+        } else {
+            Some(draft)
+        };
+
+        if let Some(parent) = unlinked {
+            if let Ok((mut parent, _, _, _)) = lymph_nodes.get_mut(parent) {
+                let entity = self.lymph_node;
+
+                parent.targets.retain(|&target| target != entity);
+                changed = true;
+            }
+        }
+
+        if let Some(action) = blueprint_action {
+            changed |= self.process_blueprint_action(
+                action,
+                blueprints,
+                lymph_nodes,
+                alignments,
+            );
+        }
+
         if changed {
             recompile_event_tx.send(RecompileEvent);
         }
 
+        if let Some(node) = selected {
+            return Poll::Ready(Some(node));
+        }
+
         if keep_opened {
             Poll::Pending
         } else {
@@ -170,6 +460,165 @@ impl UiLymphNodeEditor {
         }
     }
 
+    /// Returns the edited node's position, position of its rally point and
+    /// rally point's description - if the node has got a rally point.
+    fn rally(
+        &self,
+        lymph_nodes: &Query<(&mut LymphNode, &Transform, &Children, Entity)>,
+    ) -> Option<(Vec2, Vec2, String)> {
+        let (node, transform, _, _) = lymph_nodes.get(self.lymph_node).ok()?;
+        let rally = node.rally?;
+        let mut rally_node = None;
+
+        let pos = rally.pos(|entity| {
+            let (node, transform, _, _) = lymph_nodes.get(entity).ok()?;
+
+            rally_node = Some(node.pos);
+
+            Some(transform.translation.truncate())
+        })?;
+
+        let label = if let Some(pos) = rally_node {
+            format!("lymph node at [{}, {}]", pos.x, pos.y)
+        } else {
+            let pos = Level::world_to_nearest_local(pos);
+
+            format!("[{}, {}]", pos.x, pos.y)
+        };
+
+        Some((transform.translation.truncate(), pos, label))
+    }
+
+    /// Handles given blueprint action; returns whether any of the lymph nodes
+    /// has been changed.
+    fn process_blueprint_action(
+        &mut self,
+        action: UiBlueprintAction,
+        blueprints: &mut Blueprints,
+        lymph_nodes: &mut Query<(
+            &mut LymphNode,
+            &Transform,
+            &Children,
+            Entity,
+        )>,
+        alignments: &Query<&Alignment, With<LymphNode>>,
+    ) -> bool {
+        self.blueprint_error = None;
+
+        // Blueprints get copied from & pasted onto the player's nodes only
+        let nodes: Vec<_> = lymph_nodes
+            .iter()
+            .filter_map(|(node, _, _, entity)| {
+                let alignment = alignments.get(entity).ok()?;
+
+                alignment.is_player().then(|| (entity, node))
+            })
+            .collect();
+
+        match action {
+            UiBlueprintAction::Copy { chain } => {
+                blueprints.clipboard =
+                    Blueprint::copy(self.lymph_node, nodes, chain);
+
+                false
+            }
+
+            UiBlueprintAction::Paste(idx) => {
+                let blueprint = if let Some(idx) = idx {
+                    blueprints.saved().get(idx)
+                } else {
+                    blueprints.clipboard.as_ref()
+                };
+
+                let pasted = match blueprint
+                    .map(|blueprint| blueprint.paste(self.lymph_node, nodes))
+                {
+                    Some(Ok(pasted)) => pasted,
+
+                    Some(Err(err)) => {
+                        self.blueprint_error = Some(format!("{:#}", err));
+                        return false;
+                    }
+
+                    None => return false,
+                };
+
+                for pasted in pasted {
+                    if let Ok((mut node, _, _, _)) =
+                        lymph_nodes.get_mut(pasted.entity)
+                    {
+                        node.resource = pasted.resource;
+
+                        if let Some(targets) = pasted.targets {
+                            node.targets = targets;
+                        }
+                    }
+                }
+
+                self.draft = None;
+
+                true
+            }
+
+            UiBlueprintAction::Save => {
+                if let Err(err) = blueprints.save(&self.blueprint_name) {
+                    error!("{:?}", err);
+                    self.blueprint_error = Some(format!("{:#}", err));
+                }
+
+                false
+            }
+
+            UiBlueprintAction::Remove(idx) => {
+                if let Err(err) = blueprints.remove(idx) {
+                    error!("{:?}", err);
+                    self.blueprint_error = Some(format!("{:#}", err));
+                }
+
+                false
+            }
+        }
+    }
+
+    /// Returns lymph nodes linked into the edited one, along with their
+    /// positions and products.
+    fn inputs(
+        &self,
+        lymph_nodes: &Query<(&mut LymphNode, &Transform, &Children, Entity)>,
+    ) -> Vec<(Entity, LevelPoint, Option<LymphNodeProduct>)> {
+        let parents =
+            if let Ok((node, _, _, _)) = lymph_nodes.get(self.lymph_node) {
+                node.parents.clone()
+            } else {
+                return Default::default();
+            };
+
+        parents
+            .into_iter()
+            .filter_map(|parent| {
+                let (node, _, _, _) = lymph_nodes.get(parent).ok()?;
+
+                Some((parent, node.pos, node.product.clone()))
+            })
+            .collect()
+    }
+
+    pub fn on_right_clicked(&mut self, pos: Vec2, node: Option<Entity>) {
+        if self.lymph_node_picker.is_some() {
+            return;
+        }
+
+        // Rallying at the node itself is the same as not rallying at all
+        self.pending_rally = if node == Some(self.lymph_node) {
+            Some(None)
+        } else {
+            Some(Some(node.map_or(
+                LymphNodeRally::Point(pos),
+                LymphNodeRally::LymphNode,
+            )))
+        };
+    }
+
     pub fn on_escape_pressed(&mut self) {
         if let Some(picker) = &mut self.lymph_node_picker {
             picker.on_escape_pressed();
@@ -194,6 +643,9 @@ impl UiLymphNodeEditor {
             }
 
             self.lymph_node = node;
+            self.draft = None;
+            self.pending_rally = None;
+            self.blueprint_error = None;
 
             if let Ok(children) = lymph_nodes.get(self.lymph_node) {
                 Selector::modify(selectors, children, |selector| {
@@ -203,3 +655,34 @@ impl UiLymphNodeEditor {
         }
     }
 }
+
+/// Renders the diagnostic; clicking any of the nodes it refers to stores that
+/// node in `selected`.
+fn show_diagnostic(
+    ui: &mut egui::Ui,
+    diagnostic: &CompilationDiagnostic,
+    selected: &mut Option<Entity>,
+) {
+    for step in &diagnostic.steps {
+        ui.horizontal(|ui| {
+            ui.label(if step.is_failing { "->" } else { "  " });
+
+            if ui.small_button(step.label()).clicked() {
+                *selected = Some(step.node);
+            }
+
+            if let Some(input) = &step.input {
+                let text = describe_product(Some(input));
+
+                if step.is_failing {
+                    ui.colored_label(theme::ui::text_danger_egui(), text);
+                } else {
+                    ui.label(text);
+                }
+            }
+        });
+    }
+
+    ui.label(diagnostic.reason.as_str());
+    ui.label(format!("Suggestion: {}", diagnostic.suggestion));
+}
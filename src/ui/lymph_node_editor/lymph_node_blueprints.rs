@@ -0,0 +1,85 @@
+use bevy_egui::egui::Ui;
+
+use crate::blueprints::Blueprints;
+use crate::theme;
+
+/// What the player wants to do with blueprints
+#[derive(Clone, Copy, Debug)]
+pub enum UiBlueprintAction {
+    /// Copies the edited node or, if `chain` is set, the whole chain it's
+    /// part of
+    Copy {
+        chain: bool,
+    },
+
+    /// Pastes the clipboard (`None`) or given saved blueprint onto the edited
+    /// node
+    Paste(Option<usize>),
+
+    /// Saves the clipboard under the entered name
+    Save,
+
+    Remove(usize),
+}
+
+/// Renders the blueprints section of the lymph node editor; the chosen action
+/// gets stored in `action`.
+pub fn show_blueprints(
+    ui: &mut Ui,
+    blueprints: &Blueprints,
+    name: &mut String,
+    error: Option<&str>,
+    action: &mut Option<UiBlueprintAction>,
+) {
+    ui.label("Blueprints:");
+
+    ui.horizontal(|ui| {
+        if ui.button("Copy node").clicked() {
+            *action = Some(UiBlueprintAction::Copy { chain: false });
+        }
+
+        if ui.button("Copy chain").clicked() {
+            *action = Some(UiBlueprintAction::Copy { chain: true });
+        }
+
+        if let Some(clipboard) = &blueprints.clipboard {
+            let label = format!("Paste ({} nodes)", clipboard.node_count());
+
+            if ui.button(label).clicked() {
+                *action = Some(UiBlueprintAction::Paste(None));
+            }
+        }
+    });
+
+    if blueprints.clipboard.is_some() {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(name);
+
+            if ui.button("Save").clicked() {
+                *action = Some(UiBlueprintAction::Save);
+            }
+        });
+    }
+
+    for (idx, blueprint) in blueprints.saved().iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} ({} nodes)",
+                blueprint.name,
+                blueprint.node_count()
+            ));
+
+            if ui.small_button("Paste").clicked() {
+                *action = Some(UiBlueprintAction::Paste(Some(idx)));
+            }
+
+            if ui.small_button("Remove").clicked() {
+                *action = Some(UiBlueprintAction::Remove(idx));
+            }
+        });
+    }
+
+    if let Some(error) = error {
+        ui.colored_label(theme::ui::text_danger_egui(), error);
+    }
+}
@@ -2,7 +2,7 @@ use bevy::ecs::query::QueryEntityError;
 use bevy::prelude::*;
 use bevy_prototype_debug_lines::DebugLines;
 
-use crate::systems::bio::{LymphNode, LymphNodeTarget};
+use crate::systems::bio::LymphNode;
 use crate::ui::Poll;
 
 pub struct UiLymphNodePicker {
@@ -20,7 +20,7 @@ impl UiLymphNodePicker {
 
     pub fn process(
         &mut self,
-        mut lines: ResMut<DebugLines>,
+        lines: &mut DebugLines,
         mouse_pos: Vec2,
         lymph_nodes: &mut Query<(
             &mut LymphNode,
@@ -41,22 +41,11 @@ impl UiLymphNodePicker {
         }
 
         if let Some(target_node_entity) = self.target_node_entity {
+            // The link itself gets created by the editor, once the player
+            // applies their changes
             let result: Result<_, QueryEntityError> = try {
-                let (target_node, _, _, _) =
-                    lymph_nodes.get_mut(target_node_entity)?;
-
-                if let Some(parent_node_entity) = target_node.parent {
-                    let (mut parent_node, _, _, _) =
-                        lymph_nodes.get_mut(parent_node_entity)?;
-
-                    parent_node.target = LymphNodeTarget::Outside;
-                }
-
-                let (mut source_node, _, _, _) =
-                    lymph_nodes.get_mut(source_node_entity)?;
-
-                source_node.target =
-                    LymphNodeTarget::LymphNode(target_node_entity);
+                lymph_nodes.get(target_node_entity)?;
+                lymph_nodes.get(source_node_entity)?;
             };
 
             if result.is_err() {
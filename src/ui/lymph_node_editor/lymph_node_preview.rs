@@ -0,0 +1,196 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use bevy::prelude::*;
+use bevy_egui::egui::Ui;
+use itertools::Itertools;
+
+use crate::compiling::{describe_product, Compiler, Recipes};
+use crate::level::LevelPoint;
+use crate::systems::bio::{
+    AntigenBinder, LymphNode, LymphNodeProduct, LymphNodeResource,
+};
+use crate::systems::immunity::ImmuneMemory;
+use crate::theme;
+
+/// Changes made in the editor that haven't been applied yet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UiLymphNodeDraft {
+    pub resource: Option<LymphNodeResource>,
+    pub targets: Vec<Entity>,
+}
+
+impl UiLymphNodeDraft {
+    pub fn new(node: &LymphNode) -> Self {
+        Self {
+            resource: node.resource,
+            targets: node.targets.clone(),
+        }
+    }
+
+    pub fn apply(&self, node: &mut LymphNode) {
+        node.resource = self.resource;
+        node.targets = self.targets.clone();
+    }
+
+    pub fn is_applied_to(&self, node: &LymphNode) -> bool {
+        *self == Self::new(node)
+    }
+}
+
+/// What lymph nodes would produce if the draft got applied
+pub struct UiLymphNodePreview {
+    product: Option<LymphNodeProduct>,
+
+    /// Reason why the edited node wouldn't produce anything
+    reason: Option<String>,
+
+    /// Nodes downstream of the edited one (both before and after the change),
+    /// along with what they produce now and what they'd produce afterwards
+    downstream: Vec<(
+        LevelPoint,
+        Option<LymphNodeProduct>,
+        Option<LymphNodeProduct>,
+    )>,
+}
+
+impl UiLymphNodePreview {
+    /// Compiles a copy of the lymph nodes with the draft applied to the edited
+    /// one; the actual nodes remain untouched.
+    ///
+    /// `compiler` is kept by the editor between frames, so that only the first
+    /// preview has to compile everything.
+    pub fn compile(
+        compiler: &mut Compiler,
+        recipes: &Recipes,
+        memory: &ImmuneMemory,
+        lymph_nodes: &Query<(&mut LymphNode, &Transform, &Children, Entity)>,
+        entity: Entity,
+        draft: &UiLymphNodeDraft,
+    ) -> Option<Self> {
+        let (node, _, _, _) = lymph_nodes.get(entity).ok()?;
+        let mut edited = node.clone();
+
+        draft.apply(&mut edited);
+
+        compiler.remember(memory.memory_cells());
+        compiler.compile(
+            recipes,
+            lymph_nodes.iter().map(|(node, _, _, node_entity)| {
+                if node_entity == entity {
+                    (entity, &edited)
+                } else {
+                    (node_entity, node)
+                }
+            }),
+        );
+
+        let compiled = compiler.get(entity)?;
+        let mut downstream = Vec::new();
+        let mut visited = BTreeSet::from([entity]);
+
+        let mut pending: VecDeque<_> =
+            draft.targets.iter().chain(&node.targets).copied().collect();
+
+        while let Some(child) = pending.pop_front() {
+            if !visited.insert(child) {
+                continue;
+            }
+
+            let (child_node, _, _, _) = if let Ok(val) = lymph_nodes.get(child)
+            {
+                val
+            } else {
+                continue;
+            };
+
+            downstream.push((
+                child_node.pos,
+                child_node.product.clone(),
+                compiler.get(child).and_then(|node| node.product.clone()),
+            ));
+
+            pending.extend(&child_node.targets);
+        }
+
+        Some(Self {
+            product: compiled.product.clone(),
+            reason: compiled
+                .diagnostic
+                .as_ref()
+                .map(|diagnostic| diagnostic.reason.clone()),
+            downstream,
+        })
+    }
+
+    pub fn show(&self, ui: &mut Ui) {
+        ui.label(format!(
+            "After applying, this node would produce: {}",
+            describe_product_in_detail(self.product.as_ref())
+        ));
+
+        if let Some(reason) = &self.reason {
+            ui.colored_label(theme::ui::text_danger_egui(), reason.as_str());
+        }
+
+        if self.downstream.is_empty() {
+            return;
+        }
+
+        ui.add_space(3.0);
+        ui.label("Downstream nodes:");
+
+        for (pos, before, after) in &self.downstream {
+            let before = describe_product(before.as_ref());
+            let after = describe_product(after.as_ref());
+
+            if before == after {
+                ui.label(format!("[{}, {}]: {}", pos.x, pos.y, before));
+            } else {
+                ui.label(format!(
+                    "[{}, {}]: {} -> {}",
+                    pos.x, pos.y, before, after
+                ));
+            }
+        }
+    }
+}
+
+fn describe_product_in_detail(product: Option<&LymphNodeProduct>) -> String {
+    if let Some(LymphNodeProduct::Leukocyte(cell)) = product {
+        let AntigenBinder(binder) = cell.binder;
+
+        let proteins = if cell.proteins.is_empty() {
+            "no proteins".to_string()
+        } else {
+            cell.proteins
+                .iter()
+                .map(|protein| format!("{:?}", protein))
+                .join(", ")
+        };
+
+        let props = &cell.props;
+
+        let lifespan = if let Some(lifespan) = props.lifespan {
+            format!("lives {:.0}s", lifespan)
+        } else {
+            "lives until killed".to_string()
+        };
+
+        format!(
+            "{:?} leukocyte ({:?} body, {:?} binder, {}, {:.1} hp, {:.1}x \
+             speed, {:.1}x damage, {:.2} hp/s regen, {}, {:.1}x size)",
+            cell.kind,
+            cell.body,
+            binder,
+            proteins,
+            props.hp,
+            props.speed,
+            props.damage,
+            props.regen,
+            lifespan,
+            props.radius,
+        )
+    } else {
+        describe_product(product)
+    }
+}
@@ -0,0 +1,59 @@
+use bevy_egui::egui::{self, Ui};
+
+use crate::systems::bio::{LymphNodePriority, LymphNodeProduction};
+
+/// Default cap, used when the player enables it
+const DEFAULT_CAP: usize = 5;
+
+/// Default number of products to produce, used when the player enables it
+const DEFAULT_REMAINING: usize = 10;
+
+const MAX_COUNT: usize = 99;
+
+/// Renders the node's production settings.
+pub fn show_production(ui: &mut Ui, production: &mut LymphNodeProduction) {
+    ui.horizontal(|ui| {
+        ui.label("Priority:");
+
+        for priority in LymphNodePriority::variants() {
+            ui.selectable_value(
+                &mut production.priority,
+                priority,
+                format!("{:?}", priority),
+            );
+        }
+    });
+
+    show_count(
+        ui,
+        "Max. cells alive at once",
+        &mut production.cap,
+        DEFAULT_CAP,
+    );
+
+    show_count(
+        ui,
+        "Products left to produce",
+        &mut production.remaining,
+        DEFAULT_REMAINING,
+    );
+}
+
+fn show_count(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<usize>,
+    default: usize,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then(|| default);
+        }
+
+        if let Some(value) = value {
+            ui.add(egui::DragValue::new(value).clamp_range(0..=MAX_COUNT));
+        }
+    });
+}
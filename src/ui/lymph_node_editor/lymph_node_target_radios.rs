@@ -1,19 +1,25 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::Entity;
 use bevy_egui::egui::{Response, Ui, Widget};
 
-use crate::systems::bio::LymphNodeTarget;
+use crate::level::LevelPoint;
 
 pub struct UiLymphNodeTargetRadios<'a> {
-    current_value: &'a mut LymphNodeTarget,
+    current_value: &'a mut Vec<Entity>,
+    positions: &'a BTreeMap<Entity, LevelPoint>,
     requests_node_picker: &'a mut bool,
 }
 
 impl<'a> UiLymphNodeTargetRadios<'a> {
     pub fn new(
-        current_value: &'a mut LymphNodeTarget,
+        current_value: &'a mut Vec<Entity>,
+        positions: &'a BTreeMap<Entity, LevelPoint>,
         requests_node_picker: &'a mut bool,
     ) -> Self {
         Self {
             current_value,
+            positions,
             requests_node_picker,
         }
     }
@@ -31,36 +37,54 @@ impl Widget for UiLymphNodeTargetRadios<'_> {
 
                 ui.vertical(|ui| {
                     let response = ui.radio(
-                        matches!(self.current_value, LymphNodeTarget::Outside),
+                        self.current_value.is_empty(),
                         "Spawn products outside",
                     );
 
                     if response.clicked() {
-                        *self.current_value = LymphNodeTarget::Outside;
+                        self.current_value.clear();
                         changed = true;
                     }
                 });
 
                 ui.vertical(|ui| {
                     let response = ui.radio(
-                        matches!(
-                            self.current_value,
-                            LymphNodeTarget::LymphNode(_)
-                        ),
-                        "Send products to another node",
+                        !self.current_value.is_empty(),
+                        "Send products to other nodes",
                     );
 
                     if response.clicked() {
                         *self.requests_node_picker = true;
                     }
 
+                    let indent =
+                        ui.spacing().icon_width + ui.spacing().button_padding.x;
+
+                    let mut unlinked = None;
+
+                    for (idx, target) in self.current_value.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add_space(indent);
+
+                            if let Some(pos) = self.positions.get(target) {
+                                ui.label(format!("[{}, {}]", pos.x, pos.y));
+                            }
+
+                            if ui.small_button("Unlink").clicked() {
+                                unlinked = Some(idx);
+                            }
+                        });
+                    }
+
+                    if let Some(idx) = unlinked {
+                        self.current_value.remove(idx);
+                        changed = true;
+                    }
+
                     ui.horizontal(|ui| {
-                        ui.add_space(
-                            ui.spacing().icon_width
-                                + ui.spacing().button_padding.x,
-                        );
+                        ui.add_space(indent);
 
-                        if ui.button("Pick node").clicked() {
+                        if ui.button("Add node").clicked() {
                             *self.requests_node_picker = true;
                         }
                     });
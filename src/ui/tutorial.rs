@@ -1,9 +1,8 @@
 use bevy::prelude::*;
 use bevy_egui::egui::{TextStyle, Ui, WidgetText};
 use bevy_egui::{egui, EguiContext};
-use instant::{Duration, Instant};
 
-use crate::game::{GameState, LevelVm};
+use crate::game::{GameState, GameTime, LevelVm};
 use crate::tutorial::{TutorialState, TUTORIAL_STAGES};
 
 const LYMPH_TUTORIAL_PIC: u64 = 0;
@@ -29,6 +28,7 @@ pub fn load_assets(
 }
 
 pub fn system(
+    time: Res<GameTime>,
     mut game_state: ResMut<GameState>,
     mut tutorial_state: ResMut<TutorialState>,
     mut egui: ResMut<EguiContext>,
@@ -63,7 +63,7 @@ pub fn system(
                 game_state.tutorial = false;
 
                 game_state.vm = LevelVm::AwaitingStart {
-                    at: Instant::now() + Duration::from_secs(5),
+                    at: time.elapsed_seconds() + 5.0,
                 };
             }
 
@@ -96,7 +96,7 @@ pub fn system(
                 game_state.tutorial = false;
 
                 game_state.vm = LevelVm::AwaitingStart {
-                    at: Instant::now() + Duration::from_secs(5),
+                    at: time.elapsed_seconds() + 5.0,
                 };
             }
         }